@@ -0,0 +1,59 @@
+//! Compile time for machine-generated programs of growing size.
+//!
+//! Every statement introduces a new string and a new variable, so the
+//! string pool and variable table grow with the program. Throughput is
+//! reported per statement: with constant-time interning it stays flat as
+//! the program grows, i.e. compile time scales linearly.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use lyangpiler::{Compiler, Lexer, Parser};
+use std::hint::black_box;
+
+const SIZES: [usize; 4] = [1_000, 4_000, 16_000, 64_000];
+
+/// `statements` declarations and prints, each with its own string and variable.
+fn generated_program(statements: usize) -> String {
+    let mut source = String::new();
+    for i in 0..statements {
+        source.push_str(&format!("oi mug naam{} = \"shabda {}\"\n", i, i));
+        source.push_str(&format!("bol mug \"{}: \" + naam{}\n", i, i));
+    }
+    source
+}
+
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    group.sample_size(10);
+    for size in SIZES {
+        let source = generated_program(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &source, |b, source| {
+            b.iter_batched(
+                || Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap(),
+                |statements| Compiler::new().compile(black_box(statements)).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn pipeline(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex_parse_compile");
+    group.sample_size(10);
+    for size in SIZES {
+        let source = generated_program(size);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &source, |b, source| {
+            b.iter(|| {
+                let tokens = Lexer::new(black_box(source)).tokenize().unwrap();
+                let statements = Parser::new(tokens).parse().unwrap();
+                Compiler::new().compile(statements).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compile, pipeline);
+criterion_main!(benches);
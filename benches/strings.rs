@@ -0,0 +1,152 @@
+//! Time and memory for string-heavy programs on both VMs.
+//!
+//! Allocations are counted by a wrapper around the system allocator; the
+//! bytes each workload allocates in one run are printed before it is timed.
+//!
+//! - `string_building`: `s = s + "x"` in a loop. Throughput should stay flat
+//!   as the string grows, i.e. building is linear rather than quadratic.
+//! - `concat_chain`: one `+` chain of 64 copies of a 1 KB string.
+//! - `pool_constants`: a 1 KB literal assigned 10,000 times in a loop, which
+//!   should not copy the literal at all.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use lyangpiler::bytecode::Opcode::{self, *};
+use lyangpiler::register::{self, RegisterVM};
+use lyangpiler::span::Span;
+use lyangpiler::{BytecodeProgram, Compiler, Console, Lexer, Parser, VM};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size.saturating_sub(layout.size()), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn assemble(variables: &[&str], strings: &[&str], code: &[Opcode]) -> BytecodeProgram {
+    let mut program = BytecodeProgram::new();
+    for &name in variables {
+        program.add_variable(name.to_string());
+    }
+    for &string in strings {
+        program.add_string(string.to_string());
+    }
+    for &opcode in code {
+        program.add_instruction(opcode, Span::default());
+    }
+    program
+}
+
+/// Runs `body` and then `i = i + 1` until `i` is `count`. `i` is variable 0.
+fn repeat(count: i32, variables: &[&str], strings: &[&str], setup: &[Opcode], body: &[Opcode]) -> BytecodeProgram {
+    let start = setup.len() + 2;
+    let mut code = vec![PushNumber(0), StoreVariable(0)];
+    code.extend_from_slice(setup);
+    code.extend_from_slice(body);
+    code.extend_from_slice(&[
+        LoadVariable(0), PushNumber(1), Add, StoreVariable(0),
+        LoadVariable(0), PushNumber(count), NotEqual, JumpIfTrue(start),
+        Halt,
+    ]);
+    let mut names = vec!["i"];
+    names.extend_from_slice(variables);
+    assemble(&names, strings, &code)
+}
+
+fn string_building(length: usize) -> BytecodeProgram {
+    let s = 1;
+    repeat(
+        length as i32,
+        &["s"],
+        &["", "x"],
+        &[PushString(0), StoreVariable(s)],
+        &[LoadVariable(s), PushString(1), Concat, StoreVariable(s)],
+    )
+}
+
+fn concat_chain() -> BytecodeProgram {
+    let mut source = format!("oi mug x = \"{}\"\noi mug s = x", "k".repeat(1024));
+    for _ in 1..64 {
+        source.push_str(" + x");
+    }
+    let tokens = Lexer::new(&source).tokenize().unwrap();
+    Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap()
+}
+
+fn pool_constants() -> BytecodeProgram {
+    let text = "k".repeat(1024);
+    repeat(10_000, &["s"], &[&text], &[], &[PushString(0), StoreVariable(1)])
+}
+
+fn stack_vm(program: &BytecodeProgram) -> VM {
+    VM::new(program.clone()).with_console(Console::memory("").0)
+}
+
+fn register_vm(program: &register::RegisterProgram) -> RegisterVM {
+    RegisterVM::new(program.clone()).with_console(Console::memory("").0)
+}
+
+/// Bytes allocated while running `program` once on each VM.
+fn report_memory(name: &str, program: &BytecodeProgram) {
+    let lowered = register::lower(program).unwrap();
+    let mut vm = stack_vm(program);
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    vm.run().unwrap();
+    let stack = ALLOCATED.load(Ordering::Relaxed) - before;
+
+    let mut vm = register_vm(&lowered);
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    vm.run().unwrap();
+    let register = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!("{}: {} bytes allocated on the stack VM, {} on the register VM", name, stack, register);
+}
+
+fn time_both(c: &mut Criterion, group: &str, size: usize, program: &BytecodeProgram) {
+    let lowered = register::lower(program).unwrap();
+    let mut group = c.benchmark_group(group);
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(size as u64));
+    group.bench_with_input(BenchmarkId::new("stack", size), program, |b, program| {
+        b.iter_batched(|| stack_vm(program), |mut vm| vm.run().unwrap(), BatchSize::SmallInput)
+    });
+    group.bench_with_input(BenchmarkId::new("register", size), &lowered, |b, lowered| {
+        b.iter_batched(|| register_vm(lowered), |mut vm| vm.run().unwrap(), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn workloads(c: &mut Criterion) {
+    for length in [1_000, 4_000, 16_000] {
+        let program = string_building(length);
+        report_memory(&format!("string_building/{}", length), &program);
+        time_both(c, "string_building", length, &program);
+    }
+
+    let program = concat_chain();
+    report_memory("concat_chain", &program);
+    time_both(c, "concat_chain", 64, &program);
+
+    let program = pool_constants();
+    report_memory("pool_constants", &program);
+    time_both(c, "pool_constants", 10_000, &program);
+}
+
+criterion_group!(benches, workloads);
+criterion_main!(benches);
@@ -0,0 +1,112 @@
+//! Run time on the stack VM against the register VM.
+//!
+//! LyangLang has no loops yet, so each workload is assembled directly as
+//! bytecode in the shape the compiler will produce for a loop: the body,
+//! then a counter test and a `JumpIfTrue` back to the top. The register
+//! VM runs the same bytecode after `register::lower`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use lyangpiler::bytecode::Opcode::{self, *};
+use lyangpiler::register::{self, RegisterVM};
+use lyangpiler::span::Span;
+use lyangpiler::{BytecodeProgram, Console, Value, VM};
+
+fn assemble(variables: &[&str], strings: &[&str], code: &[Opcode]) -> BytecodeProgram {
+    let mut program = BytecodeProgram::new();
+    for &name in variables {
+        program.add_variable(name.to_string());
+    }
+    for &string in strings {
+        program.add_string(string.to_string());
+    }
+    for &opcode in code {
+        program.add_instruction(opcode, Span::default());
+    }
+    program
+}
+
+/// `s` = 0 + 1 + ... + 9999.
+fn loop_sum() -> BytecodeProgram {
+    let (i, s) = (0, 1);
+    assemble(
+        &["i", "s"],
+        &[],
+        &[
+            PushNumber(0), StoreVariable(i), PushNumber(0), StoreVariable(s),
+            // 4: s = s + i
+            LoadVariable(s), LoadVariable(i), Add, StoreVariable(s),
+            LoadVariable(i), PushNumber(1), Add, StoreVariable(i),
+            LoadVariable(i), PushNumber(10_000), NotEqual, JumpIfTrue(4),
+            Halt,
+        ],
+    )
+}
+
+/// `s` = 2000 `x`s, appended one at a time.
+fn string_building() -> BytecodeProgram {
+    let (s, i) = (0, 1);
+    assemble(
+        &["s", "i"],
+        &["", "x"],
+        &[
+            PushString(0), StoreVariable(s), PushNumber(0), StoreVariable(i),
+            // 4: s = s + "x"
+            LoadVariable(s), PushString(1), Concat, StoreVariable(s),
+            LoadVariable(i), PushNumber(1), Add, StoreVariable(i),
+            LoadVariable(i), PushNumber(2_000), NotEqual, JumpIfTrue(4),
+            Halt,
+        ],
+    )
+}
+
+/// The 30th Fibonacci number in `b`, computed iteratively 1000 times over.
+fn fibonacci() -> BytecodeProgram {
+    let (r, a, b, t, k) = (0, 1, 2, 3, 4);
+    assemble(
+        &["r", "a", "b", "t", "k"],
+        &[],
+        &[
+            PushNumber(0), StoreVariable(r),
+            // 2: outer loop
+            PushNumber(0), StoreVariable(a), PushNumber(1), StoreVariable(b), PushNumber(0), StoreVariable(k),
+            // 8: t = a + b; a = b; b = t
+            LoadVariable(a), LoadVariable(b), Add, StoreVariable(t),
+            LoadVariable(b), StoreVariable(a), LoadVariable(t), StoreVariable(b),
+            LoadVariable(k), PushNumber(1), Add, StoreVariable(k),
+            LoadVariable(k), PushNumber(30), NotEqual, JumpIfTrue(8),
+            LoadVariable(r), PushNumber(1), Add, StoreVariable(r),
+            LoadVariable(r), PushNumber(1_000), NotEqual, JumpIfTrue(2),
+            Halt,
+        ],
+    )
+}
+
+fn compare(c: &mut Criterion, name: &str, program: BytecodeProgram, variable: &str, expected: Value) {
+    let lowered = register::lower(&program).unwrap();
+    let stack_vm = || VM::new(program.clone()).with_console(Console::memory("").0);
+    let register_vm = || RegisterVM::new(lowered.clone()).with_console(Console::memory("").0);
+
+    // Both designs must compute the same thing before they are timed
+    let mut vm = stack_vm();
+    vm.run().unwrap();
+    assert_eq!(vm.get_global(variable), Some(&expected));
+    let mut vm = register_vm();
+    vm.run().unwrap();
+    assert_eq!(vm.get_global(variable), Some(&expected));
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("stack", |b| b.iter_batched(stack_vm, |mut vm| vm.run().unwrap(), BatchSize::SmallInput));
+    group.bench_function("register", |b| {
+        b.iter_batched(register_vm, |mut vm| vm.run().unwrap(), BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn workloads(c: &mut Criterion) {
+    compare(c, "loop_sum", loop_sum(), "s", Value::Number(49_995_000));
+    compare(c, "string_building", string_building(), "s", Value::from("x".repeat(2_000)));
+    compare(c, "fibonacci", fibonacci(), "b", Value::Number(1_346_269));
+}
+
+criterion_group!(benches, workloads);
+criterion_main!(benches);
@@ -91,7 +91,7 @@ try {
     Write-Host "--- lyangpiler --help ---" -ForegroundColor Gray
     & $installedExe --help
     Write-Host ""
-    Write-Host "Try: lyangpiler new demo; cd demo; lyangpiler run main.nbh --vm"
+    Write-Host "Try: lyangpiler new demo; cd demo; lyangpiler run main.nbh --vm --allow-stdin --allow-stdout"
     Write-Host "https://github.com/$repo" -ForegroundColor Blue
 }
 finally {
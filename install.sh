@@ -218,6 +218,6 @@ echo "  source ${ENABLE_SH} && lyangpiler --help"
 echo ""
 echo "Or open a new terminal, then:"
 echo "  lyangpiler --help"
-echo "  lyangpiler new demo && cd demo && lyangpiler run main.nbh --vm"
+echo "  lyangpiler new demo && cd demo && lyangpiler run main.nbh --vm --allow-stdin --allow-stdout"
 echo ""
 echo -e "${BLUE}https://github.com/${REPO}${NC}"
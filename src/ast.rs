@@ -1,31 +1,70 @@
-/// One segment in a string concatenation on the right-hand side of `=`.
+use crate::native::Type;
+use crate::span::Spanned;
+
+/// A variable name together with where it appears in the source.
+pub type Ident = Spanned<String>;
+
+/// One segment in a string concatenation on the right-hand side of `=`
+/// or in a `bol mug` statement.
 #[derive(Debug, Clone)]
 pub enum StrSegment {
     Literal(String),
-    Identifier(String),
+    Identifier(Ident),
+}
+
+/// An argument to a native function call.
+#[derive(Debug, Clone)]
+pub enum Argument {
+    Variable(Ident),
+    Literal(Value),
 }
 
 #[derive(Debug)]
 pub enum Statement {
-    Declaration(String, Value),
-    Addition(String, Vec<String>),
-    Subtraction(String, Vec<String>),
-    Multiplication(String, Vec<String>),
-    Division(String, Vec<String>),
-    StringConcat(String, Vec<StrSegment>),
-    Print(String),
-    PrintString(Vec<String>),
-    Input(String),
-    If(Condition, Vec<Statement>, Option<Box<Statement>>),  // Added Option<Box<Statement>> for else branch
+    Declaration(Ident, Value),
+    Addition(Ident, Vec<Ident>),
+    Subtraction(Ident, Vec<Ident>),
+    Multiplication(Ident, Vec<Ident>),
+    Division(Ident, Vec<Ident>),
+    StringConcat(Ident, Vec<StrSegment>),
+    Print(Ident),
+    PrintString(Vec<StrSegment>),
+    Input(Ident),
+    If(Condition, Vec<Spanned<Statement>>, Option<Box<Spanned<Statement>>>),  // Added Option<Box<Statement>> for else branch
+    /// `mug <function> args [lai target]`
+    Call(Ident, Vec<Argument>, Option<Ident>),
+    /// `oi mug` with a type annotation, as in `oi mug umer: sankhya = 18`;
+    /// runs as the statement it wraps, which has the same span.
+    Annotated(Annotation, Box<Spanned<Statement>>),
+}
+
+/// The type a declaration says a variable holds, as in `oi mug umer: sankhya = 18`.
+///
+/// Annotations do not change how a program runs; `lyangpiler check --types`
+/// reports values of any other type given to the variable.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub name: Ident,
+    pub kind: Spanned<Type>,
+}
+
+impl Annotation {
+    /// The type names annotations can use.
+    pub const NAMES: [(&'static str, Type); 2] = [("sankhya", Type::Number), ("shabda", Type::String)];
+
+    /// How `kind` is written in annotations, e.g. `sankhya`.
+    pub fn name_of(kind: Type) -> Option<&'static str> {
+        Self::NAMES.iter().find(|(_, named)| *named == kind).map(|(name, _)| *name)
+    }
 }
 
 #[derive(Debug)]
 pub enum Condition {
-    Equals(String, String),
-    NotEquals(String, String),
+    Equals(Ident, String),
+    NotEquals(Ident, String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
     Number(i32),
     String(String),
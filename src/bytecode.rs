@@ -1,7 +1,11 @@
+use crate::span::Span;
+use std::collections::HashMap;
+use std::ops::Deref;
+
 /// Bytecode module for LyangLang virtual machine (Lyangpiler)
 /// Defines bytecode instructions that the VM will execute
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(dead_code)] // Some opcodes may not be used yet but will be in future extensions
 pub enum Opcode {
     // Stack operations
@@ -32,6 +36,9 @@ pub enum Opcode {
     JumpIfFalse(usize),   // Jump to instruction if top of stack is false
     Jump(usize),          // Unconditional jump to instruction
     
+    // Native functions
+    CallNative(usize, usize), // Index into native function names, argument count
+
     // Comparison
     Equal,
     NotEqual,
@@ -41,66 +48,168 @@ pub enum Opcode {
     Halt,
 }
 
+impl Opcode {
+    /// Where a `Jump`, `JumpIfTrue` or `JumpIfFalse` may continue.
+    pub fn jump_target(self) -> Option<usize> {
+        match self {
+            Opcode::Jump(target) | Opcode::JumpIfTrue(target) | Opcode::JumpIfFalse(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// The same jump with a different target. Other opcodes are unchanged.
+    pub fn with_jump_target(self, target: usize) -> Opcode {
+        match self {
+            Opcode::Jump(_) => Opcode::Jump(target),
+            Opcode::JumpIfTrue(_) => Opcode::JumpIfTrue(target),
+            Opcode::JumpIfFalse(_) => Opcode::JumpIfFalse(target),
+            other => other,
+        }
+    }
+
+    /// Offsets of the instructions that can run after this one, which is at
+    /// `offset`. An offset past the last instruction ends the program.
+    pub fn successors(self, offset: usize) -> Vec<usize> {
+        match self {
+            Opcode::Halt => vec![],
+            Opcode::Jump(target) => vec![target],
+            Opcode::JumpIfTrue(target) | Opcode::JumpIfFalse(target) => vec![offset + 1, target],
+            _ => vec![offset + 1],
+        }
+    }
+}
+
 /// A single instruction in the bytecode program
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Instruction {
     pub opcode: Opcode,
-    #[allow(dead_code)] // Line number is used for debugging but not directly read
+    /// Source line of the statement, where the debugger stops
     pub line_number: usize,
+    /// Source of the statement this instruction was compiled from
+    pub span: Span,
 }
 
 impl Instruction {
-    pub fn new(opcode: Opcode, line_number: usize) -> Self {
-        Self { opcode, line_number }
+    pub fn new(opcode: Opcode, span: Span) -> Self {
+        Self { opcode, line_number: span.line, span }
+    }
+}
+
+/// A table of distinct strings, each found by its index or looked up by
+/// value in constant time. Reads go through `Deref<Target = [String]>`.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: Vec<String>,
+    indexes: HashMap<String, usize>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The index of `string`, adding it first if it is new.
+    pub fn intern(&mut self, string: String) -> usize {
+        if let Some(&index) = self.indexes.get(&string) {
+            return index;
+        }
+        let index = self.strings.len();
+        self.indexes.insert(string.clone(), index);
+        self.strings.push(string);
+        index
+    }
+
+    /// The index of `string`, if it has been added.
+    pub fn lookup(&self, string: &str) -> Option<usize> {
+        self.indexes.get(string).copied()
+    }
+}
+
+impl Deref for Interner {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.strings
+    }
+}
+
+impl PartialEq for Interner {
+    fn eq(&self, other: &Self) -> bool {
+        self.strings == other.strings
+    }
+}
+
+/// Builds a table from strings in index order. Repeated strings keep their
+/// slots, and lookups find the first one.
+impl From<Vec<String>> for Interner {
+    fn from(strings: Vec<String>) -> Self {
+        let mut indexes = HashMap::with_capacity(strings.len());
+        for (index, string) in strings.iter().enumerate() {
+            indexes.entry(string.clone()).or_insert(index);
+        }
+        Self { strings, indexes }
     }
 }
 
 /// Bytecode program - a sequence of instructions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BytecodeProgram {
     pub instructions: Vec<Instruction>,
-    pub string_pool: Vec<String>,
-    pub variable_names: Vec<String>,
+    pub string_pool: Interner,
+    pub variable_names: Interner,
+    /// Native functions the program calls, by name
+    pub native_names: Interner,
 }
 
 impl BytecodeProgram {
     pub fn new() -> Self {
         Self {
             instructions: Vec::new(),
-            string_pool: Vec::new(),
-            variable_names: Vec::new(),
+            string_pool: Interner::new(),
+            variable_names: Interner::new(),
+            native_names: Interner::new(),
         }
     }
 
-    pub fn add_instruction(&mut self, opcode: Opcode, line_number: usize) {
-        self.instructions.push(Instruction::new(opcode, line_number));
+    pub fn add_instruction(&mut self, opcode: Opcode, span: Span) {
+        self.instructions.push(Instruction::new(opcode, span));
     }
     
     pub fn add_string(&mut self, string: String) -> usize {
-        // Check if string already exists in pool
-        for (i, s) in self.string_pool.iter().enumerate() {
-            if s == &string {
-                return i;
-            }
-        }
-        
-        // Add new string to pool
-        let index = self.string_pool.len();
-        self.string_pool.push(string);
-        index
+        self.string_pool.intern(string)
     }
     
     pub fn add_variable(&mut self, name: String) -> usize {
-        // Check if variable already exists
-        for (i, var_name) in self.variable_names.iter().enumerate() {
-            if var_name == &name {
-                return i;
-            }
-        }
-        
-        // Add new variable
-        let index = self.variable_names.len();
-        self.variable_names.push(name);
-        index
+        self.variable_names.intern(name)
+    }
+
+    pub fn add_native(&mut self, name: String) -> usize {
+        self.native_names.intern(name)
+    }
+}
+
+impl Default for BytecodeProgram {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_returns_the_first_index_of_each_string() {
+        let mut program = BytecodeProgram::new();
+        assert_eq!(program.add_string("rato".to_string()), 0);
+        assert_eq!(program.add_string("nilo".to_string()), 1);
+        assert_eq!(program.add_string("rato".to_string()), 0);
+        assert_eq!(program.string_pool[..], ["rato", "nilo"]);
+        assert_eq!(program.string_pool.lookup("nilo"), Some(1));
+        assert_eq!(program.string_pool.lookup("hariyo"), None);
+
+        let mut loaded = Interner::from(vec!["a".to_string(), "b".to_string(), "a".to_string()]);
+        assert_eq!(loaded.lookup("a"), Some(0));
+        assert_eq!(loaded.intern("c".to_string()), 3);
+    }
+}
@@ -0,0 +1,201 @@
+//! What a running program may do outside itself.
+//!
+//! Every engine checks a [`Capabilities`] set before a program prints, reads
+//! input, or calls a native function that touches files, the clock or
+//! randomness. An operation that was not granted stops the program with a
+//! `PermissionDenied` error naming the missing [`Capability`].
+//!
+//! Nothing is granted by default, not even reading input and printing: the
+//! host grants each capability a program needs, so a public playground runs
+//! untrusted programs with only what it chose to give them.
+
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// One thing a program may be allowed to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Capability {
+    /// Read lines of input with `oi mug bhan`
+    Stdin,
+    /// Print with `bol mug`
+    Stdout,
+    /// Read the file at this path
+    Read(PathBuf),
+    /// Create or overwrite the file at this path
+    Write(PathBuf),
+    /// Read the current time
+    Clock,
+    /// Draw random numbers
+    Random,
+}
+
+impl Capability {
+    /// The `lyangpiler run` flag that grants this capability.
+    pub fn flag(&self) -> String {
+        match self {
+            Capability::Stdin => "--allow-stdin".to_string(),
+            Capability::Stdout => "--allow-stdout".to_string(),
+            Capability::Read(path) => format!("--allow-read {}", path.display()),
+            Capability::Write(path) => format!("--allow-write {}", path.display()),
+            Capability::Clock => "--allow-clock".to_string(),
+            Capability::Random => "--allow-random".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Capability::Stdin => f.write_str("stdin"),
+            Capability::Stdout => f.write_str("stdout"),
+            Capability::Read(path) => write!(f, "read {}", path.display()),
+            Capability::Write(path) => write!(f, "write {}", path.display()),
+            Capability::Clock => f.write_str("clock"),
+            Capability::Random => f.write_str("random"),
+        }
+    }
+}
+
+/// The capabilities granted to a program. The default grants nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub stdin: bool,
+    pub stdout: bool,
+    /// Files and directories that may be read, including everything inside
+    /// a directory
+    pub read: Vec<PathBuf>,
+    /// Files and directories that may be written, as for `read`
+    pub write: Vec<PathBuf>,
+    pub clock: bool,
+    pub random: bool,
+}
+
+impl Capabilities {
+    /// Grants nothing, not even the console.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Grants reading input and printing, and nothing else.
+    pub fn console() -> Self {
+        Self::none().with_stdin().with_stdout()
+    }
+
+    pub fn with_stdin(mut self) -> Self {
+        self.stdin = true;
+        self
+    }
+
+    pub fn with_stdout(mut self) -> Self {
+        self.stdout = true;
+        self
+    }
+
+    /// Allows reading `path`, or anything inside it if it is a directory.
+    pub fn with_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read.push(path.into());
+        self
+    }
+
+    /// Allows writing `path`, or anything inside it if it is a directory.
+    pub fn with_write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.write.push(path.into());
+        self
+    }
+
+    pub fn with_clock(mut self) -> Self {
+        self.clock = true;
+        self
+    }
+
+    pub fn with_random(mut self) -> Self {
+        self.random = true;
+        self
+    }
+
+    pub fn allows(&self, capability: &Capability) -> bool {
+        match capability {
+            Capability::Stdin => self.stdin,
+            Capability::Stdout => self.stdout,
+            Capability::Read(path) => covers(&self.read, path),
+            Capability::Write(path) => covers(&self.write, path),
+            Capability::Clock => self.clock,
+            Capability::Random => self.random,
+        }
+    }
+
+    /// The `PermissionDenied` error for `capability` unless it is granted.
+    /// The error has no span yet.
+    pub fn check(&self, capability: &Capability) -> Result<(), Diagnostic> {
+        if self.allows(capability) {
+            return Ok(());
+        }
+        Err(Diagnostic::new(ErrorCode::PermissionDenied)
+            .with_arg(capability)
+            .with_arg(capability.flag()))
+    }
+}
+
+/// Whether `path` is one of `grants` or inside one. Both sides are resolved
+/// first, so `..` and symbolic links cannot lead outside a grant.
+fn covers(grants: &[PathBuf], path: &Path) -> bool {
+    let Some(path) = resolve(path) else {
+        return false;
+    };
+    grants
+        .iter()
+        .filter_map(|grant| resolve(grant))
+        .any(|grant| path.starts_with(grant))
+}
+
+/// The absolute path `path` names, with links and `..` followed. A file that
+/// does not exist yet resolves through its directory. `None` if the path
+/// cannot be resolved safely.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    // A path ending in `..` has no file name, and its directory is unknown
+    let name = path.file_name()?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(resolve(parent)?.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denies_what_was_not_granted() {
+        assert!(!Capabilities::default().allows(&Capability::Stdout));
+        let capabilities = Capabilities::console();
+        assert!(capabilities.allows(&Capability::Stdout));
+        assert!(!capabilities.allows(&Capability::Clock));
+
+        let err = Capabilities::none().check(&Capability::Stdout).unwrap_err();
+        assert_eq!(err.message(), "permission denied: this needs the `stdout` capability");
+        assert_eq!(err.help_in(crate::messages::Lang::En).unwrap(), "grant it with `--allow-stdout`");
+    }
+
+    #[test]
+    fn file_grants_cover_their_contents_only() {
+        let dir = std::env::temp_dir().join(format!("lyangpiler-capability-{}", std::process::id()));
+        let inside = dir.join("inside");
+        std::fs::create_dir_all(&inside).unwrap();
+        let capabilities = Capabilities::none().with_read(&inside).with_write(inside.join("out.txt"));
+
+        assert!(capabilities.allows(&Capability::Read(inside.join("notes.txt"))));
+        assert!(capabilities.allows(&Capability::Read(inside.join("new/deeper.txt"))));
+        assert!(!capabilities.allows(&Capability::Read(dir.join("secret.txt"))));
+        assert!(!capabilities.allows(&Capability::Read(inside.join("../secret.txt"))));
+        assert!(!capabilities.allows(&Capability::Read(inside.join("missing/../../secret.txt"))));
+
+        assert!(capabilities.allows(&Capability::Write(inside.join("out.txt"))));
+        assert!(!capabilities.allows(&Capability::Write(inside.join("other.txt"))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
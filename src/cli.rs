@@ -0,0 +1,753 @@
+use clap::{Args, Parser, Subcommand};
+use crate::capability::Capabilities;
+use crate::console::{self, Console};
+use crate::debugger::Debugger;
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::limits::Limits;
+use crate::messages::{self, Lang};
+use crate::repl::{self, Session};
+use crate::lint::{self, Severity};
+use crate::{dap, disasm, formatter, lsp, nbc, optimizer, register, typecheck, verifier};
+use crate::{BytecodeProgram, Compiler, Interpreter, Lexer, Natives, NepalError, Parser as LyangParser, RegisterVM, VM};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Input file to process
+    #[arg(value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Use VM mode for execution
+    #[arg(short, long)]
+    vm: bool,
+
+    /// Text shown when the program asks for input
+    #[arg(long, value_name = "TEXT", default_value = console::DEFAULT_PROMPT)]
+    prompt: String,
+
+    #[command(flatten)]
+    capabilities: CapabilityArgs,
+
+    /// Language for error messages: en, ne or ne-rom (default: $LYANGPILER_LANG, then en)
+    #[arg(long, global = true, value_name = "LANG")]
+    lang: Option<Lang>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run a LyangLang program
+    Run {
+        /// Input file to run; `.nbc` files always run on the VM
+        file: PathBuf,
+        
+        /// Use VM mode
+        #[arg(short, long)]
+        vm: bool,
+
+        /// Text shown when the program asks for input
+        #[arg(long, value_name = "TEXT", default_value = console::DEFAULT_PROMPT)]
+        prompt: String,
+
+        /// Optimize the bytecode (implies --vm)
+        #[arg(short = 'O', long)]
+        optimize: bool,
+
+        /// Run on the register-based VM (implies --vm)
+        #[arg(long)]
+        registers: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+    
+    /// Compile a program to a `.nbc` bytecode file
+    Build {
+        /// Input file to compile
+        file: PathBuf,
+
+        /// Where to write the bytecode (default: FILE with the extension `.nbc`)
+        #[arg(short, long, value_name = "OUTPUT")]
+        output: Option<PathBuf>,
+
+        /// Optimize the bytecode
+        #[arg(short = 'O', long)]
+        optimize: bool,
+    },
+
+    /// Print the bytecode of a program (`.nbh` or `.nbc`)
+    Disasm {
+        /// Program to disassemble
+        file: PathBuf,
+
+        /// Print JSON for tools instead of a listing
+        #[arg(long)]
+        json: bool,
+
+        /// Show the optimized bytecode
+        #[arg(short = 'O', long)]
+        optimize: bool,
+    },
+
+    /// Create a new LyangLang project
+    New {
+        /// Project name
+        name: String,
+    },
+
+    /// Check a program for errors without running it
+    Check {
+        /// Input file to check
+        file: PathBuf,
+
+        /// Also infer each variable's type and report values used as the wrong type
+        #[arg(long)]
+        types: bool,
+    },
+
+    /// Rewrite programs in the standard layout
+    Fmt {
+        /// Files to format, or directories to search for `.nbh` files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Fail if a program is not formatted instead of changing it, e.g. in CI
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Look for likely mistakes in programs without running them
+    Lint {
+        /// Files to lint, or directories to search for `.nbh` files
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Apply the suggested fixes
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Start an interactive session
+    Repl {
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+
+    /// Step through a program with breakpoints, watches and variable inspection
+    Debug {
+        /// Program to debug
+        file: PathBuf,
+
+        /// Text shown when the program asks for input
+        #[arg(long, value_name = "TEXT", default_value = console::DEFAULT_PROMPT)]
+        prompt: String,
+
+        #[command(flatten)]
+        capabilities: CapabilityArgs,
+    },
+
+    /// Serve the Debug Adapter Protocol on stdin and stdout, for editors such as VS Code
+    Dap,
+
+    /// Serve the Language Server Protocol on stdin and stdout, for errors, hover and completion in editors
+    Lsp,
+}
+
+/// Caps for running untrusted programs; see [`Limits`].
+#[derive(Args)]
+struct LimitArgs {
+    /// Stop after this many steps (VM instructions or interpreter statements)
+    #[arg(long, value_name = "N")]
+    max_steps: Option<u64>,
+
+    /// Stop after this many seconds, e.g. `2` or `0.5`
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    /// Most stack slots (nested blocks on the interpreter) the program may use
+    #[arg(long, value_name = "N")]
+    max_stack: Option<usize>,
+
+    /// Most bytes of text the program's variables may hold
+    #[arg(long, value_name = "BYTES")]
+    max_heap: Option<usize>,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> Limits {
+        Limits {
+            max_steps: self.max_steps,
+            timeout: self.timeout,
+            max_stack: self.max_stack,
+            max_heap: self.max_heap,
+        }
+    }
+}
+
+/// What the program may do outside itself; see [`Capabilities`].
+/// Nothing is granted without these flags, not even reading input and printing.
+#[derive(Args)]
+struct CapabilityArgs {
+    /// Let the program read input
+    #[arg(long)]
+    allow_stdin: bool,
+
+    /// Let the program print
+    #[arg(long)]
+    allow_stdout: bool,
+
+    /// Let the program read PATH, or anything inside it if it is a directory
+    #[arg(long, value_name = "PATH")]
+    allow_read: Vec<PathBuf>,
+
+    /// Let the program create or overwrite PATH, or anything inside it if it is a directory
+    #[arg(long, value_name = "PATH")]
+    allow_write: Vec<PathBuf>,
+
+    /// Let the program read the current time
+    #[arg(long)]
+    allow_clock: bool,
+
+    /// Let the program draw random numbers
+    #[arg(long)]
+    allow_random: bool,
+}
+
+impl CapabilityArgs {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            stdin: self.allow_stdin,
+            stdout: self.allow_stdout,
+            read: self.allow_read.clone(),
+            write: self.allow_write.clone(),
+            clock: self.allow_clock,
+            random: self.allow_random,
+        }
+    }
+}
+
+fn parse_seconds(text: &str) -> Result<Duration, String> {
+    let seconds: f64 = text.parse().map_err(|_| format!("`{}` is not a number of seconds", text))?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("`{}` is not a number of seconds", text))
+}
+
+/// An error together with the source it refers to, so it can be rendered
+/// with code excerpts.
+struct Report {
+    error: NepalError,
+    source: Option<(String, String)>,
+}
+
+impl From<NepalError> for Report {
+    fn from(error: NepalError) -> Self {
+        Report { error, source: None }
+    }
+}
+
+impl Report {
+    fn render(&self, lang: Lang) -> String {
+        match &self.source {
+            Some((file_name, source)) => self.error.render(source, file_name, lang),
+            None => self.error.render("", "", lang),
+        }
+    }
+}
+
+/// Attaches the source of `file` to an error for rendering.
+fn in_file<'a>(file: &'a Path, source: &'a str) -> impl Fn(NepalError) -> Report + 'a {
+    move |error| Report {
+        error,
+        source: Some((file.display().to_string(), source.to_string())),
+    }
+}
+
+/// Parses the command line and runs the command it names.
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let lang = cli.lang.or_else(Lang::from_env).unwrap_or_default();
+
+    let result = match cli.command {
+        Some(Commands::Run { file, vm, prompt, optimize, registers, limits, capabilities }) => {
+            let use_vm = vm || optimize || registers;
+            run_program(&file, use_vm, optimize, registers, limits.limits(), capabilities.capabilities(), &prompt)
+        },
+        Some(Commands::Build { file, output, optimize }) => {
+            let output = output.unwrap_or_else(|| file.with_extension("nbc"));
+            build_program(&file, &output, optimize)
+        },
+        Some(Commands::Disasm { file, json, optimize }) => {
+            disassemble_program(&file, json, optimize)
+        },
+        Some(Commands::New { name }) => {
+            create_project(&name).map_err(Report::from)
+        },
+        Some(Commands::Check { file, types }) => {
+            check_program(&file, types)
+        },
+        Some(Commands::Fmt { paths, check }) => {
+            format_programs(&paths, check)
+        },
+        Some(Commands::Lint { paths, fix }) => match lint_programs(&paths, fix, lang) {
+            Ok(true) => Ok(()),
+            // The findings are already printed
+            Ok(false) => return ExitCode::FAILURE,
+            Err(report) => Err(report),
+        },
+        Some(Commands::Repl { capabilities }) => match run_repl(capabilities.capabilities(), lang) {
+            Ok(true) => Ok(()),
+            // The refusals are already printed
+            Ok(false) => return ExitCode::FAILURE,
+            Err(error) => Err(error.into()),
+        },
+        Some(Commands::Debug { file, prompt, capabilities }) => {
+            debug_program(&file, &prompt, capabilities.capabilities(), lang)
+        },
+        Some(Commands::Dap) => {
+            let input = std::io::BufReader::new(std::io::stdin());
+            dap::Server::new(input, std::io::stdout()).with_lang(lang).run().map_err(|e| NepalError::from(e).into())
+        },
+        Some(Commands::Lsp) => {
+            let input = std::io::BufReader::new(std::io::stdin());
+            lsp::Server::new(input, std::io::stdout()).with_lang(lang).run().map_err(|e| NepalError::from(e).into())
+        },
+        None => {
+            // Legacy mode - handle direct file input
+            let capabilities = cli.capabilities.capabilities();
+            if let Some(file) = cli.input {
+                run_program(&file, cli.vm, false, false, Limits::default(), capabilities, &cli.prompt)
+            } else if let Ok(example) = std::fs::canonicalize("example.nbh") {
+                run_program(&example, cli.vm, false, false, Limits::default(), capabilities, &cli.prompt)
+            } else {
+                Err(NepalError::from(Diagnostic::new(ErrorCode::NoInputFile)).into())
+            }
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(report) => {
+            print_report(&report, lang);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_report(report: &Report, lang: Lang) {
+    eprint!("{}", report.render(lang));
+    let count = report.error.errors().len();
+    if count > 1 {
+        eprintln!("{}", messages::error_count(count, lang));
+    }
+}
+
+fn read_source(file: &Path) -> Result<String, NepalError> {
+    std::fs::read_to_string(file).map_err(|e| {
+        Diagnostic::new(ErrorCode::FileRead)
+            .with_arg(file.display())
+            .with_arg(e)
+            .into()
+    })
+}
+
+fn run_program(
+    file: &Path,
+    use_vm: bool,
+    optimize: bool,
+    registers: bool,
+    limits: Limits,
+    capabilities: Capabilities,
+    prompt: &str,
+) -> Result<(), Report> {
+    let console = Console::stdio().with_prompt(prompt);
+    if is_bytecode(file) {
+        // There is no source to show, but errors can still name the file
+        let bytes = read_bytecode(file)?;
+        let program = nbc::decode(&bytes).map_err(|d| in_file(file, "")(d.into()))?;
+        println!("Running with Lyangpiler VM");
+        return run_bytecode(program, registers, limits, capabilities, console).map_err(in_file(file, ""));
+    }
+    let input = read_source(file)?;
+    run_source(&input, use_vm, optimize, registers, limits, capabilities, console).map_err(in_file(file, &input))
+}
+
+fn is_bytecode(file: &Path) -> bool {
+    file.extension().is_some_and(|extension| extension == "nbc")
+}
+
+fn read_bytecode(file: &Path) -> Result<Vec<u8>, NepalError> {
+    std::fs::read(file).map_err(|e| {
+        Diagnostic::new(ErrorCode::FileRead)
+            .with_arg(file.display())
+            .with_arg(e)
+            .into()
+    })
+}
+
+fn build_program(file: &Path, output: &Path, optimize: bool) -> Result<(), Report> {
+    let input = read_source(file)?;
+    let program = compile_source(&input, optimize).map_err(in_file(file, &input))?;
+    std::fs::write(output, nbc::encode(&program)).map_err(|e| {
+        NepalError::from(Diagnostic::new(ErrorCode::FileWrite).with_arg(output.display()).with_arg(e))
+    })?;
+    println!("Wrote {} ({} instructions)", output.display(), program.instructions.len());
+    Ok(())
+}
+
+fn disassemble_program(file: &Path, json: bool, optimize: bool) -> Result<(), Report> {
+    let (mut program, source) = if is_bytecode(file) {
+        let bytes = read_bytecode(file)?;
+        let program = nbc::decode(&bytes).map_err(|d| in_file(file, "")(d.into()))?;
+        (program, None)
+    } else {
+        let input = read_source(file)?;
+        (compile_source(&input, false).map_err(in_file(file, &input))?, Some(input))
+    };
+    if optimize {
+        program = optimizer::optimize(program);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&disasm::to_json(&program)).unwrap_or_default());
+    } else {
+        print!("{}", disasm::disassemble(&program, source.as_deref()));
+    }
+    Ok(())
+}
+
+fn compile_source(input: &str, optimize: bool) -> Result<BytecodeProgram, NepalError> {
+    let tokens = Lexer::new(input).tokenize()?;
+    let statements = LyangParser::new(tokens).parse()?;
+    let program = Compiler::new().compile(statements)?;
+    Ok(if optimize { optimizer::optimize(program) } else { program })
+}
+
+fn run_bytecode(
+    program: BytecodeProgram,
+    registers: bool,
+    limits: Limits,
+    capabilities: Capabilities,
+    console: Console,
+) -> Result<(), NepalError> {
+    verifier::verify(&program)?;
+    if registers {
+        RegisterVM::new(register::lower(&program)?)
+            .with_console(console)
+            .with_limits(limits)
+            .with_capabilities(capabilities)
+            .run()?;
+    } else {
+        VM::new(program)
+            .with_console(console)
+            .with_limits(limits)
+            .with_capabilities(capabilities)
+            .run()?;
+    }
+    println!("Program execution completed.");
+    Ok(())
+}
+
+fn run_source(
+    input: &str,
+    use_vm: bool,
+    optimize: bool,
+    registers: bool,
+    limits: Limits,
+    capabilities: Capabilities,
+    console: Console,
+) -> Result<(), NepalError> {
+    let mut lexer = Lexer::new(input);
+    let tokens = lexer.tokenize()?;
+    let mut parser = LyangParser::new(tokens);
+    let statements = parser.parse()?;
+
+    if use_vm {
+        println!("Running with Lyangpiler VM");
+        let mut compiler = Compiler::new();
+        let mut program = compiler.compile(statements)?;
+        if optimize {
+            program = optimizer::optimize(program);
+        }
+        run_bytecode(program, registers, limits, capabilities, console)?;
+    } else {
+        let mut interpreter = Interpreter::new()
+            .with_console(console)
+            .with_limits(limits)
+            .with_capabilities(capabilities);
+        interpreter.run(statements)?;
+    }
+    Ok(())
+}
+
+const DEBUG_PROMPT: &str = "(debug) ";
+
+fn debug_program(file: &Path, prompt: &str, capabilities: Capabilities, lang: Lang) -> Result<(), Report> {
+    let input = read_source(file)?;
+    let program = compile_source(&input, false).map_err(in_file(file, &input))?;
+    verifier::verify(&program).map_err(|d| in_file(file, &input)(d.into()))?;
+    let debugger = Debugger::new(&program, &input, Console::stdio().with_prompt(DEBUG_PROMPT)).with_lang(lang);
+    println!("Debugging {}. Type help for commands.", file.display());
+    VM::new(program)
+        .with_console(Console::stdio().with_prompt(prompt))
+        .with_capabilities(capabilities)
+        .with_debug_hook(debugger)
+        .run()
+        .map_err(in_file(file, &input))?;
+    println!("Program execution completed.");
+    Ok(())
+}
+
+const REPL_PROMPT: &str = "lyang> ";
+const REPL_CONTINUE: &str = "   ... ";
+const REPL_HELP: &str = "\
+:vars          show every variable and its value
+:reset         forget all variables
+:load <file>   run a .nbh file in this session
+:bytecode      show the bytecode compiled so far
+:quit          leave (or press Ctrl-D)
+A blank line runs an unfinished entry as it is.";
+
+/// Runs the REPL until `:quit` or end of input. Returns false if an entry
+/// was refused a capability, so that a script piped in fails as `run` would.
+fn run_repl(capabilities: Capabilities, lang: Lang) -> Result<bool, NepalError> {
+    let mut editor = rustyline::DefaultEditor::new().map_err(std::io::Error::other)?;
+    let history = dirs::home_dir().map(|home| home.join(".lyangpiler_history"));
+    if let Some(history) = &history {
+        // There is no history yet on first use
+        let _ = editor.load_history(history);
+    }
+
+    println!("LyangLang REPL. Type :help for commands.");
+    let mut session = Session::new().with_capabilities(capabilities);
+    let mut entry = String::new();
+    let mut refused = false;
+    loop {
+        let prompt = if entry.is_empty() { REPL_PROMPT } else { REPL_CONTINUE };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(rustyline::error::ReadlineError::Interrupted) => {
+                entry.clear();
+                continue;
+            }
+            Err(rustyline::error::ReadlineError::Eof) => break,
+            Err(e) => return Err(std::io::Error::other(e).into()),
+        };
+
+        if entry.is_empty() {
+            let command = line.trim();
+            if command.is_empty() {
+                continue;
+            }
+            if let Some(command) = command.strip_prefix(':') {
+                let _ = editor.add_history_entry(line.as_str());
+                if !run_repl_command(command, &mut session, &mut refused, lang) {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let _ = editor.add_history_entry(line.as_str());
+        let blank = line.trim().is_empty();
+        entry.push_str(&line);
+        entry.push('\n');
+        if !blank && repl::needs_more_input(&entry) {
+            continue;
+        }
+        if let Err(error) = session.eval(&entry) {
+            refused |= is_refused(&error);
+            print_report(&Report { error, source: Some(("<repl>".to_string(), entry.clone())) }, lang);
+        }
+        entry.clear();
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(!refused)
+}
+
+/// Runs a `:` command, setting `refused` if a file it loads is refused a
+/// capability. Returns false when the session should end.
+fn run_repl_command(command: &str, session: &mut Session, refused: &mut bool, lang: Lang) -> bool {
+    let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    match name {
+        "vars" => session.vars().iter().for_each(|line| println!("{}", line)),
+        "reset" => session.reset(),
+        "bytecode" => session.bytecode().iter().for_each(|line| println!("{}", line)),
+        "load" if argument.trim().is_empty() => eprintln!("Usage: :load <file>"),
+        "load" => {
+            let file = Path::new(argument.trim());
+            let result = read_source(file)
+                .map_err(Report::from)
+                .and_then(|source| session.eval(&source).map_err(in_file(file, &source)));
+            if let Err(report) = result {
+                *refused |= is_refused(&report.error);
+                print_report(&report, lang);
+            }
+        }
+        "quit" | "q" => return false,
+        "help" => println!("{}", REPL_HELP),
+        _ => eprintln!("Unknown command :{}. Type :help for commands.", command),
+    }
+    true
+}
+
+/// Whether `error` stopped a program for using a capability it was not granted.
+fn is_refused(error: &NepalError) -> bool {
+    error.diagnostic().is_some_and(|diagnostic| diagnostic.code == ErrorCode::PermissionDenied)
+}
+
+fn create_project(name: &str) -> Result<(), NepalError> {
+    let project_dir = PathBuf::from(name);
+    if project_dir.exists() {
+        return Err(Diagnostic::new(ErrorCode::ProjectExists).with_arg(name).into());
+    }
+
+    std::fs::create_dir(&project_dir)?;
+    
+    // Create main.nbh
+    let main_file = project_dir.join("main.nbh");
+    std::fs::write(main_file, "bol mug \"Namaste, world!\"")?;
+    
+    // Create the project file with every lint rule at its default severity
+    let project_file = project_dir.join(lint::Config::FILE_NAME);
+    let config = serde_json::to_string_pretty(&lint::Config::default().to_json()).unwrap_or_default();
+    std::fs::write(project_file, config + "\n")?;
+
+    // Create README.md
+    let readme = project_dir.join("README.md");
+    std::fs::write(readme, format!("# {}\n\nA LyangLang project.\n\n## Running\n\n```bash\nlyangpiler main.nbh --vm --allow-stdin --allow-stdout\n```\n", name))?;
+
+    println!("Created new LyangLang project: {}", name);
+    println!("  cd {}", name);
+    println!("  lyangpiler main.nbh --vm --allow-stdin --allow-stdout");
+    
+    Ok(())
+}
+
+fn check_program(file: &Path, types: bool) -> Result<(), Report> {
+    let input = read_source(file)?;
+    check_source(&input, types).map_err(in_file(file, &input))?;
+    if types {
+        println!("Program syntax and types are valid!");
+    } else {
+        println!("Program syntax is valid!");
+    }
+    Ok(())
+}
+
+fn format_programs(paths: &[PathBuf], check: bool) -> Result<(), Report> {
+    let mut files = Vec::new();
+    for path in paths {
+        find_programs(path, &mut files)?;
+    }
+    let mut unformatted = Vec::new();
+    for file in &files {
+        let input = read_source(file)?;
+        let formatted = formatter::format(&input).map_err(in_file(file, &input))?;
+        if formatted == input {
+            continue;
+        }
+        if check {
+            unformatted.push(NepalError::from(Diagnostic::new(ErrorCode::NotFormatted).with_arg(file.display())));
+            continue;
+        }
+        std::fs::write(file, formatted).map_err(|e| {
+            NepalError::from(Diagnostic::new(ErrorCode::FileWrite).with_arg(file.display()).with_arg(e))
+        })?;
+        println!("Formatted {}", file.display());
+    }
+    match unformatted.len() {
+        0 => Ok(()),
+        1 => Err(unformatted.remove(0).into()),
+        _ => Err(NepalError::Multiple(unformatted).into()),
+    }
+}
+
+/// Lints every program under `paths`, returning whether none has an error-level finding.
+fn lint_programs(paths: &[PathBuf], fix: bool, lang: Lang) -> Result<bool, Report> {
+    let mut files = Vec::new();
+    for path in paths {
+        find_programs(path, &mut files)?;
+    }
+    let (mut errors, mut warnings, mut fixable) = (0, 0, 0);
+    for file in &files {
+        let config = lint_config(file)?;
+        let input = read_source(file)?;
+        let findings = lint::lint(&input, &config).map_err(in_file(file, &input))?;
+        let (fixed, remaining): (Vec<_>, Vec<_>) = findings.into_iter().partition(|finding| fix && !finding.fix.is_empty());
+        if !fixed.is_empty() {
+            std::fs::write(file, lint::fix(&input, &fixed)).map_err(|e| {
+                NepalError::from(Diagnostic::new(ErrorCode::FileWrite).with_arg(file.display()).with_arg(e))
+            })?;
+            println!("Fixed {} problems in {}", fixed.len(), file.display());
+        }
+        for finding in &remaining {
+            eprint!("{}", finding.render(&input, &file.display().to_string(), lang));
+            match finding.severity {
+                Severity::Error => errors += 1,
+                _ => warnings += 1,
+            }
+            if !finding.fix.is_empty() {
+                fixable += 1;
+            }
+        }
+    }
+    if errors + warnings > 0 {
+        eprintln!("{}", messages::lint_count(errors, warnings, lang));
+    }
+    if fixable > 0 {
+        eprintln!("{}", messages::fixable_count(fixable, lang));
+    }
+    Ok(errors == 0)
+}
+
+/// The lint settings of the project `file` is in, from the nearest project file above it.
+fn lint_config(file: &Path) -> Result<lint::Config, NepalError> {
+    let directory = std::fs::canonicalize(file).ok().and_then(|file| file.parent().map(Path::to_path_buf));
+    let Some(project) = directory.iter().flat_map(|dir| dir.ancestors()).map(|dir| dir.join(lint::Config::FILE_NAME)).find(|path| path.is_file()) else {
+        return Ok(lint::Config::default());
+    };
+    let text = read_source(&project)?;
+    lint::Config::from_json(&text)
+        .map_err(|e| Diagnostic::new(ErrorCode::InvalidLintConfig).with_arg(project.display()).with_arg(e).into())
+}
+
+/// Adds `path` to `files`, or every `.nbh` file under it if it is a directory.
+fn find_programs(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), NepalError> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let entries = std::fs::read_dir(path).map_err(|e| {
+        NepalError::from(Diagnostic::new(ErrorCode::FileRead).with_arg(path.display()).with_arg(e))
+    })?;
+    let mut entries: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    entries.sort();
+    for entry in entries {
+        let hidden = entry.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.') || name == "target");
+        if entry.is_dir() && !hidden {
+            find_programs(&entry, files)?;
+        } else if entry.extension().is_some_and(|extension| extension == "nbh") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn check_source(input: &str, types: bool) -> Result<(), NepalError> {
+    let mut lexer = Lexer::new(input);
+    let tokens = lexer.tokenize()?;
+    let statements = LyangParser::new(tokens).parse()?;
+    if types {
+        typecheck::check(&statements, &Natives::stdlib())?;
+    }
+    Ok(())
+}
@@ -1,34 +1,55 @@
-use crate::ast::{Statement, Value as AstValue, Condition, StrSegment};
+use crate::ast::{Argument, Ident, Statement, Value as AstValue, Condition, StrSegment};
 use crate::bytecode::{BytecodeProgram, Opcode};
+use crate::diagnostic::{Diagnostic, ErrorCode};
 use crate::error::NepalError;
+use crate::native::Natives;
+use crate::span::{Span, Spanned};
 
 /// Compiler for LyangLang - converts AST to bytecode
 pub struct Compiler {
     program: BytecodeProgram,
-    current_line: usize,
+    /// Span of the statement being compiled, attached to each emitted instruction
+    current_span: Span,
+    /// Functions calls are checked against
+    natives: Natives,
 }
 
 impl Compiler {
     pub fn new() -> Self {
         Self {
             program: BytecodeProgram::new(),
-            current_line: 1,
+            current_span: Span::default(),
+            natives: Natives::stdlib(),
         }
     }
 
-    pub fn compile(&mut self, statements: Vec<Statement>) -> Result<BytecodeProgram, NepalError> {
+    /// Checks calls against `natives` instead of the standard library.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    /// Appends to `program` instead of starting a new one, reusing its string
+    /// pool and variable table so code compiled earlier stays valid.
+    pub fn with_program(mut self, program: BytecodeProgram) -> Self {
+        self.program = program;
+        self
+    }
+
+    pub fn compile(&mut self, statements: Vec<Spanned<Statement>>) -> Result<BytecodeProgram, NepalError> {
         for statement in statements {
             self.compile_statement(statement)?;
         }
         
         // Add halt instruction
-        self.program.add_instruction(Opcode::Halt, self.current_line);
+        self.program.add_instruction(Opcode::Halt, self.current_span);
         
         Ok(self.program.clone())
     }
 
-    fn compile_statement(&mut self, statement: Statement) -> Result<(), NepalError> {
-        match statement {
+    fn compile_statement(&mut self, statement: Spanned<Statement>) -> Result<(), NepalError> {
+        self.current_span = statement.span;
+        match statement.node {
             Statement::Declaration(name, value) => {
                 self.compile_declaration(name, value)?;
             },
@@ -59,253 +80,263 @@ impl Compiler {
             Statement::If(condition, then_statements, else_statement) => {
                 self.compile_conditional(condition, then_statements, else_statement)?;
             },
+            Statement::Call(function, args, target) => {
+                self.compile_call(function, args, target)?;
+            },
+            Statement::Annotated(_, statement) => {
+                self.compile_statement(*statement)?;
+            },
         }
 
         Ok(())
     }
 
-    fn compile_declaration(&mut self, name: String, value: AstValue) -> Result<(), NepalError> {
-        let var_idx = self.program.add_variable(name);
+    fn compile_declaration(&mut self, name: Ident, value: AstValue) -> Result<(), NepalError> {
+        let var_idx = self.program.add_variable(name.node);
         
         match value {
             AstValue::Number(num) => {
-                self.program.add_instruction(Opcode::PushNumber(num), self.current_line);
+                self.program.add_instruction(Opcode::PushNumber(num), self.current_span);
             },
             AstValue::String(s) => {
                 let str_idx = self.program.add_string(s);
-                self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
+                self.program.add_instruction(Opcode::PushString(str_idx), self.current_span);
             },
         }
         
-        self.program.add_instruction(Opcode::StoreVariable(var_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(var_idx), self.current_span);
         
         Ok(())
     }
 
-    fn compile_addition(&mut self, target: String, sources: Vec<String>) -> Result<(), NepalError> {
+    fn compile_addition(&mut self, target: Ident, sources: Vec<Ident>) -> Result<(), NepalError> {
         if sources.is_empty() {
-            return Err(NepalError::RuntimeError("Addition requires at least one source variable"));
+            return Err(self.missing_operands("jod"));
         }
         
-        let target_idx = self.program.add_variable(target);
+        let target_idx = self.program.add_variable(target.node);
         
         // Load the first source variable
-        let first_var_idx = self.program.add_variable(sources[0].clone());
-        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_line);
+        let first_var_idx = self.program.add_variable(sources[0].node.clone());
+        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_span);
         
         // Add the remaining source variables
-        for i in 1..sources.len() {
-            let src_var_idx = self.program.add_variable(sources[i].clone());
-            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_line);
-            self.program.add_instruction(Opcode::Add, self.current_line);
+        for source in sources.iter().skip(1) {
+            let src_var_idx = self.program.add_variable(source.node.clone());
+            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_span);
+            self.program.add_instruction(Opcode::Add, self.current_span);
         }
         
         // Store the result in the target variable
-        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
         
         Ok(())
     }
 
-    fn compile_subtraction(&mut self, target: String, sources: Vec<String>) -> Result<(), NepalError> {
+    fn compile_subtraction(&mut self, target: Ident, sources: Vec<Ident>) -> Result<(), NepalError> {
         if sources.is_empty() {
-            return Err(NepalError::RuntimeError("Subtraction requires at least one source variable"));
+            return Err(self.missing_operands("ghata"));
         }
         
-        let target_idx = self.program.add_variable(target);
+        let target_idx = self.program.add_variable(target.node);
         
         // Load the first source variable
-        let first_var_idx = self.program.add_variable(sources[0].clone());
-        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_line);
+        let first_var_idx = self.program.add_variable(sources[0].node.clone());
+        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_span);
         
         // Subtract the remaining source variables
-        for i in 1..sources.len() {
-            let src_var_idx = self.program.add_variable(sources[i].clone());
-            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_line);
-            self.program.add_instruction(Opcode::Subtract, self.current_line);
+        for source in sources.iter().skip(1) {
+            let src_var_idx = self.program.add_variable(source.node.clone());
+            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_span);
+            self.program.add_instruction(Opcode::Subtract, self.current_span);
         }
         
         // Store the result in the target variable
-        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
         
         Ok(())
     }
 
-    fn compile_multiplication(&mut self, target: String, sources: Vec<String>) -> Result<(), NepalError> {
+    fn compile_multiplication(&mut self, target: Ident, sources: Vec<Ident>) -> Result<(), NepalError> {
         if sources.is_empty() {
-            return Err(NepalError::RuntimeError(
-                "Multiplication requires at least one source variable",
-            ));
+            return Err(self.missing_operands("guna"));
         }
 
-        let target_idx = self.program.add_variable(target);
-        let first_var_idx = self.program.add_variable(sources[0].clone());
-        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_line);
+        let target_idx = self.program.add_variable(target.node);
+        let first_var_idx = self.program.add_variable(sources[0].node.clone());
+        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_span);
 
-        for i in 1..sources.len() {
-            let src_var_idx = self.program.add_variable(sources[i].clone());
-            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_line);
-            self.program.add_instruction(Opcode::Multiply, self.current_line);
+        for source in sources.iter().skip(1) {
+            let src_var_idx = self.program.add_variable(source.node.clone());
+            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_span);
+            self.program.add_instruction(Opcode::Multiply, self.current_span);
         }
 
-        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
         Ok(())
     }
 
-    fn compile_division(&mut self, target: String, sources: Vec<String>) -> Result<(), NepalError> {
+    fn compile_division(&mut self, target: Ident, sources: Vec<Ident>) -> Result<(), NepalError> {
         if sources.is_empty() {
-            return Err(NepalError::RuntimeError(
-                "Division requires at least one source variable",
-            ));
+            return Err(self.missing_operands("bhag"));
         }
 
-        let target_idx = self.program.add_variable(target);
-        let first_var_idx = self.program.add_variable(sources[0].clone());
-        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_line);
+        let target_idx = self.program.add_variable(target.node);
+        let first_var_idx = self.program.add_variable(sources[0].node.clone());
+        self.program.add_instruction(Opcode::LoadVariable(first_var_idx), self.current_span);
 
-        for i in 1..sources.len() {
-            let src_var_idx = self.program.add_variable(sources[i].clone());
-            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_line);
-            self.program.add_instruction(Opcode::Divide, self.current_line);
+        for source in sources.iter().skip(1) {
+            let src_var_idx = self.program.add_variable(source.node.clone());
+            self.program.add_instruction(Opcode::LoadVariable(src_var_idx), self.current_span);
+            self.program.add_instruction(Opcode::Divide, self.current_span);
         }
 
-        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
         Ok(())
     }
 
-    fn compile_string_concat(&mut self, target: String, parts: Vec<StrSegment>) -> Result<(), NepalError> {
+    fn compile_string_concat(&mut self, target: Ident, parts: Vec<StrSegment>) -> Result<(), NepalError> {
         if parts.is_empty() {
-            return Err(NepalError::RuntimeError("String concatenation requires at least one part"));
+            return Err(Diagnostic::new(ErrorCode::EmptyConcatenation)
+                .with_span(self.current_span)
+                .into());
         }
         
-        let target_idx = self.program.add_variable(target);
-        
-        match &parts[0] {
-            StrSegment::Literal(s) => {
-                let str_idx = self.program.add_string(s.clone());
-                self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
-            }
-            StrSegment::Identifier(name) => {
-                let var_idx = self.program.add_variable(name.clone());
-                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
-            }
-        }
+        let target_idx = self.program.add_variable(target.node);
         
+        self.compile_segment(&parts[0]);
         for part in parts.iter().skip(1) {
-            match part {
-                StrSegment::Literal(s) => {
-                    let str_idx = self.program.add_string(s.clone());
-                    self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
-                }
-                StrSegment::Identifier(name) => {
-                    let var_idx = self.program.add_variable(name.clone());
-                    self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
-                }
-            }
-            
-            self.program.add_instruction(Opcode::Concat, self.current_line);
+            self.compile_segment(part);
+            self.program.add_instruction(Opcode::Concat, self.current_span);
         }
         
-        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
         
         Ok(())
     }
 
-    fn compile_print_variable(&mut self, name: String) -> Result<(), NepalError> {
-        let var_idx = self.program.add_variable(name);
-        self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
-        self.program.add_instruction(Opcode::Print, self.current_line);
+    fn compile_print_variable(&mut self, name: Ident) -> Result<(), NepalError> {
+        let var_idx = self.program.add_variable(name.node);
+        self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_span);
+        self.program.add_instruction(Opcode::Print, self.current_span);
         
         Ok(())
     }
 
-    fn compile_print_string(&mut self, parts: Vec<String>) -> Result<(), NepalError> {
+    fn compile_print_string(&mut self, parts: Vec<StrSegment>) -> Result<(), NepalError> {
         if parts.is_empty() {
             return Ok(());
         }
         
-        // Process first part
-        let first_part = &parts[0];
-        
-        if first_part.starts_with('{') && first_part.ends_with('}') {
-            // It's a variable interpolation
-            let var_name = &first_part[1..first_part.len() - 1];
-            let var_idx = self.program.add_variable(var_name.to_string());
-            self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
-        } else {
-            // It's a string literal
-            let str_idx = self.program.add_string(first_part.clone());
-            self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
-        }
-        
-        // Process remaining parts, concatenating them
-        for i in 1..parts.len() {
-            let part = &parts[i];
-            
-            if part.starts_with('{') && part.ends_with('}') {
-                // It's a variable interpolation
-                let var_name = &part[1..part.len() - 1];
-                let var_idx = self.program.add_variable(var_name.to_string());
-                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
-            } else {
-                // It's a string literal
-                let str_idx = self.program.add_string(part.clone());
-                self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
-            }
-            
-            // Concatenate with previous parts
-            self.program.add_instruction(Opcode::Concat, self.current_line);
+        // Push the first part, then concatenate the rest onto it
+        self.compile_segment(&parts[0]);
+        for part in parts.iter().skip(1) {
+            self.compile_segment(part);
+            self.program.add_instruction(Opcode::Concat, self.current_span);
         }
         
         // Print the result
-        self.program.add_instruction(Opcode::Print, self.current_line);
+        self.program.add_instruction(Opcode::Print, self.current_span);
         
         Ok(())
     }
 
-    fn compile_input(&mut self, name: String) -> Result<(), NepalError> {
-        let var_idx = self.program.add_variable(name);
+    /// Pushes a literal segment or loads an interpolated variable.
+    fn compile_segment(&mut self, part: &StrSegment) {
+        match part {
+            StrSegment::Literal(s) => {
+                let str_idx = self.program.add_string(s.clone());
+                self.program.add_instruction(Opcode::PushString(str_idx), self.current_span);
+            }
+            StrSegment::Identifier(name) => {
+                let var_idx = self.program.add_variable(name.node.clone());
+                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_span);
+            }
+        }
+    }
+
+    fn compile_input(&mut self, name: Ident) -> Result<(), NepalError> {
+        let var_idx = self.program.add_variable(name.node);
         
         // Generate bytecode to read input from user
-        self.program.add_instruction(Opcode::Input, self.current_line);
+        self.program.add_instruction(Opcode::Input, self.current_span);
         
         // Store input in the variable
-        self.program.add_instruction(Opcode::StoreVariable(var_idx), self.current_line);
+        self.program.add_instruction(Opcode::StoreVariable(var_idx), self.current_span);
         
         Ok(())
     }
 
+    fn compile_call(&mut self, function: Ident, args: Vec<Argument>, target: Option<Ident>) -> Result<(), NepalError> {
+        self.natives
+            .check_call(&function.node, args.len())
+            .map_err(|d| d.with_span(self.current_span))?;
+
+        for arg in &args {
+            match arg {
+                Argument::Variable(name) => {
+                    let var_idx = self.program.add_variable(name.node.clone());
+                    self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_span);
+                }
+                Argument::Literal(AstValue::Number(n)) => {
+                    self.program.add_instruction(Opcode::PushNumber(*n), self.current_span);
+                }
+                Argument::Literal(AstValue::String(s)) => {
+                    let str_idx = self.program.add_string(s.clone());
+                    self.program.add_instruction(Opcode::PushString(str_idx), self.current_span);
+                }
+            }
+        }
+
+        let native_idx = self.program.add_native(function.node);
+        self.program.add_instruction(Opcode::CallNative(native_idx, args.len()), self.current_span);
+
+        // Keep the result only if there is somewhere to put it
+        match target {
+            Some(target) => {
+                let target_idx = self.program.add_variable(target.node);
+                self.program.add_instruction(Opcode::StoreVariable(target_idx), self.current_span);
+            }
+            None => self.program.add_instruction(Opcode::Pop, self.current_span),
+        }
+
+        Ok(())
+    }
+
     fn compile_conditional(
         &mut self,
         condition: Condition, 
-        then_statements: Vec<Statement>, 
-        else_statement: Option<Box<Statement>>
+        then_statements: Vec<Spanned<Statement>>, 
+        else_statement: Option<Box<Spanned<Statement>>>
     ) -> Result<(), NepalError> {
         match condition {
             Condition::Equals(var_name, literal) => {
                 // Load variable
-                let var_idx = self.program.add_variable(var_name);
-                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
+                let var_idx = self.program.add_variable(var_name.node);
+                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_span);
                 
                 // Load literal to compare with
                 let str_idx = self.program.add_string(literal);
-                self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
+                self.program.add_instruction(Opcode::PushString(str_idx), self.current_span);
                 
                 // Compare for equality
-                self.program.add_instruction(Opcode::Equal, self.current_line);
+                self.program.add_instruction(Opcode::Equal, self.current_span);
                 
                 // Add conditional jump (will update the address later)
                 let jump_idx = self.program.instructions.len();
-                self.program.add_instruction(Opcode::JumpIfFalse(0), self.current_line);
+                self.program.add_instruction(Opcode::JumpIfFalse(0), self.current_span);
                 
                 // Compile the "then" statements
+                let condition_span = self.current_span;
                 for stmt in then_statements {
                     self.compile_statement(stmt)?;
                 }
+                self.current_span = condition_span;
                 
                 // If there's an else branch, add a jump past the else code
                 let else_jump_idx = if else_statement.is_some() {
                     let else_jump = self.program.instructions.len();
-                    self.program.add_instruction(Opcode::Jump(0), self.current_line);
+                    self.program.add_instruction(Opcode::Jump(0), self.current_span);
                     Some(else_jump)
                 } else {
                     None
@@ -331,29 +362,31 @@ impl Compiler {
             
             Condition::NotEquals(var_name, literal) => {
                 // Load variable
-                let var_idx = self.program.add_variable(var_name);
-                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_line);
+                let var_idx = self.program.add_variable(var_name.node);
+                self.program.add_instruction(Opcode::LoadVariable(var_idx), self.current_span);
                 
                 // Load literal to compare with
                 let str_idx = self.program.add_string(literal);
-                self.program.add_instruction(Opcode::PushString(str_idx), self.current_line);
+                self.program.add_instruction(Opcode::PushString(str_idx), self.current_span);
                 
                 // Compare for inequality
-                self.program.add_instruction(Opcode::NotEqual, self.current_line);
+                self.program.add_instruction(Opcode::NotEqual, self.current_span);
                 
                 // Add conditional jump (will update the address later)
                 let jump_idx = self.program.instructions.len();
-                self.program.add_instruction(Opcode::JumpIfFalse(0), self.current_line);
+                self.program.add_instruction(Opcode::JumpIfFalse(0), self.current_span);
                 
                 // Compile the "then" statements
+                let condition_span = self.current_span;
                 for stmt in then_statements {
                     self.compile_statement(stmt)?;
                 }
+                self.current_span = condition_span;
                 
                 // If there's an else branch, add a jump past the else code
                 let else_jump_idx = if else_statement.is_some() {
                     let else_jump = self.program.instructions.len();
-                    self.program.add_instruction(Opcode::Jump(0), self.current_line);
+                    self.program.add_instruction(Opcode::Jump(0), self.current_span);
                     Some(else_jump)
                 } else {
                     None
@@ -381,9 +414,16 @@ impl Compiler {
         Ok(())
     }
     
-    // Mark with # to keep it but eliminate the unused warning
-    #[allow(dead_code)]
-    pub fn set_line(&mut self, line: usize) {
-        self.current_line = line;
+    fn missing_operands(&self, operation: &str) -> NepalError {
+        Diagnostic::new(ErrorCode::MissingOperands)
+            .with_arg(operation)
+            .with_span(self.current_span)
+            .into()
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,146 @@
+//! Where running programs read input from and write output to.
+//!
+//! Both engines take a [`Console`], so a program can run against the
+//! terminal, against scripted input in tests, or inside another application.
+
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Cursor, Write};
+use std::rc::Rc;
+
+/// Prompt written before `oi mug bhan` reads a line, unless configured otherwise.
+pub const DEFAULT_PROMPT: &str = "> ";
+
+/// The input and output handles of a running program.
+pub struct Console {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    prompt: String,
+}
+
+impl Console {
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            prompt: DEFAULT_PROMPT.to_string(),
+        }
+    }
+
+    /// The process's standard input and output.
+    pub fn stdio() -> Self {
+        Self::new(StdinLines::default(), io::stdout())
+    }
+
+    /// Scripted `input` and an in-memory output buffer that can be read back.
+    pub fn memory(input: &str) -> (Self, OutputBuffer) {
+        let output = OutputBuffer::default();
+        let console = Self::new(Cursor::new(input.as_bytes().to_vec()), output.clone());
+        (console, output)
+    }
+
+    /// Sets the text written before each line of input is read.
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Writes `text` followed by a newline. The error has no span yet.
+    pub fn print_line(&mut self, text: impl std::fmt::Display) -> Result<(), Diagnostic> {
+        writeln!(self.output, "{}", text).map_err(output_failed)
+    }
+
+    /// Shows the prompt and reads one line without its surrounding whitespace.
+    /// At end of input the line is empty. The error has no span yet.
+    pub fn read_line(&mut self) -> Result<String, Diagnostic> {
+        Ok(self.read_entry()?.unwrap_or_default())
+    }
+
+    /// Like [`Console::read_line`], but `None` at end of input.
+    pub fn read_entry(&mut self) -> Result<Option<String>, Diagnostic> {
+        write!(self.output, "{}", self.prompt).map_err(output_failed)?;
+        self.output.flush().map_err(output_failed)?;
+
+        let mut line = String::new();
+        let read = self
+            .input
+            .read_line(&mut line)
+            .map_err(|e| Diagnostic::new(ErrorCode::InputFailed).with_arg(e))?;
+        Ok((read > 0).then(|| line.trim().to_string()))
+    }
+}
+
+/// Standard input read one line at a time through the process-wide buffer,
+/// so other readers of stdin (such as the REPL's line editor) see whatever
+/// the program has not read.
+#[derive(Default)]
+struct StdinLines {
+    line: Vec<u8>,
+    consumed: usize,
+}
+
+impl io::Read for StdinLines {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl BufRead for StdinLines {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.consumed == self.line.len() {
+            self.line.clear();
+            self.consumed = 0;
+            io::stdin().lock().read_until(b'\n', &mut self.line)?;
+        }
+        Ok(&self.line[self.consumed..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed = (self.consumed + amount).min(self.line.len());
+    }
+}
+
+fn output_failed(error: io::Error) -> Diagnostic {
+    Diagnostic::new(ErrorCode::OutputFailed).with_arg(error)
+}
+
+/// Output kept in memory; clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl OutputBuffer {
+    /// Everything written so far.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_scripted_lines_and_shows_the_prompt() {
+        let (console, output) = Console::memory("  Ram \n");
+        let mut console = console.with_prompt("naam? ");
+        assert_eq!(console.read_line().unwrap(), "Ram");
+        assert_eq!(console.read_line().unwrap(), "");
+        console.print_line("Namaste").unwrap();
+        assert_eq!(output.contents(), "naam? naam? Namaste\n");
+    }
+}
@@ -0,0 +1,643 @@
+//! A Debug Adapter Protocol server, so editors such as VS Code can debug
+//! LyangLang programs.
+//!
+//! The server speaks DAP over a pair of streams, normally stdio: each
+//! message is a `Content-Length` header and a JSON body. It supports
+//! `initialize`, `launch`, `setBreakpoints`, `configurationDone`, `threads`,
+//! `stackTrace`, `scopes`, `variables`, `evaluate`, `next`, `stepIn`,
+//! `continue` and `disconnect`. Breakpoints and stepping come from
+//! [`Stepper`], so they behave exactly as in `lyangpiler debug`.
+//!
+//! `launch` takes the `program` to debug, `stopOnEntry`, the `input` that
+//! `oi mug bhan` reads, since stdin carries the protocol, and the
+//! capabilities to `allow`, e.g. `{ "stdin": true, "stdout": true, "read":
+//! ["data"] }`; as with `lyangpiler run`, nothing is granted otherwise. What
+//! the program prints arrives as `output` events.
+
+use crate::bytecode::BytecodeProgram;
+use crate::capability::Capabilities;
+use crate::compiler::Compiler;
+use crate::console::Console;
+use crate::debugger::{self, Pause, Stepper};
+use crate::error::NepalError;
+use crate::lexer::Lexer;
+use crate::messages::Lang;
+use crate::parser::Parser;
+use crate::protocol;
+use crate::verifier;
+use crate::vm::{DebugHook, Resume, VM};
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// The only thread a LyangLang program has.
+const THREAD_ID: i64 = 1;
+/// `variablesReference` of the variables scope.
+const VARIABLES: i64 = 1;
+/// `variablesReference` of the operand stack scope.
+const STACK: i64 = 2;
+
+/// Reads requests from and writes responses and events to the client.
+struct Transport {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    seq: i64,
+    /// Whether the client has gone, by disconnecting or closing the input
+    closed: bool,
+}
+
+/// The transport is shared by the server and the console of the program it
+/// debugs, which turns output into events.
+type Shared = Rc<RefCell<Transport>>;
+
+impl Transport {
+    /// The next message, or `None` once the client closes the input.
+    fn read(&mut self) -> io::Result<Option<Json>> {
+        let message = protocol::read(&mut self.input)?;
+        self.closed |= message.is_none();
+        Ok(message)
+    }
+
+    fn send(&mut self, mut message: Json) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        protocol::write(&mut self.output, &message)
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": true,
+            "command": request["command"],
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Json, message: impl std::fmt::Display) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "success": false,
+            "command": request["command"],
+            "message": message.to_string(),
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Json) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+/// A DAP server debugging one program.
+pub struct Server {
+    transport: Transport,
+    lang: Lang,
+}
+
+impl Server {
+    /// Reads requests from `input` and writes to `output`.
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
+        Self {
+            transport: Transport {
+                input: Box::new(input),
+                output: Box::new(output),
+                seq: 0,
+                closed: false,
+            },
+            lang: Lang::default(),
+        }
+    }
+
+    /// Reports errors in `lang`.
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Serves one debugging session, returning when the client disconnects
+    /// or closes the input.
+    pub fn run(self) -> io::Result<()> {
+        let lang = self.lang;
+        let transport = Rc::new(RefCell::new(self.transport));
+        let Some(launch) = configure(&transport, lang)? else {
+            return Ok(());
+        };
+
+        let console = Console::new(Cursor::new(launch.input.into_bytes()), Output::new(&transport)).with_prompt("");
+        let mut vm = VM::new(launch.program)
+            .with_console(console)
+            .with_capabilities(launch.capabilities)
+            .with_debug_hook(launch.session);
+        let result = vm.run();
+        drop(vm);
+
+        let mut transport = transport.borrow_mut();
+        if transport.closed {
+            return Ok(());
+        }
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(error) => {
+                let output = error.render(&launch.text, &launch.path, lang);
+                transport.event("output", json!({ "category": "stderr", "output": output }))?;
+                1
+            }
+        };
+        transport.event("exited", json!({ "exitCode": exit_code }))?;
+        transport.event("terminated", json!({}))?;
+
+        // The client may still ask about threads before it disconnects
+        while let Some(request) = transport.read()? {
+            match command(&request) {
+                "threads" => transport.respond(&request, json!({ "threads": [] }))?,
+                "disconnect" | "terminate" => return transport.respond(&request, json!({})),
+                _ => transport.fail(&request, "the program has finished")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A program ready to run once the client has set its breakpoints.
+struct Launch {
+    program: BytecodeProgram,
+    session: Session,
+    input: String,
+    capabilities: Capabilities,
+    /// The source, for rendering runtime errors
+    text: String,
+    path: String,
+}
+
+/// Handles requests until `configurationDone`, returning the program to
+/// run, or `None` if the client left first.
+fn configure(shared: &Shared, lang: Lang) -> io::Result<Option<Launch>> {
+    let mut launch: Option<Launch> = None;
+    loop {
+        let mut transport = shared.borrow_mut();
+        let Some(request) = transport.read()? else {
+            return Ok(None);
+        };
+        match (command(&request), &mut launch) {
+            ("initialize", _) => transport.respond(&request, capabilities())?,
+            ("launch", _) => match load(&request["arguments"], shared, lang) {
+                Ok(loaded) => {
+                    launch = Some(loaded);
+                    transport.respond(&request, json!({}))?;
+                    transport.event("initialized", json!({}))?;
+                }
+                Err(message) => transport.fail(&request, message)?,
+            },
+            ("setBreakpoints", Some(launch)) => {
+                let body = launch.session.set_breakpoints(&request["arguments"]);
+                transport.respond(&request, body)?;
+            }
+            ("configurationDone", Some(_)) => {
+                transport.respond(&request, json!({}))?;
+                return Ok(launch);
+            }
+            ("setBreakpoints" | "configurationDone", None) => transport.fail(&request, "launch a program first")?,
+            ("threads", _) => transport.respond(&request, threads())?,
+            ("disconnect" | "terminate", _) => {
+                transport.closed = true;
+                transport.respond(&request, json!({}))?;
+                return Ok(None);
+            }
+            (name, _) => transport.fail(&request, format!("`{}` is not supported", name))?,
+        }
+    }
+}
+
+/// Compiles the program named in `launch` arguments. The error is the
+/// message to show the user.
+fn load(arguments: &Json, shared: &Shared, lang: Lang) -> Result<Launch, String> {
+    let Some(path) = arguments["program"].as_str() else {
+        return Err("launch needs the `program` to debug".to_string());
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let program = compile(&text).map_err(|e| e.render(&text, path, lang))?;
+
+    let mut stepper = Stepper::new(&program);
+    if !arguments["stopOnEntry"].as_bool().unwrap_or(false) {
+        stepper.resume();
+    }
+    let name = Path::new(path).file_name().map_or(path.into(), |name| name.to_string_lossy());
+    let session = Session {
+        transport: Rc::clone(shared),
+        stepper,
+        source: json!({ "name": name, "path": path }),
+        lang,
+    };
+    Ok(Launch {
+        program,
+        session,
+        input: arguments["input"].as_str().unwrap_or_default().to_string(),
+        capabilities: allowed(&arguments["allow"]),
+        text,
+        path: path.to_string(),
+    })
+}
+
+/// The capabilities the `allow` launch argument grants.
+fn allowed(allow: &Json) -> Capabilities {
+    let flag = |name: &str| allow[name].as_bool().unwrap_or(false);
+    let paths = |name: &str| -> Vec<PathBuf> {
+        allow[name].as_array().into_iter().flatten().filter_map(Json::as_str).map(PathBuf::from).collect()
+    };
+    Capabilities {
+        stdin: flag("stdin"),
+        stdout: flag("stdout"),
+        read: paths("read"),
+        write: paths("write"),
+        clock: flag("clock"),
+        random: flag("random"),
+    }
+}
+
+fn compile(source: &str) -> Result<BytecodeProgram, NepalError> {
+    let tokens = Lexer::new(source).tokenize()?;
+    let program = Compiler::new().compile(Parser::new(tokens).parse()?)?;
+    verifier::verify(&program)?;
+    Ok(program)
+}
+
+fn command(request: &Json) -> &str {
+    request["command"].as_str().unwrap_or_default()
+}
+
+fn capabilities() -> Json {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsEvaluateForHovers": true,
+        "supportsSteppingGranularity": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn threads() -> Json {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// The running program, seen from the client while it is paused.
+struct Session {
+    transport: Shared,
+    stepper: Stepper,
+    /// The DAP `Source` every frame is in
+    source: Json,
+    lang: Lang,
+}
+
+impl Session {
+    /// Replaces the breakpoints with the ones in `setBreakpoints` arguments.
+    /// Lines where no statement starts are reported as unverified.
+    fn set_breakpoints(&mut self, arguments: &Json) -> Json {
+        self.stepper.clear_breakpoints();
+        let lines = arguments["breakpoints"].as_array().into_iter().flatten().map(|b| b["line"].as_u64());
+        let breakpoints: Vec<Json> = lines
+            .map(|line| {
+                let line = line.unwrap_or_default() as usize;
+                if self.stepper.add_breakpoint(line) || self.stepper.breakpoints().any(|set| set == line) {
+                    json!({ "id": line, "verified": true, "line": line })
+                } else {
+                    json!({ "verified": false, "line": line, "message": "no statement starts on this line" })
+                }
+            })
+            .collect();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    /// Tells the client why the program paused, then answers requests until
+    /// one resumes it.
+    fn paused(&mut self, vm: &VM, pause: Pause) -> io::Result<Resume> {
+        let shared = Rc::clone(&self.transport);
+        let mut transport = shared.borrow_mut();
+        let mut stopped = json!({ "threadId": THREAD_ID, "allThreadsStopped": true });
+        stopped["reason"] = json!(match pause {
+            Pause::Entry => "entry",
+            Pause::Step | Pause::Instruction => "step",
+            Pause::Breakpoint(_) => "breakpoint",
+        });
+        if let Pause::Breakpoint(line) = pause {
+            stopped["hitBreakpointIds"] = json!([line]);
+        }
+        transport.event("stopped", stopped)?;
+
+        loop {
+            let Some(request) = transport.read()? else {
+                return Ok(Resume::Stop);
+            };
+            let arguments = &request["arguments"];
+            let by_instruction = arguments["granularity"] == "instruction";
+            match command(&request) {
+                "threads" => transport.respond(&request, threads())?,
+                "stackTrace" => transport.respond(&request, self.stack_trace(vm))?,
+                "scopes" => transport.respond(&request, scopes())?,
+                "variables" => {
+                    let body = variables(vm, arguments["variablesReference"].as_i64());
+                    transport.respond(&request, body)?
+                }
+                "evaluate" => {
+                    let expression = arguments["expression"].as_str().unwrap_or_default();
+                    match debugger::evaluate(vm, expression) {
+                        Ok(value) => {
+                            let body = json!({ "result": debugger::show(&value), "variablesReference": 0 });
+                            transport.respond(&request, body)?
+                        }
+                        Err(error) => transport.fail(&request, self.message(&error))?,
+                    }
+                }
+                "setBreakpoints" => {
+                    let body = self.set_breakpoints(arguments);
+                    transport.respond(&request, body)?
+                }
+                "next" | "stepIn" | "continue" => {
+                    match command(&request) {
+                        _ if by_instruction => self.stepper.step_instruction(),
+                        "next" => self.stepper.step_over(vm),
+                        "stepIn" => self.stepper.step_into(),
+                        _ => self.stepper.resume(),
+                    }
+                    transport.respond(&request, json!({ "allThreadsContinued": true }))?;
+                    return Ok(Resume::Continue);
+                }
+                "disconnect" | "terminate" => {
+                    transport.closed = true;
+                    transport.respond(&request, json!({}))?;
+                    return Ok(Resume::Stop);
+                }
+                name => transport.fail(&request, format!("`{}` is not supported", name))?,
+            }
+        }
+    }
+
+    /// The program's only frame, at the source line of the instruction
+    /// about to run.
+    fn stack_trace(&self, vm: &VM) -> Json {
+        let Some(instruction) = vm.program().instructions.get(vm.ip()) else {
+            return json!({ "stackFrames": [], "totalFrames": 0 });
+        };
+        let frame = json!({
+            "id": 1,
+            "name": self.source["name"],
+            "source": self.source,
+            "line": instruction.line_number,
+            "column": instruction.span.column + 1,
+            "instructionPointerReference": format!("{:04}", vm.ip()),
+        });
+        json!({ "stackFrames": [frame], "totalFrames": 1 })
+    }
+
+    fn message(&self, error: &NepalError) -> String {
+        match error.diagnostic() {
+            Some(diagnostic) => diagnostic.message_in(self.lang),
+            None => error.to_string(),
+        }
+    }
+}
+
+impl DebugHook for Session {
+    fn before(&mut self, vm: &VM) -> Resume {
+        let Some(pause) = self.stepper.pause(vm) else {
+            return Resume::Continue;
+        };
+        // A client that cannot be reached cannot resume the program either
+        self.paused(vm, pause).unwrap_or(Resume::Stop)
+    }
+}
+
+fn scopes() -> Json {
+    json!({ "scopes": [
+        { "name": "Variables", "presentationHint": "locals", "variablesReference": VARIABLES, "expensive": false },
+        { "name": "Stack", "variablesReference": STACK, "expensive": false },
+    ] })
+}
+
+/// The variables, or the operand stack with its top last.
+fn variables(vm: &VM, reference: Option<i64>) -> Json {
+    let variable = |name: String, value| json!({ "name": name, "value": debugger::show(value), "variablesReference": 0 });
+    let variables: Vec<Json> = match reference {
+        Some(VARIABLES) => vm.globals().map(|(name, value)| variable(name.to_string(), value)).collect(),
+        Some(STACK) => vm.stack().iter().enumerate().map(|(slot, value)| variable(slot.to_string(), value)).collect(),
+        _ => Vec::new(),
+    };
+    json!({ "variables": variables })
+}
+
+/// What the program prints, sent to the client one line per `output` event.
+struct Output {
+    transport: Shared,
+    line: Vec<u8>,
+}
+
+impl Output {
+    fn new(transport: &Shared) -> Self {
+        Self { transport: Rc::clone(transport), line: Vec::new() }
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let output = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+        self.transport.borrow_mut().event("output", json!({ "category": "stdout", "output": output }))
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if byte == b'\n' {
+                self.send()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.line.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::OutputBuffer;
+    use crate::protocol::tests::{frame, unframe};
+
+    const SOURCE: &str = "\
+oi mug bhan naam
+yedi naam babaal \"Sita\" bhane
+  oi mug umer = 20
+  bol mug naam
+sakiyo
+bol mug \"bye \" + naam
+";
+
+    /// Writes `source` to a file of its own, for `launch` to read.
+    fn program(test: &str, source: &str) -> String {
+        let path = std::env::temp_dir().join(format!("lyangpiler-dap-{}-{}.nbh", test, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Plays `requests` to a server, numbering them, and returns every
+    /// message it sent back.
+    fn transcript(requests: &[Json]) -> Vec<Json> {
+        let requests: Vec<Json> = requests
+            .iter()
+            .enumerate()
+            .map(|(seq, request)| {
+                let mut request = request.clone();
+                request["seq"] = json!(seq + 1);
+                request["type"] = json!("request");
+                request
+            })
+            .collect();
+        let output = OutputBuffer::default();
+        Server::new(frame(&requests), output.clone()).run().unwrap();
+        unframe(&output)
+    }
+
+    fn launch(path: &str, stop_on_entry: bool) -> Vec<Json> {
+        vec![
+            json!({ "command": "initialize", "arguments": { "adapterID": "lyanglang" } }),
+            json!({ "command": "launch", "arguments": {
+                "program": path,
+                "stopOnEntry": stop_on_entry,
+                "input": "Sita\n",
+                "allow": { "stdin": true, "stdout": true },
+            } }),
+        ]
+    }
+
+    fn responses<'a>(messages: &'a [Json], command: &str) -> Vec<&'a Json> {
+        messages.iter().filter(|m| m["type"] == "response" && m["command"] == command).collect()
+    }
+
+    fn events<'a>(messages: &'a [Json], event: &str) -> Vec<&'a Json> {
+        messages.iter().filter(|m| m["event"] == event).map(|m| &m["body"]).collect()
+    }
+
+    #[test]
+    fn stops_at_breakpoints_and_shows_variables() {
+        let path = program("breakpoints", SOURCE);
+        let mut requests = launch(&path, false);
+        requests.extend([
+            json!({ "command": "setBreakpoints", "arguments": { "source": { "path": path }, "breakpoints": [{ "line": 4 }, { "line": 5 }] } }),
+            json!({ "command": "configurationDone" }),
+            json!({ "command": "threads" }),
+            json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }),
+            json!({ "command": "scopes", "arguments": { "frameId": 1 } }),
+            json!({ "command": "variables", "arguments": { "variablesReference": 1 } }),
+            json!({ "command": "evaluate", "arguments": { "expression": "\"hi \" + naam", "context": "watch" } }),
+            json!({ "command": "evaluate", "arguments": { "expression": "rang", "context": "hover" } }),
+            json!({ "command": "continue", "arguments": { "threadId": 1 } }),
+            json!({ "command": "disconnect" }),
+        ]);
+        let messages = transcript(&requests);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(messages[0]["body"]["supportsConfigurationDoneRequest"], true);
+        let initialized = messages.iter().position(|m| m["event"] == "initialized").unwrap();
+        let launched = messages.iter().position(|m| m["command"] == "launch").unwrap();
+        assert!(launched < initialized);
+
+        let breakpoints = &responses(&messages, "setBreakpoints")[0]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0], json!({ "id": 4, "verified": true, "line": 4 }));
+        assert_eq!(breakpoints[1]["verified"], false);
+
+        assert_eq!(events(&messages, "stopped"), [&json!({ "reason": "breakpoint", "threadId": 1, "allThreadsStopped": true, "hitBreakpointIds": [4] })]);
+        let frame = &responses(&messages, "stackTrace")[0]["body"]["stackFrames"][0];
+        assert_eq!((&frame["line"], &frame["column"]), (&json!(4), &json!(3)));
+        assert_eq!(frame["source"]["path"], json!(path));
+
+        let variables = &responses(&messages, "variables")[0]["body"]["variables"];
+        assert_eq!(variables[0], json!({ "name": "naam", "value": "\"Sita\"", "variablesReference": 0 }));
+        assert_eq!(variables[1]["value"], "20");
+
+        let evaluated = responses(&messages, "evaluate");
+        assert_eq!(evaluated[0]["body"]["result"], "\"hi Sita\"");
+        assert_eq!(evaluated[1]["success"], false);
+        assert_eq!(evaluated[1]["message"], "`rang` is used before it has a value");
+
+        let output: Vec<&Json> = events(&messages, "output").iter().map(|body| &body["output"]).collect();
+        assert_eq!(output, ["Sita\n", "bye Sita\n"]);
+        assert_eq!(events(&messages, "exited"), [&json!({ "exitCode": 0 })]);
+        assert_eq!(events(&messages, "terminated").len(), 1);
+        assert_eq!(responses(&messages, "disconnect")[0]["success"], true);
+    }
+
+    /// The line of each pause while taking `steps` from the entry, and why
+    /// each pause happened.
+    fn stepping(test: &str, steps: &[Json]) -> (Vec<Json>, Vec<Json>, Vec<Json>) {
+        let path = program(test, SOURCE);
+        let mut requests = launch(&path, true);
+        requests.push(json!({ "command": "configurationDone" }));
+        for step in steps {
+            requests.push(json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }));
+            requests.push(step.clone());
+        }
+        requests.push(json!({ "command": "stackTrace", "arguments": { "threadId": 1 } }));
+        requests.push(json!({ "command": "disconnect" }));
+        let messages = transcript(&requests);
+        std::fs::remove_file(&path).unwrap();
+
+        let frames = responses(&messages, "stackTrace").iter().map(|r| r["body"]["stackFrames"][0].clone()).collect();
+        let reasons = events(&messages, "stopped").iter().map(|body| body["reason"].clone()).collect();
+        let terminated = events(&messages, "terminated").into_iter().cloned().collect();
+        (frames, reasons, terminated)
+    }
+
+    #[test]
+    fn steps_over_and_into_yedi_blocks() {
+        let next = json!({ "command": "next", "arguments": { "threadId": 1 } });
+        let step_in = json!({ "command": "stepIn", "arguments": { "threadId": 1 } });
+
+        let (frames, reasons, terminated) = stepping("over", &[next.clone(), next.clone()]);
+        let lines: Vec<&Json> = frames.iter().map(|frame| &frame["line"]).collect();
+        assert_eq!(lines, [1, 2, 6]);
+        assert_eq!(reasons, ["entry", "step", "step"]);
+        // Disconnecting stops the program where it is
+        assert!(terminated.is_empty());
+
+        let (frames, _, _) = stepping("into", &[next.clone(), step_in.clone(), step_in, next]);
+        let lines: Vec<&Json> = frames.iter().map(|frame| &frame["line"]).collect();
+        assert_eq!(lines, [1, 2, 3, 4, 6]);
+
+        let instruction = json!({ "command": "next", "arguments": { "threadId": 1, "granularity": "instruction" } });
+        let (frames, _, _) = stepping("instruction", &[instruction]);
+        assert_eq!((&frames[0]["line"], &frames[1]["line"]), (&json!(1), &json!(1)));
+        assert_eq!((&frames[0]["instructionPointerReference"], &frames[1]["instructionPointerReference"]), (&json!("0000"), &json!("0001")));
+    }
+
+    #[test]
+    fn reports_programs_that_cannot_run() {
+        let path = program("broken", "bol mug \"namaste\n");
+        let messages = transcript(&launch(&path, false));
+        std::fs::remove_file(&path).unwrap();
+        let launched = responses(&messages, "launch")[0];
+        assert_eq!(launched["success"], false);
+        assert!(launched["message"].as_str().unwrap().contains("error[LY0004]: unterminated string"));
+        assert!(events(&messages, "initialized").is_empty());
+
+        let path = program("failing", "mug jod x lai y\n");
+        let mut requests = launch(&path, false);
+        requests.push(json!({ "command": "configurationDone" }));
+        let messages = transcript(&requests);
+        std::fs::remove_file(&path).unwrap();
+        let stderr = events(&messages, "output")[0];
+        assert_eq!(stderr["category"], "stderr");
+        assert!(stderr["output"].as_str().unwrap().contains("`x` is used before it has a value"));
+        assert_eq!(events(&messages, "exited"), [&json!({ "exitCode": 1 })]);
+
+        // Nothing is granted unless `allow` says so
+        assert_eq!(allowed(&Json::Null), Capabilities::none());
+        let allow = json!({ "stdout": true, "clock": false, "read": ["data", 5] });
+        assert_eq!(allowed(&allow), Capabilities::none().with_stdout().with_read("data"));
+    }
+}
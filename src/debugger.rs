@@ -0,0 +1,471 @@
+//! Source-level debugging on the VM.
+//!
+//! [`Stepper`] decides where a running program pauses: at breakpoints, and
+//! after stepping to the next statement, over a `yedi` block or by a single
+//! instruction. It only looks at the VM, so any front end can drive it.
+//! [`Debugger`] is the interactive prompt behind `lyangpiler debug`.
+
+use crate::ast::{Statement, StrSegment};
+use crate::bytecode::BytecodeProgram;
+use crate::console::Console;
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::error::NepalError;
+use crate::lexer::Lexer;
+use crate::messages::Lang;
+use crate::parser::Parser;
+use crate::span::Span;
+use crate::value::{BinaryOp, Value};
+use crate::vm::{DebugHook, Resume, VM};
+use std::collections::{BTreeSet, HashSet};
+
+/// Why the program paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pause {
+    /// Before the first statement
+    Entry,
+    /// After `step` or `next`
+    Step,
+    /// After running a single instruction
+    Instruction,
+    /// At a breakpoint on this line
+    Breakpoint(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Step,
+    /// Step, but not into anything inside this statement
+    Over(Span),
+    Instruction,
+    Continue,
+}
+
+/// Where to pause a program, given its breakpoints and the last command.
+/// Programs pause before their first statement.
+#[derive(Debug, Clone)]
+pub struct Stepper {
+    /// Offsets of the first instruction of each statement
+    starts: HashSet<usize>,
+    /// Lines on which a statement starts
+    lines: BTreeSet<usize>,
+    breakpoints: BTreeSet<usize>,
+    mode: Mode,
+    paused: bool,
+}
+
+impl Stepper {
+    pub fn new(program: &BytecodeProgram) -> Self {
+        // A statement's instructions share its span. The first one in the
+        // program starts it; later ones, such as the jump at the end of a
+        // `yedi` block, belong to a statement already under way.
+        let mut seen = HashSet::new();
+        let mut starts = HashSet::new();
+        let mut lines = BTreeSet::new();
+        for (offset, instruction) in program.instructions.iter().enumerate() {
+            let span = instruction.span;
+            if span.is_known() && seen.insert((span.start, span.end)) {
+                starts.insert(offset);
+                lines.insert(instruction.line_number);
+            }
+        }
+        Self {
+            starts,
+            lines,
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+            paused: false,
+        }
+    }
+
+    /// Pauses before a statement on `line` runs. Returns `false`, adding
+    /// nothing, if no statement starts on `line`.
+    pub fn add_breakpoint(&mut self, line: usize) -> bool {
+        self.lines.contains(&line) && self.breakpoints.insert(line)
+    }
+
+    /// Returns `false` if there was no breakpoint on `line`.
+    pub fn remove_breakpoint(&mut self, line: usize) -> bool {
+        self.breakpoints.remove(&line)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Pause at the next statement, including ones inside a `yedi` block.
+    pub fn step_into(&mut self) {
+        self.mode = Mode::Step;
+    }
+
+    /// Pause at the next statement after the one `vm` is in, so a `yedi`
+    /// block runs without pausing inside it.
+    pub fn step_over(&mut self, vm: &VM) {
+        self.mode = match vm.program().instructions.get(vm.ip()) {
+            Some(instruction) => Mode::Over(instruction.span),
+            None => Mode::Step,
+        };
+    }
+
+    /// Pause before the next instruction.
+    pub fn step_instruction(&mut self) {
+        self.mode = Mode::Instruction;
+    }
+
+    /// Pause only at breakpoints.
+    pub fn resume(&mut self) {
+        self.mode = Mode::Continue;
+    }
+
+    /// Whether to pause before the instruction `vm` is about to run, and why.
+    pub fn pause(&mut self, vm: &VM) -> Option<Pause> {
+        let instruction = vm.program().instructions.get(vm.ip())?;
+        let start = self.starts.contains(&vm.ip());
+        let line = instruction.line_number;
+        let pause = if start && self.breakpoints.contains(&line) {
+            Pause::Breakpoint(line)
+        } else {
+            match self.mode {
+                Mode::Instruction => Pause::Instruction,
+                Mode::Step if start => Pause::Step,
+                Mode::Over(span) if start && !contains(span, instruction.span) => Pause::Step,
+                _ => return None,
+            }
+        };
+        let first = !std::mem::replace(&mut self.paused, true);
+        Some(if first && pause == Pause::Step { Pause::Entry } else { pause })
+    }
+}
+
+fn contains(outer: Span, inner: Span) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Evaluates `expression` the way `bol mug` would print it: a variable, a
+/// string, or a `+` chain of them, using the variables in `vm`.
+pub fn evaluate(vm: &VM, expression: &str) -> Result<Value, NepalError> {
+    const PRINT: &str = "bol mug ";
+    let tokens = Lexer::new(&format!("{}{}", PRINT, expression)).tokenize()?;
+    let mut statements = Parser::new(tokens).parse()?;
+    if statements.len() > 1 {
+        let extra = &statements[1].span;
+        let text = expression.get(extra.start - PRINT.len()..).unwrap_or_default();
+        let word = text.split_whitespace().next().unwrap_or_default();
+        return Err(Diagnostic::new(ErrorCode::UnexpectedToken).with_arg(format!("`{}`", word)).into());
+    }
+    let parts = match statements.pop().map(|statement| statement.node) {
+        Some(Statement::Print(name)) => vec![StrSegment::Identifier(name)],
+        Some(Statement::PrintString(parts)) => parts,
+        _ => return Err(Diagnostic::new(ErrorCode::ExpectedPrintable).with_arg("nothing").into()),
+    };
+
+    let mut result: Option<Value> = None;
+    for part in parts {
+        let value = match part {
+            StrSegment::Literal(text) => Value::from(text),
+            StrSegment::Identifier(name) => vm
+                .get_global(&name.node)
+                .cloned()
+                .ok_or_else(|| Diagnostic::new(ErrorCode::UndefinedVariable).with_arg(&name.node))?,
+        };
+        result = Some(match result {
+            Some(text) => BinaryOp::Concat.apply(&text, &value)?,
+            None => value,
+        });
+    }
+    Ok(result.unwrap_or_else(|| Value::from("")))
+}
+
+/// `value` as the debugger shows it, with strings quoted.
+pub fn show(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("{:?}", text),
+        other => other.to_string(),
+    }
+}
+
+const HELP: &str = "\
+break <line>   b   pause whenever <line> is about to run
+delete <line>  d   remove the breakpoint on <line>
+step           s   run to the next line, going into yedi blocks
+next           n   run to the next line, stepping over yedi blocks
+stepi          si  run one bytecode instruction
+continue       c   run until a breakpoint or the end
+print <expr>   p   show a variable or expression, e.g. p \"hi \" + naam
+watch <expr>   w   show <expr> every time the program pauses
+unwatch <n>        stop showing watch <n>
+vars               show every variable
+stack              show the operand stack, top last
+list           l   show the source around the current line
+quit           q   stop the program
+An empty line repeats the last command.";
+
+/// An interactive debugger that reads commands from its console whenever
+/// the program pauses.
+pub struct Debugger {
+    stepper: Stepper,
+    console: Console,
+    source: Vec<String>,
+    watches: Vec<String>,
+    last_command: String,
+    lang: Lang,
+}
+
+impl Debugger {
+    /// Debugs `program`, compiled from `source`, reading commands from and
+    /// writing to `console`.
+    pub fn new(program: &BytecodeProgram, source: &str, console: Console) -> Self {
+        Self {
+            stepper: Stepper::new(program),
+            console,
+            source: source.lines().map(str::to_string).collect(),
+            watches: Vec::new(),
+            last_command: String::new(),
+            lang: Lang::default(),
+        }
+    }
+
+    /// Shows errors in expressions in `lang`.
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Writes a line of debugger output. There is nowhere to report a
+    /// failure, so it is ignored.
+    fn say(&mut self, text: impl std::fmt::Display) {
+        let _ = self.console.print_line(text);
+    }
+
+    fn show_pause(&mut self, vm: &VM, pause: Pause) {
+        let Some(instruction) = vm.program().instructions.get(vm.ip()) else {
+            return;
+        };
+        let line = instruction.line_number;
+        if let Pause::Breakpoint(line) = pause {
+            self.say(format!("Breakpoint on line {}", line));
+        }
+        let text = self.source.get(line.wrapping_sub(1)).cloned().unwrap_or_default();
+        self.say(format!("{:>4} | {}", line, text));
+        if pause == Pause::Instruction {
+            self.say(format!("       next instruction {:04} {:?}", vm.ip(), instruction.opcode));
+        }
+        for (number, expression) in self.watches.clone().iter().enumerate() {
+            let value = self.evaluate(vm, expression);
+            self.say(format!("watch {}: {} = {}", number + 1, expression, value));
+        }
+    }
+
+    /// `expression`'s value, or the error evaluating it, for display.
+    fn evaluate(&self, vm: &VM, expression: &str) -> String {
+        match evaluate(vm, expression) {
+            Ok(value) => show(&value),
+            Err(error) => match error.diagnostic() {
+                Some(diagnostic) => format!("<{}>", diagnostic.message_in(self.lang)),
+                None => format!("<{}>", error),
+            },
+        }
+    }
+
+    /// Runs one command. Returns how to go on if it resumes the program.
+    fn command(&mut self, vm: &VM, command: &str) -> Option<Resume> {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let argument = argument.trim();
+        match name {
+            "" => {}
+            "s" | "step" => {
+                self.stepper.step_into();
+                return Some(Resume::Continue);
+            }
+            "n" | "next" => {
+                self.stepper.step_over(vm);
+                return Some(Resume::Continue);
+            }
+            "si" | "stepi" => {
+                self.stepper.step_instruction();
+                return Some(Resume::Continue);
+            }
+            "c" | "continue" => {
+                self.stepper.resume();
+                return Some(Resume::Continue);
+            }
+            "q" | "quit" => return Some(Resume::Stop),
+            "b" | "break" => match argument.parse() {
+                Ok(line) if self.stepper.add_breakpoint(line) => self.say(format!("Breakpoint on line {}", line)),
+                Ok(line) if self.stepper.breakpoints().any(|set| set == line) => {
+                    self.say(format!("There is already a breakpoint on line {}", line))
+                }
+                Ok(line) => self.say(format!("No statement starts on line {}", line)),
+                Err(_) => self.say("Usage: break <line>"),
+            },
+            "d" | "delete" => match argument.parse() {
+                Ok(line) if self.stepper.remove_breakpoint(line) => {
+                    self.say(format!("Removed the breakpoint on line {}", line))
+                }
+                Ok(line) => self.say(format!("There is no breakpoint on line {}", line)),
+                Err(_) => self.say("Usage: delete <line>"),
+            },
+            "p" | "print" if argument.is_empty() => self.say("Usage: print <expression>"),
+            "p" | "print" => {
+                let value = self.evaluate(vm, argument);
+                self.say(value);
+            }
+            "w" | "watch" if argument.is_empty() => self.say("Usage: watch <expression>"),
+            "w" | "watch" => {
+                self.watches.push(argument.to_string());
+                let value = self.evaluate(vm, argument);
+                self.say(format!("watch {}: {} = {}", self.watches.len(), argument, value));
+            }
+            "unwatch" => match argument.parse::<usize>() {
+                Ok(number) if (1..=self.watches.len()).contains(&number) => {
+                    let expression = self.watches.remove(number - 1);
+                    self.say(format!("Stopped watching {}", expression));
+                }
+                _ => self.say("Usage: unwatch <n>, where <n> is the number shown next to the watch"),
+            },
+            "vars" => {
+                let lines: Vec<String> =
+                    vm.globals().map(|(name, value)| format!("{} = {}", name, show(value))).collect();
+                if lines.is_empty() {
+                    self.say("No variable has a value yet");
+                }
+                lines.into_iter().for_each(|line| self.say(line));
+            }
+            "stack" => {
+                let lines: Vec<String> = vm.stack().iter().map(show).collect();
+                if lines.is_empty() {
+                    self.say("The stack is empty");
+                }
+                lines.into_iter().for_each(|line| self.say(line));
+            }
+            "l" | "list" => {
+                let current = vm.program().instructions.get(vm.ip()).map_or(0, |i| i.line_number);
+                let first = current.saturating_sub(3).max(1);
+                let last = (current + 3).min(self.source.len());
+                for line in first..=last {
+                    let marker = if line == current { '>' } else { ' ' };
+                    let breakpoint = if self.stepper.breakpoints().any(|set| set == line) { '*' } else { ' ' };
+                    let text = self.source[line - 1].clone();
+                    self.say(format!("{}{}{:>3} | {}", marker, breakpoint, line, text));
+                }
+            }
+            "h" | "help" => self.say(HELP),
+            _ => self.say(format!("Unknown command `{}`. Type help for commands.", name)),
+        }
+        None
+    }
+}
+
+impl DebugHook for Debugger {
+    fn before(&mut self, vm: &VM) -> Resume {
+        let Some(pause) = self.stepper.pause(vm) else {
+            return Resume::Continue;
+        };
+        self.show_pause(vm, pause);
+        loop {
+            // Stop the program at the end of input instead of waiting forever
+            let Ok(Some(line)) = self.console.read_entry() else {
+                return Resume::Stop;
+            };
+            let command = if line.is_empty() { self.last_command.clone() } else { line };
+            self.last_command = command.clone();
+            if let Some(resume) = self.command(vm, &command) {
+                return resume;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capability::Capabilities;
+    use crate::compiler::Compiler;
+
+    const SOURCE: &str = "\
+oi mug naam = \"Sita\"
+yedi naam babaal \"Sita\" bhane
+  oi mug umer = 20
+  bol mug naam
+sakiyo
+bol mug \"bye \" + naam
+";
+
+    fn compile(source: &str) -> BytecodeProgram {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap()
+    }
+
+    /// The debugger's output for `commands`, and the program's own output.
+    fn debug(commands: &str) -> (String, String) {
+        let program = compile(SOURCE);
+        let (console, transcript) = Console::memory(commands);
+        let debugger = Debugger::new(&program, SOURCE, console.with_prompt("(debug) "));
+        let (console, output) = Console::memory("");
+        VM::new(program)
+            .with_console(console)
+            .with_capabilities(Capabilities::console())
+            .with_debug_hook(debugger)
+            .run()
+            .unwrap();
+        (transcript.contents(), output.contents())
+    }
+
+    /// The source lines shown at each pause.
+    fn source_lines(transcript: &str) -> Vec<&str> {
+        transcript
+            .lines()
+            .map(|line| line.trim_start_matches("(debug) "))
+            .filter(|line| line.contains(" | "))
+            .collect()
+    }
+
+    #[test]
+    fn steps_into_and_over_yedi_blocks() {
+        let (transcript, output) = debug("s\ns\ns\ns\nc\n");
+        let lines = source_lines(&transcript);
+        assert_eq!(
+            lines,
+            [
+                "   1 | oi mug naam = \"Sita\"",
+                "   2 | yedi naam babaal \"Sita\" bhane",
+                "   3 |   oi mug umer = 20",
+                "   4 |   bol mug naam",
+                "   6 | bol mug \"bye \" + naam",
+            ]
+        );
+        assert_eq!(output, "Sita\nbye Sita\n");
+
+        let (transcript, _) = debug("n\nn\nn\n");
+        let lines = source_lines(&transcript);
+        assert_eq!(
+            lines,
+            ["   1 | oi mug naam = \"Sita\"", "   2 | yedi naam babaal \"Sita\" bhane", "   6 | bol mug \"bye \" + naam"]
+        );
+    }
+
+    #[test]
+    fn breakpoints_watches_and_inspection() {
+        let (transcript, output) = debug("b 5\nb 4\nw \"hi \" + naam\nw umer\nc\nvars\np umer\nsi\nstack\nq\n");
+        assert!(transcript.contains("(debug) No statement starts on line 5\n"));
+        assert!(transcript.contains("watch 2: umer = <`umer` is used before it has a value>\n"));
+        assert!(transcript.contains(
+            "Breakpoint on line 4\n   4 |   bol mug naam\nwatch 1: \"hi \" + naam = \"hi Sita\"\nwatch 2: umer = 20\n"
+        ));
+        assert!(transcript.contains("(debug) naam = \"Sita\"\numer = 20\n"));
+        assert!(transcript.contains("next instruction 0009 Print\nwatch 1"));
+        assert!(transcript.contains("(debug) \"Sita\"\n(debug) "));
+        // `quit` stops the program before it prints
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn the_program_stops_at_the_end_of_input() {
+        let (transcript, output) = debug("");
+        assert_eq!(transcript, "   1 | oi mug naam = \"Sita\"\n(debug) ");
+        assert_eq!(output, "");
+    }
+}
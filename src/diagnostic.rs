@@ -0,0 +1,614 @@
+//! Structured diagnostics for LyangLang.
+//!
+//! Every problem the toolchain reports is a [`Diagnostic`]: a stable
+//! [`ErrorCode`], the arguments its message needs, a primary span and any
+//! secondary labels or notes. Diagnostics render as source excerpts with
+//! carets pointing at the offending code.
+
+use crate::messages::{self, Heading, Lang};
+use crate::span::Span;
+use std::fmt;
+
+/// Declares [`ErrorCode`] with [`ErrorCode::ALL`] and [`ErrorCode::category`]
+/// from one list, so that neither can miss a code.
+macro_rules! error_codes {
+    ($($category:ident { $($code:ident,)* })*) => {
+        /// Stable identifier for every kind of diagnostic.
+        ///
+        /// Codes are grouped by phase: `LY00xx` lexer, `LY01xx` parser,
+        /// `LY02xx` compiler, `LY03xx` runtime, `LY04xx` bytecode files,
+        /// `LY05xx` lints and `LY09xx` command line.
+        /// Never renumber an existing code; add new ones instead.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($($code,)*)*
+        }
+
+        impl ErrorCode {
+            /// Every code, in numbering order.
+            pub const ALL: [ErrorCode; [$($(ErrorCode::$code,)*)*].len()] = [$($(ErrorCode::$code,)*)*];
+
+            /// The phase that reports this code.
+            pub fn category(self) -> Category {
+                match self {
+                    $($(ErrorCode::$code)|* => Category::$category,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    Lexer {
+        UnexpectedCharacter,
+        ExpectedEqualsAfterBang,
+        NumberTooLarge,
+        UnterminatedString,
+        UnterminatedBlockComment,
+    }
+    Parser {
+        UnexpectedToken,
+        ExpectedMugOperation,
+        ExpectedFeri,
+        ExpectedIdentifier,
+        ExpectedComparison,
+        ExpectedStringLiteral,
+        ExpectedBhane,
+        ExpectedEquals,
+        ExpectedValue,
+        ExpectedCommaOrLai,
+        ExpectedPrintable,
+        UnexpectedEndOfFile,
+        ExpectedArgument,
+        ExpectedTypeName,
+        InputAnnotation,
+        NestingTooDeep,
+    }
+    Compiler {
+        MissingOperands,
+        EmptyConcatenation,
+        UnknownFunction,
+        WrongArgumentCount,
+        OperandType,
+        AnnotationMismatch,
+    }
+    Runtime {
+        StackUnderflow,
+        InvalidStringIndex,
+        InvalidVariableIndex,
+        InvalidOperands,
+        DivisionByZero,
+        ConditionNotBoolean,
+        InvalidComparison,
+        InputFailed,
+        OutputFailed,
+        NumberOverflow,
+        UndefinedVariable,
+        ArgumentType,
+        NativeFailed,
+        InvalidNativeIndex,
+        ResourceLimit,
+        PermissionDenied,
+        NotANumber,
+        FileReadFailed,
+        FileWriteFailed,
+        EmptyRange,
+        ClockBeforeEpoch,
+    }
+    Bytecode {
+        NotBytecode,
+        UnsupportedBytecodeVersion,
+        CorruptBytecode,
+        InvalidJumpTarget,
+        StackMismatch,
+    }
+    Lint {
+        UnusedVariable,
+        UnreadAssignment,
+        ShadowedDeclaration,
+        NumberComparedToString,
+        UnreachableCode,
+        EmptyYedi,
+    }
+    CommandLine {
+        FileRead,
+        ProjectExists,
+        NoInputFile,
+        FileWrite,
+        NotFormatted,
+        InvalidLintConfig,
+    }
+}
+
+/// The phase of the toolchain that reports a code, which is also the
+/// hundreds of its number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Lexer,
+    Parser,
+    Compiler,
+    Runtime,
+    Bytecode,
+    Lint,
+    CommandLine,
+}
+
+impl ErrorCode {
+    /// The code as printed in diagnostics, e.g. `LY0104`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "LY0001",
+            ErrorCode::ExpectedEqualsAfterBang => "LY0002",
+            ErrorCode::NumberTooLarge => "LY0003",
+            ErrorCode::UnterminatedString => "LY0004",
+            ErrorCode::UnterminatedBlockComment => "LY0005",
+
+            ErrorCode::UnexpectedToken => "LY0101",
+            ErrorCode::ExpectedMugOperation => "LY0102",
+            ErrorCode::ExpectedFeri => "LY0103",
+            ErrorCode::ExpectedIdentifier => "LY0104",
+            ErrorCode::ExpectedComparison => "LY0105",
+            ErrorCode::ExpectedStringLiteral => "LY0106",
+            ErrorCode::ExpectedBhane => "LY0107",
+            ErrorCode::ExpectedEquals => "LY0108",
+            ErrorCode::ExpectedValue => "LY0109",
+            ErrorCode::ExpectedCommaOrLai => "LY0110",
+            ErrorCode::ExpectedPrintable => "LY0111",
+            ErrorCode::UnexpectedEndOfFile => "LY0112",
+            ErrorCode::ExpectedArgument => "LY0113",
+            ErrorCode::ExpectedTypeName => "LY0114",
+            ErrorCode::InputAnnotation => "LY0115",
+            ErrorCode::NestingTooDeep => "LY0116",
+
+            ErrorCode::MissingOperands => "LY0201",
+            ErrorCode::EmptyConcatenation => "LY0202",
+            ErrorCode::UnknownFunction => "LY0203",
+            ErrorCode::WrongArgumentCount => "LY0204",
+            ErrorCode::OperandType => "LY0205",
+            ErrorCode::AnnotationMismatch => "LY0207",
+
+            ErrorCode::StackUnderflow => "LY0301",
+            ErrorCode::InvalidStringIndex => "LY0302",
+            ErrorCode::InvalidVariableIndex => "LY0303",
+            ErrorCode::InvalidOperands => "LY0304",
+            ErrorCode::DivisionByZero => "LY0305",
+            ErrorCode::ConditionNotBoolean => "LY0306",
+            ErrorCode::InvalidComparison => "LY0307",
+            ErrorCode::InputFailed => "LY0308",
+            ErrorCode::OutputFailed => "LY0309",
+            ErrorCode::NumberOverflow => "LY0310",
+            ErrorCode::UndefinedVariable => "LY0311",
+            ErrorCode::ArgumentType => "LY0312",
+            ErrorCode::NativeFailed => "LY0313",
+            ErrorCode::InvalidNativeIndex => "LY0314",
+            ErrorCode::ResourceLimit => "LY0315",
+            ErrorCode::PermissionDenied => "LY0316",
+            ErrorCode::NotANumber => "LY0317",
+            ErrorCode::FileReadFailed => "LY0318",
+            ErrorCode::FileWriteFailed => "LY0319",
+            ErrorCode::EmptyRange => "LY0320",
+            ErrorCode::ClockBeforeEpoch => "LY0321",
+
+            ErrorCode::NotBytecode => "LY0401",
+            ErrorCode::UnsupportedBytecodeVersion => "LY0402",
+            ErrorCode::CorruptBytecode => "LY0403",
+            ErrorCode::InvalidJumpTarget => "LY0404",
+            ErrorCode::StackMismatch => "LY0405",
+
+            ErrorCode::UnusedVariable => "LY0501",
+            ErrorCode::UnreadAssignment => "LY0502",
+            ErrorCode::ShadowedDeclaration => "LY0503",
+            ErrorCode::NumberComparedToString => "LY0504",
+            ErrorCode::UnreachableCode => "LY0505",
+            ErrorCode::EmptyYedi => "LY0506",
+
+            ErrorCode::FileRead => "LY0901",
+            ErrorCode::ProjectExists => "LY0902",
+            ErrorCode::NoInputFile => "LY0903",
+            ErrorCode::FileWrite => "LY0904",
+            ErrorCode::NotFormatted => "LY0905",
+            ErrorCode::InvalidLintConfig => "LY0906",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Text for secondary labels and footnotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Note {
+    /// Points at the `yedi` whose condition is incomplete.
+    ConditionStartsHere,
+    /// Points at a nested `/*` that was never closed.
+    NestedCommentOpened,
+    /// The VM reached a state the compiler should never produce.
+    InternalError,
+    /// Points at a statement that always fails, before unreachable code.
+    ProgramStopsHere,
+    /// Points at where a variable was given the type it has.
+    TypeGivenHere,
+    /// Shows how to turn the text in variable `{0}` into a number.
+    ConvertToNumber,
+}
+
+/// Argument words that are translated along with the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Word {
+    Number,
+    String,
+    Boolean,
+    /// A value whose type is not known until the program runs
+    AnyType,
+    // Units of resource limits
+    Steps,
+    Seconds,
+    StackSlots,
+    NestedBlocks,
+    Bytes,
+    // Parts of bytecode files
+    BytecodeVersion,
+    StringPool,
+    VariableTable,
+    NativeTable,
+    InstructionList,
+    LineTable,
+    // How a part of a bytecode file is damaged
+    EndsInside,
+    NumberTooLarge,
+    LongerThanFile,
+    InvalidUtf8,
+    UnknownOpcode,
+    WrongInstructionCount,
+    DataAfter,
+}
+
+/// One argument substituted into a diagnostic's templates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    /// Shown as-is in every language, e.g. a variable name or token.
+    Text(String),
+    /// Translated into the display language.
+    Word(Word),
+    /// What another code expected to find, i.e. its translated label.
+    Expected(ErrorCode),
+}
+
+impl Arg {
+    fn in_lang(&self, lang: Lang) -> String {
+        match self {
+            Arg::Text(text) => text.clone(),
+            Arg::Word(word) => messages::word(*word, lang).to_string(),
+            Arg::Expected(code) => messages::label(*code, lang).unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// A secondary span with its explanation.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub note: Note,
+}
+
+/// A single reportable problem.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: ErrorCode,
+    pub args: Vec<Arg>,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<Note>,
+}
+
+impl Diagnostic {
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            code,
+            args: Vec::new(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Appends the next positional argument for the message templates.
+    pub fn with_arg(mut self, arg: impl ToString) -> Self {
+        self.args.push(Arg::Text(arg.to_string()));
+        self
+    }
+
+    /// Appends an argument that is translated with the message.
+    pub fn with_word(mut self, word: Word) -> Self {
+        self.args.push(Arg::Word(word));
+        self
+    }
+
+    /// Appends what `code` expected, for errors reported in its place.
+    pub fn with_expected(mut self, code: ErrorCode) -> Self {
+        self.args.push(Arg::Expected(code));
+        self
+    }
+
+    /// Sets the primary span. Spans without a known location are ignored.
+    pub fn with_span(mut self, span: Span) -> Self {
+        if span.is_known() {
+            self.span = Some(span);
+        }
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, note: Note) -> Self {
+        if span.is_known() {
+            self.labels.push(Label { span, note });
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    /// The message in English.
+    pub fn message(&self) -> String {
+        self.message_in(Lang::En)
+    }
+
+    pub fn message_in(&self, lang: Lang) -> String {
+        self.fill(messages::message(self.code, lang), lang)
+    }
+
+    pub fn label_in(&self, lang: Lang) -> Option<String> {
+        messages::label(self.code, lang).map(|text| self.fill(text, lang))
+    }
+
+    pub fn help_in(&self, lang: Lang) -> Option<String> {
+        messages::help(self.code, lang).map(|text| self.fill(text, lang))
+    }
+
+    /// Renders the diagnostic in `lang` with excerpts from `source`, which `file_name` names.
+    pub fn render(&self, source: &str, file_name: &str, lang: Lang) -> String {
+        self.render_as(Heading::Error, source, file_name, lang)
+    }
+
+    /// Like [`Diagnostic::render`], but headed e.g. "warning" instead of "error".
+    pub(crate) fn render_as(&self, heading: Heading, source: &str, file_name: &str, lang: Lang) -> String {
+        let mut out = format!(
+            "{}[{}]: {}\n",
+            messages::heading(heading, lang),
+            self.code,
+            self.message_in(lang)
+        );
+
+        let mut marks: Vec<(Span, char, Option<String>)> = Vec::new();
+        if let Some(span) = self.span {
+            marks.push((span, '^', self.label_in(lang)));
+        }
+        for label in &self.labels {
+            marks.push((label.span, '-', Some(messages::note(label.note, lang).to_string())));
+        }
+        marks.retain(|(span, _, _)| line_at(source, span).is_some());
+
+        let width = marks
+            .iter()
+            .map(|(span, _, _)| span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        if let Some((span, _, _)) = marks.first() {
+            out.push_str(&format!("{}--> {}:{}:{}\n", gutter, file_name, span.line, span.column + 1));
+            out.push_str(&format!("{} |\n", gutter));
+
+            let mut line_numbers: Vec<usize> = marks.iter().map(|(span, _, _)| span.line).collect();
+            line_numbers.sort_unstable();
+            line_numbers.dedup();
+
+            for line_number in line_numbers {
+                let text = marks
+                    .iter()
+                    .filter(|(span, _, _)| span.line == line_number)
+                    .find_map(|(span, _, _)| line_at(source, span))
+                    .expect("marks not in the source were dropped");
+                out.push_str(&format!("{:>width$} | {}\n", line_number, text, width = width));
+                for (span, mark, label) in marks.iter().filter(|(span, _, _)| span.line == line_number) {
+                    let length = underline_length(source, span, text);
+                    out.push_str(&format!(
+                        "{} | {}{}",
+                        gutter,
+                        " ".repeat(span.column),
+                        mark.to_string().repeat(length)
+                    ));
+                    if let Some(label) = label {
+                        out.push(' ');
+                        out.push_str(label);
+                    }
+                    out.push('\n');
+                }
+            }
+            out.push_str(&format!("{} |\n", gutter));
+        } else if !file_name.is_empty() {
+            // The source is not available, e.g. when running a `.nbc` file
+            match self.span {
+                Some(span) => out.push_str(&format!(
+                    "{}--> {}:{}:{}\n",
+                    gutter,
+                    file_name,
+                    span.line,
+                    span.column + 1
+                )),
+                None => out.push_str(&format!("{}--> {}\n", gutter, file_name)),
+            }
+        }
+
+        for note in &self.notes {
+            out.push_str(&format!(
+                "{} = {}: {}\n",
+                gutter,
+                messages::heading(Heading::Note, lang),
+                self.fill(messages::note(*note, lang), lang)
+            ));
+        }
+        if let Some(help) = self.help_in(lang) {
+            out.push_str(&format!("{} = {}: {}\n", gutter, messages::heading(Heading::Help, lang), help));
+        }
+        out
+    }
+
+    /// Replaces `{0}`, `{1}`, ... in `template` with the matching argument.
+    fn fill(&self, template: &str, lang: Lang) -> String {
+        let mut text = template.to_string();
+        for (i, arg) in self.args.iter().enumerate() {
+            text = text.replace(&format!("{{{}}}", i), &arg.in_lang(lang));
+        }
+        text
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error[{}]: {}", self.code, self.message())?;
+        if let Some(span) = self.span {
+            write!(f, " (line {}, column {})", span.line, span.column + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The line of `source` that `span` starts on, found from its byte offset
+/// so that rendering does not scan the whole source.
+fn line_at<'a>(source: &'a str, span: &Span) -> Option<&'a str> {
+    let start = span.start;
+    if !span.is_known() || start > source.len() || !source.is_char_boundary(start) {
+        return None;
+    }
+    let begin = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    if begin == source.len() {
+        // Past the end of the last line
+        return None;
+    }
+    let end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+    let line = &source[begin..end];
+    Some(line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// Number of carets needed to underline `span` on its first line (at least one).
+fn underline_length(source: &str, span: &Span, line_text: &str) -> usize {
+    let on_line = line_text.chars().count().saturating_sub(span.column);
+    let covered = source
+        .get(span.start..span.end)
+        .map(|text| text.chars().take_while(|&c| c != '\n').count())
+        .unwrap_or(1);
+    covered.min(on_line).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_code_excerpt_and_caret() {
+        let source = "bol mug \"hi\"\noi mug = 5\n";
+        let diagnostic = Diagnostic::new(ErrorCode::ExpectedIdentifier)
+            .with_arg("`=`")
+            .with_arg("`oi mug`")
+            .with_span(Span::new(20, 21, 2, 7));
+        let rendered = diagnostic.render(source, "main.nbh", Lang::En);
+        assert_eq!(
+            rendered,
+            "error[LY0104]: expected a variable name after `oi mug`, found `=`\n\
+             \x20--> main.nbh:2:8\n\
+             \x20 |\n\
+             2 | oi mug = 5\n\
+             \x20 |        ^ expected a variable name\n\
+             \x20 |\n"
+        );
+    }
+
+    #[test]
+    fn renders_secondary_labels_and_help() {
+        let source = "yedi rang babaal \"rato\"\nbol mug rang\n";
+        let diagnostic = Diagnostic::new(ErrorCode::ExpectedBhane)
+            .with_arg("`bol mug`")
+            .with_span(Span::new(24, 31, 2, 0))
+            .with_label(Span::new(0, 4, 1, 0), Note::ConditionStartsHere);
+        let rendered = diagnostic.render(source, "main.nbh", Lang::En);
+        assert!(rendered.contains("1 | yedi rang babaal \"rato\"\n  | ---- condition starts here\n"));
+        assert!(rendered.contains("2 | bol mug rang\n  | ^^^^^^^ expected `bhane`\n"));
+    }
+
+    #[test]
+    fn renders_the_line_a_span_starts_on() {
+        let source = "bol mug \"hi\"\r\noi mug = 5\r\n";
+        let diagnostic = Diagnostic::new(ErrorCode::ExpectedIdentifier)
+            .with_arg("`=`")
+            .with_arg("`oi mug`")
+            .with_span(Span::new(21, 22, 2, 7));
+        assert!(diagnostic.render(source, "main.nbh", Lang::En).contains("2 | oi mug = 5\n  |        ^ "));
+
+        // A span past the end of the source has no excerpt
+        let past = Diagnostic::new(ErrorCode::UnexpectedEndOfFile).with_span(Span::new(26, 26, 3, 0));
+        assert!(!past.render(source, "main.nbh", Lang::En).contains(" | "));
+    }
+
+    #[test]
+    fn renders_in_nepali() {
+        let source = "bol mug \"hi\"\noi mug = 5\n";
+        let diagnostic = Diagnostic::new(ErrorCode::ExpectedIdentifier)
+            .with_arg("`=`")
+            .with_arg("`oi mug`")
+            .with_span(Span::new(20, 21, 2, 7));
+        let rendered = diagnostic.render(source, "main.nbh", Lang::Ne);
+        assert!(rendered.starts_with("त्रुटि[LY0104]: `oi mug` पछि चरको नाम चाहिन्छ, तर `=` भेटियो\n"));
+        assert!(rendered.contains("^ चरको नाम चाहिन्छ"));
+
+        let rendered = diagnostic.render(source, "main.nbh", Lang::NeRom);
+        assert!(rendered.starts_with("truti[LY0104]: `oi mug` pachhi char ko naam chahinchha"));
+    }
+
+    #[test]
+    fn word_arguments_are_translated() {
+        let diagnostic = Diagnostic::new(ErrorCode::InvalidOperands)
+            .with_arg("jod")
+            .with_word(Word::String)
+            .with_word(Word::Number);
+        assert_eq!(diagnostic.message_in(Lang::En), "cannot use `jod` on a string and a number");
+        assert_eq!(diagnostic.message_in(Lang::Ne), "स्ट्रिङ र संख्या मा `jod` प्रयोग गर्न सकिँदैन");
+    }
+
+    #[test]
+    fn expected_arguments_use_the_other_codes_label() {
+        let diagnostic = Diagnostic::new(ErrorCode::UnexpectedEndOfFile).with_expected(ErrorCode::ExpectedEquals);
+        assert_eq!(diagnostic.message_in(Lang::En), "unexpected end of file, expected `=`");
+        assert_eq!(diagnostic.message_in(Lang::NeRom), "file anapekshit rup ma sakiyo, `=` chahinchha");
+    }
+
+    #[test]
+    fn categories_match_code_numbers() {
+        for code in ErrorCode::ALL {
+            let hundreds = match code.category() {
+                Category::Lexer => "LY00",
+                Category::Parser => "LY01",
+                Category::Compiler => "LY02",
+                Category::Runtime => "LY03",
+                Category::Bytecode => "LY04",
+                Category::Lint => "LY05",
+                Category::CommandLine => "LY09",
+            };
+            assert!(code.as_str().starts_with(hundreds), "{:?} is {}", code, code.as_str());
+        }
+        // In numbering order, with no number used twice
+        let numbers: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn display_includes_code_and_location() {
+        let diagnostic = Diagnostic::new(ErrorCode::DivisionByZero).with_span(Span::new(0, 3, 4, 2));
+        assert_eq!(diagnostic.to_string(), "error[LY0305]: division by zero (line 4, column 3)");
+    }
+}
@@ -0,0 +1,199 @@
+//! Human- and machine-readable listings of compiled programs.
+//!
+//! Used by `lyangpiler disasm`. Operands are shown resolved: string-pool
+//! entries as their text, variable and function slots by name, and jump
+//! targets as labels (`L0`, `L1`, ... in address order).
+
+use crate::bytecode::{BytecodeProgram, Opcode};
+use serde_json::{json, Value as Json};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// A text listing of `program`. With the `source` it was compiled from, each
+/// statement's first line is shown above its instructions.
+pub fn disassemble(program: &BytecodeProgram, source: Option<&str>) -> String {
+    let labels = labels(program);
+    let lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+    let mut out = format!(
+        "; {} instructions, {} strings, {} variables, {} native functions\n",
+        program.instructions.len(),
+        program.string_pool.len(),
+        program.variable_names.len(),
+        program.native_names.len()
+    );
+
+    let mut last_line = 0;
+    for (offset, instruction) in program.instructions.iter().enumerate() {
+        let line = instruction.span.line;
+        if line != last_line && line > 0 {
+            match lines.get(line - 1) {
+                Some(text) => writeln!(out, "\n; {:>4} | {}", line, text.trim_end()),
+                None => writeln!(out, "\n; line {}", line),
+            }
+            .unwrap();
+            last_line = line;
+        }
+        if let Some(label) = labels.get(&offset) {
+            writeln!(out, "{}:", label).unwrap();
+        }
+        let (name, _) = parts(instruction.opcode);
+        let operand = describe(program, instruction.opcode, &labels);
+        let text = format!("  {:04}  {:<14}{}", offset, name, operand);
+        writeln!(out, "{}", text.trim_end()).unwrap();
+    }
+    // Jumps past the last instruction end the program
+    if let Some(label) = labels.get(&program.instructions.len()) {
+        writeln!(out, "{}:", label).unwrap();
+    }
+    out
+}
+
+/// The listing as JSON, for tools:
+///
+/// ```json
+/// {"strings": [...], "variables": [...], "natives": [...],
+///  "instructions": [{"offset": 0, "opcode": "PushNumber", "operands": [10],
+///                    "detail": "10", "label": null, "line": 1, "column": 1}]}
+/// ```
+///
+/// `label` is set on instructions that are jump targets; `line` and `column`
+/// are `null` for instructions without a source location.
+pub fn to_json(program: &BytecodeProgram) -> Json {
+    let labels = labels(program);
+    let instructions: Vec<Json> = program
+        .instructions
+        .iter()
+        .enumerate()
+        .map(|(offset, instruction)| {
+            let (name, operands) = parts(instruction.opcode);
+            let (line, column) = match instruction.span.is_known() {
+                true => (Some(instruction.span.line), Some(instruction.span.column + 1)),
+                false => (None, None),
+            };
+            json!({
+                "offset": offset,
+                "opcode": name,
+                "operands": operands,
+                "detail": describe(program, instruction.opcode, &labels),
+                "label": labels.get(&offset),
+                "line": line,
+                "column": column,
+            })
+        })
+        .collect();
+    json!({
+        "strings": *program.string_pool,
+        "variables": *program.variable_names,
+        "natives": *program.native_names,
+        "instructions": instructions,
+    })
+}
+
+/// A label for every jump target, numbered in address order.
+fn labels(program: &BytecodeProgram) -> BTreeMap<usize, String> {
+    let mut targets: Vec<usize> = program
+        .instructions
+        .iter()
+        .filter_map(|instruction| instruction.opcode.jump_target())
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+        .into_iter()
+        .enumerate()
+        .map(|(number, target)| (target, format!("L{}", number)))
+        .collect()
+}
+
+/// The opcode's name and raw operands.
+fn parts(opcode: Opcode) -> (&'static str, Vec<i64>) {
+    match opcode {
+        Opcode::PushNumber(n) => ("PushNumber", vec![i64::from(n)]),
+        Opcode::PushString(index) => ("PushString", vec![index as i64]),
+        Opcode::PushVariable(index) => ("PushVariable", vec![index as i64]),
+        Opcode::Pop => ("Pop", vec![]),
+        Opcode::StoreVariable(index) => ("StoreVariable", vec![index as i64]),
+        Opcode::LoadVariable(index) => ("LoadVariable", vec![index as i64]),
+        Opcode::Add => ("Add", vec![]),
+        Opcode::Subtract => ("Subtract", vec![]),
+        Opcode::Multiply => ("Multiply", vec![]),
+        Opcode::Divide => ("Divide", vec![]),
+        Opcode::Concat => ("Concat", vec![]),
+        Opcode::Print => ("Print", vec![]),
+        Opcode::Input => ("Input", vec![]),
+        Opcode::JumpIfTrue(target) => ("JumpIfTrue", vec![target as i64]),
+        Opcode::JumpIfFalse(target) => ("JumpIfFalse", vec![target as i64]),
+        Opcode::Jump(target) => ("Jump", vec![target as i64]),
+        Opcode::CallNative(index, argc) => ("CallNative", vec![index as i64, argc as i64]),
+        Opcode::Equal => ("Equal", vec![]),
+        Opcode::NotEqual => ("NotEqual", vec![]),
+        Opcode::Return => ("Return", vec![]),
+        Opcode::Halt => ("Halt", vec![]),
+    }
+}
+
+/// The operands with indexes resolved, e.g. `"Namaste"` or `L2`.
+fn describe(program: &BytecodeProgram, opcode: Opcode, labels: &BTreeMap<usize, String>) -> String {
+    let name = |table: &[String], index: usize| match table.get(index) {
+        Some(name) => name.clone(),
+        None => format!("#{} (invalid)", index),
+    };
+    match opcode {
+        Opcode::PushNumber(n) => n.to_string(),
+        Opcode::PushString(index) => match program.string_pool.get(index) {
+            Some(text) => format!("{:?}", text),
+            None => format!("#{} (invalid)", index),
+        },
+        Opcode::PushVariable(index) | Opcode::StoreVariable(index) | Opcode::LoadVariable(index) => {
+            name(&program.variable_names, index)
+        }
+        Opcode::Jump(target) | Opcode::JumpIfTrue(target) | Opcode::JumpIfFalse(target) => labels[&target].clone(),
+        Opcode::CallNative(index, argc) => format!("{}/{}", name(&program.native_names, index), argc),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    const SOURCE: &str = "oi mug rang = \"rato\"\nyedi rang babaal \"rato\" bhane\n  bol mug \"ho\"\nsakiyo\n";
+
+    fn compile(source: &str) -> BytecodeProgram {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lists_resolved_operands_labels_and_source() {
+        let listing = disassemble(&compile(SOURCE), Some(SOURCE));
+        assert!(listing.contains(";    2 | yedi rang babaal \"rato\" bhane\n"), "{}", listing);
+        assert!(listing.contains("StoreVariable rang\n"), "{}", listing);
+        assert!(listing.contains("PushString    \"ho\"\n"), "{}", listing);
+
+        let jump = listing.lines().find(|l| l.contains("JumpIfFalse")).unwrap();
+        let label = jump.split_whitespace().last().unwrap();
+        assert!(listing.contains(&format!("\n{}:\n", label)), "{}", listing);
+    }
+
+    #[test]
+    fn json_has_one_entry_per_instruction() {
+        let program = compile("oi mug rang = \"a\\b\"\n");
+        let json = to_json(&program);
+        let instructions = json["instructions"].as_array().unwrap();
+        assert_eq!(instructions.len(), program.instructions.len());
+        assert_eq!(json["variables"], json!(["rang"]));
+        assert_eq!(json["strings"], json!(["a\\b"]));
+        let store = instructions.iter().find(|i| i["opcode"] == "StoreVariable").unwrap();
+        assert_eq!((&store["operands"], &store["detail"], &store["line"]), (&json!([0]), &json!("rang"), &json!(1)));
+        assert_eq!(store["label"], Json::Null);
+
+        let json = to_json(&compile(SOURCE));
+        let jump = json["instructions"].as_array().unwrap().iter().find(|i| i["opcode"] == "JumpIfFalse").unwrap();
+        assert!(json["instructions"].as_array().unwrap().iter().any(|i| i["label"] == jump["detail"]));
+        assert_eq!(to_json(&BytecodeProgram::new())["instructions"], json!([]));
+    }
+}
@@ -0,0 +1,170 @@
+//! High-level API for running LyangLang from other Rust programs.
+
+use crate::capability::Capabilities;
+use crate::compiler::Compiler;
+use crate::console::Console;
+use crate::error::NepalError;
+use crate::lexer::Lexer;
+use crate::native::{Natives, Type};
+use crate::parser::Parser;
+use crate::value::Value;
+use crate::vm::VM;
+use std::collections::HashMap;
+
+/// Runs scripts on the VM, keeping their variables from one script to the next.
+pub struct Engine {
+    globals: HashMap<String, Value>,
+    /// Taken by the VM while a script runs
+    console: Option<Console>,
+    natives: Natives,
+    capabilities: Capabilities,
+}
+
+impl Engine {
+    /// An engine with no variables that uses standard input and output.
+    pub fn new() -> Self {
+        Self::with_console(Console::stdio())
+    }
+
+    pub fn with_console(console: Console) -> Self {
+        Self {
+            globals: HashMap::new(),
+            console: Some(console),
+            natives: Natives::stdlib(),
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    /// Lets scripts do only what `capabilities` grants. By default they can
+    /// do nothing outside themselves, not even print.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Compiles and runs `source`. Variables it assigns stay visible to later
+    /// scripts and through [`Engine::get_global`], even if it fails part way.
+    pub fn eval(&mut self, source: &str) -> Result<(), NepalError> {
+        let tokens = Lexer::new(source).tokenize()?;
+        let statements = Parser::new(tokens).parse()?;
+        let program = Compiler::new()
+            .with_natives(self.natives.clone())
+            .compile(statements)?;
+
+        let console = self.console.take().unwrap_or_else(Console::stdio);
+        let mut vm = VM::new(program)
+            .with_console(console)
+            .with_natives(self.natives.clone())
+            .with_capabilities(self.capabilities.clone());
+        for (name, value) in &self.globals {
+            vm.set_global(name, value.clone());
+        }
+
+        let result = vm.run();
+        for (name, value) in vm.globals() {
+            self.globals.insert(name.to_string(), value.clone());
+        }
+        self.console = Some(vm.into_console());
+        result
+    }
+
+    /// Makes `function` callable from scripts as `mug <name> args lai target`.
+    /// Scripts are checked to pass one argument per entry in `params`, and
+    /// each argument must have that entry's type.
+    pub fn register_fn(
+        &mut self,
+        name: impl Into<String>,
+        params: &[Type],
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        self.natives.register(name, params, function);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// Assigns a variable that later scripts can read.
+    pub fn set_global(&mut self, name: impl Into<String>, value: impl Into<Value>) {
+        self.globals.insert(name.into(), value.into());
+    }
+
+    /// Every variable with its value, in no particular order.
+    pub fn globals(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.globals.iter().map(|(name, value)| (name.as_str(), value))
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::ErrorCode;
+
+    fn engine() -> (Engine, crate::console::OutputBuffer) {
+        let (console, output) = Console::memory("");
+        (Engine::with_console(console).with_capabilities(Capabilities::console()), output)
+    }
+
+    #[test]
+    fn variables_persist_between_scripts() {
+        let (mut engine, output) = engine();
+        engine.eval("oi mug a = 2").unwrap();
+        engine.eval("oi mug b = 3\nmug jod a, b lai c\nbol mug c").unwrap();
+        assert_eq!(engine.get_global("c"), Some(&Value::Number(5)));
+        assert_eq!(output.contents(), "5\n");
+    }
+
+    #[test]
+    fn host_values_are_visible_to_scripts() {
+        let (mut engine, output) = engine();
+        engine.set_global("naam", "Sita");
+        engine.eval("bol mug \"Namaste \" + naam").unwrap();
+        assert_eq!(output.contents(), "Namaste Sita\n");
+    }
+
+    #[test]
+    fn scripts_call_registered_functions() {
+        let (mut engine, output) = engine();
+        engine.register_fn("dollar", &[Type::Number], |args| match args[0] {
+            Value::Number(rupees) => Ok(Value::Number(rupees / 133)),
+            _ => unreachable!(),
+        });
+        engine.eval("oi mug rakam = 1330\nmug dollar rakam lai usd\nbol mug \"$\" + usd").unwrap();
+        assert_eq!(output.contents(), "$10\n");
+
+        let err = engine.eval("mug dollar \"saya\" lai usd").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::ArgumentType);
+        let err = engine.eval("mug dollar lai usd").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::WrongArgumentCount);
+    }
+
+    #[test]
+    fn failed_scripts_keep_earlier_assignments() {
+        let (mut engine, _) = engine();
+        let err = engine.eval("oi mug x = 1\nbol mug y").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::UndefinedVariable);
+        assert_eq!(engine.get_global("x"), Some(&Value::Number(1)));
+    }
+
+    #[test]
+    fn scripts_only_get_granted_capabilities() {
+        let (mut engine, _) = engine();
+        let err = engine.eval("mug sanyog 6 lai pasa").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::PermissionDenied);
+        let err = Engine::with_console(Console::memory("").0).eval("bol mug \"x\"").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::PermissionDenied);
+
+        let (console, output) = Console::memory("");
+        let mut engine = Engine::with_console(console).with_capabilities(Capabilities::none().with_random());
+        engine.eval("mug sanyog 1 lai pasa").unwrap();
+        assert_eq!(engine.get_global("pasa"), Some(&Value::Number(0)));
+        assert!(engine.eval("bol mug pasa").is_err());
+        assert_eq!(output.contents(), "");
+    }
+}
@@ -1,75 +1,101 @@
+use crate::diagnostic::{Category, Diagnostic, ErrorCode};
+use crate::messages::Lang;
 use std::io;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum NepalError {
-    #[error("Lexical error: {0}")]
-    LexError(&'static str),
+    #[error("{0}")]
+    LexError(Box<Diagnostic>),
 
-    #[error("Parse error: {0}")]
-    ParseError(&'static str),
+    #[error("{0}")]
+    ParseError(Box<Diagnostic>),
 
-    #[error("Runtime error: {0}")]
-    RuntimeError(&'static str),
+    #[error("{0}")]
+    CompileError(Box<Diagnostic>),
 
-    #[error("Type error: {0}")]
-    #[allow(dead_code)]
-    TypeError(&'static str),
+    #[error("{0}")]
+    RuntimeError(Box<Diagnostic>),
+
+    #[error("{0}")]
+    TypeError(Box<Diagnostic>),
+
+    #[error("{0}")]
+    NameError(Box<Diagnostic>),
+
+    /// The program went over one of its [`Limits`](crate::limits::Limits).
+    #[error("{0}")]
+    ResourceLimit(Box<Diagnostic>),
 
-    #[error("Name error: {0}")]
-    #[allow(dead_code)]
-    NameError(&'static str),
-    
     #[error("IO error: {0}")]
     IoError(#[from] io::Error),
-    
-    #[error("Error at line {line}: {error_type}\nDetails: {message}\nCode: {code}\n{pointer}")]
-    #[allow(dead_code)]
-    FormattedError {
-        line: usize,
-        error_type: String,
-        message: String,
-        code: String,
-        pointer: String,
-    },
+
+    /// Several independent errors, e.g. every syntax error in a file.
+    #[error("{}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n"))]
+    Multiple(Vec<NepalError>),
+}
+
+impl From<Diagnostic> for NepalError {
+    /// Wraps a diagnostic in the error variant matching its code.
+    fn from(diagnostic: Diagnostic) -> Self {
+        let code = diagnostic.code;
+        let diagnostic = Box::new(diagnostic);
+        match code {
+            ErrorCode::InvalidOperands
+            | ErrorCode::InvalidComparison
+            | ErrorCode::ConditionNotBoolean
+            | ErrorCode::ArgumentType => NepalError::TypeError(diagnostic),
+            ErrorCode::UndefinedVariable => NepalError::NameError(diagnostic),
+            ErrorCode::ResourceLimit => NepalError::ResourceLimit(diagnostic),
+            _ => match code.category() {
+                Category::Lexer => NepalError::LexError(diagnostic),
+                Category::Parser => NepalError::ParseError(diagnostic),
+                Category::Compiler => NepalError::CompileError(diagnostic),
+                Category::Runtime | Category::Bytecode | Category::Lint | Category::CommandLine => {
+                    NepalError::RuntimeError(diagnostic)
+                }
+            },
+        }
+    }
 }
 
 impl NepalError {
-    /// Create a formatted error with line number and code snippet
-    #[allow(dead_code)]
-    pub fn with_location(
-        error: NepalError, 
-        line: usize, 
-        code: &str, 
-        column: usize
-    ) -> Self {
-        let error_message = error.to_string();
-        let error_parts: Vec<&str> = error_message.split(':').collect();
-        let error_type = error_parts.get(0).unwrap_or(&"Error").to_string();
-        let message = error_parts.get(1).unwrap_or(&"Unknown error").trim().to_string();
-        
-        let code_line = code.lines().nth(line - 1).unwrap_or("").to_string();
-        let pointer = format!("{}^---- Error location", " ".repeat(column));
-        
-        Self::FormattedError {
-            line,
-            error_type,
-            message,
-            code: code_line,
-            pointer,
+    /// The structured diagnostic behind this error, if it has one.
+    pub fn diagnostic(&self) -> Option<&Diagnostic> {
+        match self {
+            NepalError::LexError(d)
+            | NepalError::ParseError(d)
+            | NepalError::CompileError(d)
+            | NepalError::RuntimeError(d)
+            | NepalError::TypeError(d)
+            | NepalError::NameError(d)
+            | NepalError::ResourceLimit(d) => Some(d),
+            NepalError::IoError(_) => None,
+            NepalError::Multiple(errors) => errors.first().and_then(|e| e.diagnostic()),
         }
     }
-    
-    /// Convert standard Nepali error messages
-    #[allow(dead_code)]
-    pub fn to_nepali_message(&self) -> String {
+
+    /// The individual errors, flattening `Multiple`.
+    pub fn errors(&self) -> Vec<&NepalError> {
+        match self {
+            NepalError::Multiple(errors) => errors.iter().flat_map(|e| e.errors()).collect(),
+            other => vec![other],
+        }
+    }
+
+    /// Renders the error in `lang` with excerpts from `source`, which `file_name` names.
+    pub fn render(&self, source: &str, file_name: &str, lang: Lang) -> String {
         match self {
-            NepalError::NameError(_) => "अपरिभाषित चर".to_string(),
-            NepalError::TypeError(_) => "प्रकार बेमेल".to_string(),
-            NepalError::RuntimeError(_) if self.to_string().contains("Invalid operation") => 
-                "अमान्य संचालन".to_string(),
-            NepalError::ParseError(_) => "वाक्य रचना त्रुटि".to_string(),
-            _ => self.to_string()
+            NepalError::Multiple(errors) => errors
+                .iter()
+                .map(|e| e.render(source, file_name, lang))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => match self.diagnostic() {
+                Some(diagnostic) => diagnostic.render(source, file_name, lang),
+                None => format!("error: {}\n", self),
+            },
         }
     }
 }
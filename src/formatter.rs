@@ -0,0 +1,182 @@
+//! The canonical layout of LyangLang source, behind `lyangpiler fmt`.
+//!
+//! - one statement per line, with `yedi` bodies indented four spaces
+//! - a single space between tokens, and none before a `,` or `:`
+//! - keywords spelled the standard way, so `yadi` becomes `yedi`
+//! - at most one blank line in a row, and none at the start or end
+//! - comments kept where they are written
+//!
+//! Only whitespace and keyword spelling change: the tokens and their order
+//! stay the same, so a formatted program does exactly what it did before.
+//! Programs with syntax errors are not formatted.
+
+use crate::ast::Statement;
+use crate::error::NepalError;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::span::Spanned;
+use crate::token::{Lexeme, Token};
+use std::collections::HashSet;
+
+const INDENT: &str = "    ";
+
+/// Returns `source` in the canonical layout.
+pub fn format(source: &str) -> Result<String, NepalError> {
+    let lexemes = Lexer::new(source).tokenize_with_trivia()?;
+    let tokens: Vec<Spanned<Token>> = lexemes
+        .iter()
+        .filter_map(|lexeme| match &lexeme.node {
+            Lexeme::Token(token) => Some(Spanned::new(token.clone(), lexeme.span)),
+            _ => None,
+        })
+        .collect();
+    let statements = Parser::new(tokens).parse()?;
+    let mut starts = HashSet::new();
+    statement_starts(&statements, &mut starts);
+
+    let mut layout = Layout::default();
+    let mut after_bhane = false;
+    for lexeme in &lexemes {
+        match &lexeme.node {
+            Lexeme::Newline => layout.newline(),
+            Lexeme::Comment(text) => layout.push(text, false),
+            Lexeme::Token(token) => {
+                let starts_line = starts.contains(&lexeme.span.start) || *token == Token::Sakiyo || after_bhane;
+                if starts_line && layout.has_token {
+                    layout.break_line();
+                }
+                // `aile` closes the innermost open block, just as the parser reads it
+                if matches!(token, Token::Sakiyo | Token::Aile) && !layout.has_token {
+                    layout.depth = layout.depth.saturating_sub(1);
+                }
+                let text = match token {
+                    Token::Identifier(_) | Token::Number(_) | Token::String(_) => {
+                        &source[lexeme.span.start..lexeme.span.end]
+                    }
+                    other => other.keyword(),
+                };
+                layout.push(text, matches!(token, Token::Comma | Token::Colon));
+                layout.has_token = true;
+                after_bhane = *token == Token::Bhane;
+                if after_bhane {
+                    layout.depth += 1;
+                }
+            }
+        }
+    }
+    Ok(layout.finish())
+}
+
+/// Where each statement begins, including those inside `yedi` bodies.
+fn statement_starts(statements: &[Spanned<Statement>], starts: &mut HashSet<usize>) {
+    for statement in statements {
+        starts.insert(statement.span.start);
+        if let Statement::If(_, body, else_branch) = &statement.node {
+            statement_starts(body, starts);
+            if let Some(other) = else_branch {
+                statement_starts(std::slice::from_ref(other), starts);
+            }
+        }
+    }
+}
+
+/// The formatted lines, built up one piece of text at a time.
+#[derive(Default)]
+struct Layout {
+    lines: Vec<String>,
+    line: String,
+    /// Whether the current line holds a token rather than only comments
+    has_token: bool,
+    depth: usize,
+    /// Whether a blank line goes before the next text
+    blank: bool,
+}
+
+impl Layout {
+    fn push(&mut self, text: &str, attach: bool) {
+        if self.line.is_empty() {
+            if self.blank && !self.lines.is_empty() {
+                self.lines.push(String::new());
+            }
+            self.blank = false;
+            self.line = INDENT.repeat(self.depth);
+        } else if !attach {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+    }
+
+    /// Ends the current line, if it has anything on it.
+    fn break_line(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+            self.has_token = false;
+        }
+    }
+
+    fn newline(&mut self) {
+        // The break that ends a line with text on it is not a blank line
+        if self.line.is_empty() {
+            self.blank = true;
+        } else {
+            self.break_line();
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.break_line();
+        self.lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::SAMPLES;
+
+    fn tokens(source: &str) -> Vec<Token> {
+        Lexer::new(source).tokenize().unwrap().into_iter().map(|token| token.node).collect()
+    }
+
+    #[test]
+    fn lays_out_statements_blocks_and_comments() {
+        let source = "\n\n# naam sodhne\noi mug bhan naam   // yaha\n\n\n\noi mug a=2 bol mug a\nmug jod a ,a lai b\nyadi naam babaal \"Sita\" bhane bol mug \"Namaste \"+naam\n\n  yedi b laamo \"4\" bhane\n/* bhitra */ bol mug b sakiyo\naile feri naam laamo \"Sita\" bhane\nbol mug \"ko?\" sakiyo\n\n";
+        let expected = "\
+# naam sodhne
+oi mug bhan naam // yaha
+
+oi mug a = 2
+bol mug a
+mug jod a, a lai b
+yedi naam babaal \"Sita\" bhane
+    bol mug \"Namaste \" + naam
+
+    yedi b laamo \"4\" bhane
+        /* bhitra */ bol mug b
+    sakiyo
+aile feri naam laamo \"Sita\" bhane
+    bol mug \"ko?\"
+sakiyo
+";
+        assert_eq!(format(source).unwrap(), expected);
+        assert_eq!(format(expected).unwrap(), expected);
+        assert_eq!(format("oi mug umer :sankhya=18").unwrap(), "oi mug umer: sankhya = 18\n");
+    }
+
+    #[test]
+    fn samples_are_formatted_and_stay_the_same() {
+        for sample in SAMPLES {
+            let formatted = format(sample).unwrap();
+            assert_eq!(format(&formatted).unwrap(), formatted);
+            assert_eq!(tokens(&formatted), tokens(sample));
+            assert_eq!(formatted, sample);
+        }
+        assert_eq!(format("").unwrap(), "");
+    }
+
+    #[test]
+    fn refuses_programs_with_syntax_errors() {
+        assert!(format("yedi a babaal 1 bhane\nbol mug a\n").is_err());
+        assert!(format("bol mug \"adhuro\n").is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! What each variable may hold wherever it appears, found by following the
+//! program once. The linter, `check --types` and the editor's symbols all
+//! read it, so they agree on every type.
+//!
+//! There are no loops, so the program is followed in order. A variable's
+//! type comes from what it is given: a number or string literal, arithmetic
+//! (`jod` with a string joins text), `oi mug bhan` (a string) or a native
+//! call (any type). After a `yedi` body a variable keeps its type if the
+//! body gives it the same one and may be any type otherwise; one given a
+//! value only in the body may or may not have one. A variable
+//! [annotated](Annotation) `sankhya` or `shabda` has that type everywhere.
+//!
+//! A statement that reads a variable with no value on any path, or a `yedi`
+//! comparing a number to a string, always stops the program, so what follows
+//! it in its block never runs. That code is still followed, but a `yedi` body
+//! that always stops changes nothing after it.
+
+use crate::ast::{Annotation, Argument, Condition, Ident, Statement, StrSegment, Value};
+use crate::native::Type;
+use crate::span::{Span, Spanned};
+use std::collections::HashMap;
+
+/// What a name does where it appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// A variable is given a value
+    Write,
+    /// A variable's value is used
+    Read,
+    /// A native function is called
+    Call,
+}
+
+/// What a variable may hold at some point in the program.
+#[derive(Debug, Clone, Copy)]
+pub struct Known {
+    pub kind: Type,
+    /// Where it got that type: where it was given a value, or its annotation
+    pub origin: Span,
+    /// Whether it has a value on every path here, not just some
+    pub always: bool,
+}
+
+/// One appearance of a variable or function name.
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub name: String,
+    pub span: Span,
+    pub access: Access,
+    /// The statement the name appears in
+    pub statement: Span,
+    /// What the variable may hold just before it is read or just after it is
+    /// given a value; `None` if it has no value on any path
+    pub known: Option<Known>,
+    /// For a write, the type of the value given, which an annotation may contradict
+    pub given: Option<Type>,
+    /// The `yedi` bodies it is in, outermost first
+    pub blocks: Vec<usize>,
+    /// Whether the program can get here
+    pub reachable: bool,
+    /// For a write in a `yedi` body, whether the variable had a value before the body
+    pub outside: bool,
+}
+
+/// A statement that always stops the program, with the rest of its block.
+#[derive(Debug, Clone, Copy)]
+pub struct Stop {
+    pub statement: Span,
+    pub unreachable: Span,
+}
+
+/// What a program's variables hold at every name in it.
+#[derive(Debug, Clone, Default)]
+pub struct Inference {
+    /// In the order the program uses them
+    occurrences: Vec<Occurrence>,
+    /// Occurrences by the start of their name
+    index: HashMap<usize, usize>,
+    annotations: HashMap<String, Annotation>,
+    stops: Vec<Stop>,
+}
+
+impl Inference {
+    pub fn new(statements: &[Spanned<Statement>]) -> Self {
+        let mut annotations = HashMap::new();
+        annotate(statements, &mut annotations);
+        let mut follower = Follower {
+            inference: Inference { annotations, ..Self::default() },
+            known: HashMap::new(),
+            blocks: Vec::new(),
+            next_block: 0,
+            reachable: true,
+        };
+        follower.block(statements);
+        follower.inference
+    }
+
+    /// Every name in the program, in the order the program uses them.
+    pub fn occurrences(&self) -> &[Occurrence] {
+        &self.occurrences
+    }
+
+    /// Where `name` appears in the program that was followed.
+    pub fn of(&self, name: &Ident) -> Option<&Occurrence> {
+        self.index.get(&name.span.start).map(|&i| &self.occurrences[i])
+    }
+
+    /// The first annotation of variable `name`, which holds for the whole program.
+    pub fn annotation(&self, name: &str) -> Option<&Annotation> {
+        self.annotations.get(name)
+    }
+
+    /// The statements the program can reach that always stop it, where more
+    /// of their block follows.
+    pub fn stops(&self) -> &[Stop] {
+        &self.stops
+    }
+}
+
+/// Collects the first annotation of each variable, including in `yedi` bodies.
+fn annotate(statements: &[Spanned<Statement>], annotations: &mut HashMap<String, Annotation>) {
+    for statement in statements {
+        match &statement.node {
+            Statement::Annotated(annotation, _) => {
+                annotations.entry(annotation.name.node.clone()).or_insert_with(|| annotation.clone());
+            }
+            Statement::If(_, body, else_branch) => {
+                annotate(body, annotations);
+                if let Some(other) = else_branch {
+                    annotate(std::slice::from_ref(other), annotations);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A `yedi` body being followed.
+struct Branch {
+    id: usize,
+    /// What each variable given a value in the body held before it
+    saved: HashMap<String, Option<Known>>,
+}
+
+struct Follower {
+    inference: Inference,
+    known: HashMap<String, Known>,
+    /// The `yedi` bodies around the current statement, outermost first
+    blocks: Vec<Branch>,
+    next_block: usize,
+    reachable: bool,
+}
+
+impl Follower {
+    fn block(&mut self, statements: &[Spanned<Statement>]) {
+        for (i, statement) in statements.iter().enumerate() {
+            if !self.statement(statement) || !self.reachable {
+                continue;
+            }
+            self.reachable = false;
+            if let (Some(first), Some(last)) = (statements.get(i + 1), statements.last()) {
+                let unreachable = first.span.to(last.span);
+                self.inference.stops.push(Stop { statement: statement.span, unreachable });
+            }
+        }
+    }
+
+    /// Follows one statement, returning whether it always stops the program.
+    fn statement(&mut self, statement: &Spanned<Statement>) -> bool {
+        let span = statement.span;
+        match &statement.node {
+            Statement::Declaration(name, value) => {
+                let kind = match value {
+                    Value::Number(_) => Type::Number,
+                    Value::String(_) => Type::String,
+                };
+                self.write(name, kind, span);
+                false
+            }
+            Statement::Input(name) => {
+                self.write(name, Type::String, span);
+                false
+            }
+            Statement::Addition(target, sources) => {
+                let kinds = self.reads(sources.iter(), span);
+                let kind = match &kinds {
+                    Some(kinds) if kinds.contains(&Type::String) => Type::String,
+                    Some(kinds) if kinds.iter().all(|&kind| kind == Type::Number) => Type::Number,
+                    _ => Type::Any,
+                };
+                self.write(target, kind, span);
+                kinds.is_none()
+            }
+            Statement::Subtraction(target, sources)
+            | Statement::Multiplication(target, sources)
+            | Statement::Division(target, sources) => {
+                let undefined = self.reads(sources.iter(), span).is_none();
+                self.write(target, Type::Number, span);
+                undefined
+            }
+            Statement::StringConcat(target, parts) => {
+                let undefined = self.reads(identifiers(parts), span).is_none();
+                self.write(target, Type::String, span);
+                undefined
+            }
+            Statement::Print(name) => self.read(name, span).is_none(),
+            Statement::PrintString(parts) => self.reads(identifiers(parts), span).is_none(),
+            Statement::Call(function, args, target) => {
+                self.occur(function, Access::Call, span, None);
+                let names = args.iter().filter_map(|arg| match arg {
+                    Argument::Variable(name) => Some(name),
+                    Argument::Literal(_) => None,
+                });
+                let undefined = self.reads(names, span).is_none();
+                if let Some(target) = target {
+                    self.write(target, Type::Any, span);
+                }
+                undefined
+            }
+            Statement::If(condition, body, else_branch) => {
+                let (Condition::Equals(name, _) | Condition::NotEquals(name, _)) = condition;
+                let fails = self.read(name, span).is_none_or(|known| known.kind == Type::Number);
+                let reachable = self.reachable;
+                self.reachable = reachable && !fails;
+                self.branch(body);
+                if let Some(other) = else_branch {
+                    self.branch(std::slice::from_ref(other));
+                }
+                self.reachable = reachable;
+                fails
+            }
+            Statement::Annotated(_, statement) => self.statement(statement),
+        }
+    }
+
+    /// Follows a `yedi` body, which may or may not run.
+    fn branch(&mut self, statements: &[Spanned<Statement>]) {
+        let reachable = self.reachable;
+        self.blocks.push(Branch { id: self.next_block, saved: HashMap::new() });
+        self.next_block += 1;
+        self.block(statements);
+        let branch = self.blocks.pop().expect("pushed above");
+        // A body that always stops only leaves the path where it did not run
+        let stopped = reachable && !self.reachable;
+        self.reachable = reachable;
+
+        if let Some(parent) = self.blocks.last_mut() {
+            for (name, before) in &branch.saved {
+                parent.saved.entry(name.clone()).or_insert(*before);
+            }
+        }
+        // Only what the body changed needs merging with what it was before
+        for (name, before) in branch.saved {
+            let after = self.known.get(&name).copied();
+            let merged = match (before, after) {
+                _ if stopped => before,
+                (Some(old), Some(new)) if old.kind != new.kind => Some(Known { kind: Type::Any, ..old }),
+                (Some(old), _) => Some(old),
+                (None, Some(new)) => Some(Known { always: false, ..new }),
+                (None, None) => None,
+            };
+            match merged {
+                Some(known) => self.known.insert(name, known),
+                None => self.known.remove(&name),
+            };
+        }
+    }
+
+    /// Records a read of `name`, returning what it may hold.
+    fn read(&mut self, name: &Ident, statement: Span) -> Option<Known> {
+        let known = self.known.get(&name.node).copied();
+        self.occur(name, Access::Read, statement, known);
+        known
+    }
+
+    /// Reads each of `names`, returning their types, or `None` if any of
+    /// them has no value.
+    fn reads<'s>(&mut self, names: impl Iterator<Item = &'s Ident>, statement: Span) -> Option<Vec<Type>> {
+        // Every name is read, even after one without a value
+        let known: Vec<Option<Known>> = names.map(|name| self.read(name, statement)).collect();
+        known.into_iter().map(|known| known.map(|known| known.kind)).collect()
+    }
+
+    fn write(&mut self, name: &Ident, given: Type, statement: Span) {
+        let outside = self.blocks.last().is_some_and(|branch| match branch.saved.get(&name.node) {
+            Some(before) => before.is_some(),
+            None => self.known.contains_key(&name.node),
+        });
+        let known = match self.inference.annotations.get(&name.node) {
+            Some(annotation) => Known { kind: annotation.kind.node, origin: annotation.kind.span, always: true },
+            None => Known { kind: given, origin: name.span, always: true },
+        };
+        if let Some(branch) = self.blocks.last_mut() {
+            let before = self.known.get(&name.node).copied();
+            branch.saved.entry(name.node.clone()).or_insert(before);
+        }
+        self.known.insert(name.node.clone(), known);
+
+        self.occur(name, Access::Write, statement, Some(known));
+        let occurrence = self.inference.occurrences.last_mut().expect("pushed above");
+        occurrence.given = Some(given);
+        occurrence.outside = outside;
+    }
+
+    fn occur(&mut self, name: &Ident, access: Access, statement: Span, known: Option<Known>) {
+        let inference = &mut self.inference;
+        inference.index.insert(name.span.start, inference.occurrences.len());
+        inference.occurrences.push(Occurrence {
+            name: name.node.clone(),
+            span: name.span,
+            access,
+            statement,
+            known,
+            given: None,
+            blocks: self.blocks.iter().map(|branch| branch.id).collect(),
+            reachable: self.reachable,
+            outside: false,
+        });
+    }
+}
+
+pub(crate) fn identifiers(parts: &[StrSegment]) -> impl Iterator<Item = &Ident> {
+    parts.iter().filter_map(|part| match part {
+        StrSegment::Identifier(name) => Some(name),
+        StrSegment::Literal(_) => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// What the read of `name` on 1-based line `line` may hold.
+    fn read_on(source: &str, line: usize, name: &str) -> Option<Known> {
+        let statements = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        let inference = Inference::new(&statements);
+        let occurrence = inference
+            .occurrences()
+            .iter()
+            .find(|o| o.name == name && o.access == Access::Read && o.span.line == line)
+            .unwrap();
+        occurrence.known
+    }
+
+    #[test]
+    fn merges_what_yedi_bodies_may_give() {
+        let source = "\
+oi mug bhan a
+oi mug n = 1
+yedi a babaal \"x\" bhane
+    yedi a babaal \"y\" bhane
+        oi mug b = \"1\"
+        oi mug n = \"ek\"
+    sakiyo
+    bol mug b
+sakiyo
+bol mug b
+bol mug n
+";
+        // Given a value two bodies in, so only on some paths
+        assert!(!read_on(source, 8, "b").unwrap().always);
+        assert!(!read_on(source, 10, "b").unwrap().always);
+        let n = read_on(source, 11, "n").unwrap();
+        assert_eq!((n.kind, n.always), (Type::Any, true));
+    }
+
+    #[test]
+    fn bodies_that_always_stop_change_nothing_after_them() {
+        let source = "\
+oi mug bhan a
+yedi a babaal \"x\" bhane
+    oi mug b = 1
+    bol mug kohi
+    bol mug \"pachhi\"
+sakiyo
+bol mug b
+";
+        assert!(read_on(source, 7, "b").is_none());
+        let statements = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        let inference = Inference::new(&statements);
+        let stop = inference.stops()[0];
+        assert_eq!(&source[stop.unreachable.start..stop.unreachable.end], "bol mug \"pachhi\"");
+        // `bol mug b` stops the program too, but nothing follows it
+        assert_eq!(inference.stops().len(), 1);
+    }
+}
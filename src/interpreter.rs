@@ -1,161 +1,290 @@
 use std::collections::HashMap;
-use crate::ast::{Statement, Condition, Value, StrSegment};
+use crate::ast::{Argument, Ident, Statement, Condition, StrSegment};
+use crate::capability::{Capabilities, Capability};
+use crate::console::Console;
+use crate::diagnostic::{Diagnostic, ErrorCode};
+use crate::error::NepalError;
+use crate::limits::{Limits, Meter};
+use crate::native::Natives;
+use crate::span::{Span, Spanned};
+use crate::value::{BinaryOp, Value};
 
+/// Tree-walking interpreter. It gives the same output and errors as
+/// compiling the program and running it on the VM.
 pub struct Interpreter {
     variables: HashMap<String, Value>,
+    console: Console,
+    natives: Natives,
+    capabilities: Capabilities,
+    meter: Meter,
+    /// `yedi` blocks entered and not yet left
+    depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Interpreter {
             variables: HashMap::new(),
+            console: Console::stdio(),
+            natives: Natives::stdlib(),
+            capabilities: Capabilities::default(),
+            meter: Meter::new(Limits::default()),
+            depth: 0,
         }
     }
 
-    pub fn execute(&mut self, statement: Statement) {
-        match statement {
+    /// Stops the program with a `ResourceLimit` error if it goes over `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.meter = Meter::new(limits);
+        self
+    }
+
+    /// Lets programs do only what `capabilities` grants.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Runs programs against `console` instead of standard input and output.
+    pub fn with_console(mut self, console: Console) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Calls native functions from `natives` instead of the standard library.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    /// Runs a whole program. Problems the compiler would reject are reported
+    /// before anything runs, as on the VM.
+    pub fn run(&mut self, statements: Vec<Spanned<Statement>>) -> Result<(), NepalError> {
+        for statement in &statements {
+            check(statement, &self.natives)?;
+        }
+        self.meter.restart();
+        for statement in statements {
+            self.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    pub fn execute(&mut self, statement: Spanned<Statement>) -> Result<(), NepalError> {
+        if let Statement::Annotated(_, statement) = statement.node {
+            return self.execute(*statement);
+        }
+        let span = statement.span;
+        self.meter.step().map_err(|d| d.with_span(span))?;
+        match statement.node {
             Statement::Declaration(name, value) => {
-                self.variables.insert(name, value);
+                self.assign(name.node, value.into(), span)?;
             }
             Statement::Addition(target, sources) => {
-                let sum: i32 = sources
-                    .iter()
-                    .filter_map(|name| {
-                        if let Some(Value::Number(n)) = self.variables.get(name) {
-                            Some(*n)
-                        } else {
-                            None
-                        }
-                    })
-                    .sum();
-                self.variables.insert(target, Value::Number(sum));
+                self.fold(BinaryOp::Add, target, &sources, span)?;
             }
             Statement::Subtraction(target, sources) => {
-                if let Some(&Value::Number(first)) = sources.first().and_then(|name| self.variables.get(name)) {
-                    let diff = sources.iter().skip(1).fold(first, |acc, name| {
-                        if let Some(Value::Number(n)) = self.variables.get(name) {
-                            acc - n
-                        } else {
-                            acc
-                        }
-                    });
-                    self.variables.insert(target, Value::Number(diff));
-                }
+                self.fold(BinaryOp::Subtract, target, &sources, span)?;
             }
             Statement::Multiplication(target, sources) => {
-                if sources.is_empty() {
-                    return;
-                }
-                let prod: i32 = sources
-                    .iter()
-                    .filter_map(|name| {
-                        if let Some(Value::Number(n)) = self.variables.get(name) {
-                            Some(*n)
-                        } else {
-                            None
-                        }
-                    })
-                    .product();
-                self.variables.insert(target, Value::Number(prod));
+                self.fold(BinaryOp::Multiply, target, &sources, span)?;
             }
             Statement::Division(target, sources) => {
-                if let Some(&Value::Number(first)) = sources.first().and_then(|name| self.variables.get(name)) {
-                    let mut acc = first;
-                    let mut ok = true;
-                    for name in sources.iter().skip(1) {
-                        if let Some(Value::Number(n)) = self.variables.get(name) {
-                            if *n == 0 {
-                                ok = false;
-                                break;
-                            }
-                            acc /= n;
-                        }
-                    }
-                    if ok {
-                        self.variables.insert(target, Value::Number(acc));
-                    }
-                }
+                self.fold(BinaryOp::Divide, target, &sources, span)?;
             }
             Statement::Print(name) => {
-                if let Some(value) = self.variables.get(&name) {
-                    match value {
-                        Value::Number(n) => println!("{}", n),
-                        Value::String(s) => println!("{}", s),
-                    }
-                }
+                let value = self.get(&name, span)?;
+                self.capabilities.check(&Capability::Stdout).map_err(|d| d.with_span(span))?;
+                self.console.print_line(value).map_err(|d| d.with_span(span))?;
             }
             Statement::PrintString(parts) => {
-                let mut output = String::new();
-                for part in parts {
-                    if part.starts_with('{') && part.ends_with('}') {
-                        let var_name = &part[1..part.len()-1];
-                        if let Some(value) = self.variables.get(var_name) {
-                            match value {
-                                Value::Number(n) => output.push_str(&n.to_string()),
-                                Value::String(s) => output.push_str(s),
-                            }
-                        }
-                    } else {
-                        output.push_str(&part);
-                    }
+                if let Some(value) = self.concat(&parts, span)? {
+                    self.capabilities.check(&Capability::Stdout).map_err(|d| d.with_span(span))?;
+                    self.console.print_line(value).map_err(|d| d.with_span(span))?;
                 }
-                println!("{}", output);
             }
             Statement::Input(name) => {
-                use std::io::{self, Write};
-                print!("> ");
-                io::stdout().flush().unwrap();
-                let mut input = String::new();
-                io::stdin().read_line(&mut input).unwrap();
-                self.variables.insert(name, Value::String(input.trim().to_string()));
+                self.capabilities.check(&Capability::Stdin).map_err(|d| d.with_span(span))?;
+                let input = self.console.read_line().map_err(|d| d.with_span(span))?;
+                self.assign(name.node, Value::from(input), span)?;
             }
             Statement::If(condition, statements, else_branch) => {
-                let execute = match condition {
-                    Condition::Equals(var1, string_literal) => {
-                        if let Some(Value::String(input)) = self.variables.get(&var1) {
-                            // Case-insensitive so "Rato" matches yedi ... "rato" (common chat typing).
-                            input.to_lowercase() == string_literal.to_lowercase()
-                        } else {
-                            false
-                        }
-                    }
-                    Condition::NotEquals(var1, string_literal) => {
-                        if let Some(Value::String(input)) = self.variables.get(&var1) {
-                            input.to_lowercase() != string_literal.to_lowercase()
-                        } else {
-                            false
-                        }
-                    }
+                let (name, literal, expected) = match condition {
+                    Condition::Equals(name, literal) => (name, literal, true),
+                    Condition::NotEquals(name, literal) => (name, literal, false),
                 };
+                let value = self.get(&name, span)?;
+                // Case-insensitive so "Rato" matches yedi ... "rato" (common chat typing).
+                let equal = value
+                    .equals(&Value::from(literal))
+                    .map_err(|d| d.with_span(span))?;
 
-                if execute {
-                    for stmt in statements {
-                        self.execute(stmt);
-                    }
+                if equal == expected {
+                    self.depth += 1;
+                    let result = self.meter.nesting(self.depth).map_err(|d| d.with_span(span).into());
+                    let result = result.and_then(|()| statements.into_iter().try_for_each(|stmt| self.execute(stmt)));
+                    self.depth -= 1;
+                    result?;
                 } else if let Some(else_stmt) = else_branch {
-                    self.execute(*else_stmt);
+                    self.execute(*else_stmt)?;
                 }
             }
             Statement::StringConcat(target, parts) => {
-                let result = parts
-                    .iter()
-                    .map(|seg| match seg {
-                        StrSegment::Literal(s) => s.clone(),
-                        StrSegment::Identifier(name) => {
-                            if let Some(value) = self.variables.get(name) {
-                                match value {
-                                    Value::String(s) => s.clone(),
-                                    Value::Number(n) => n.to_string(),
-                                }
-                            } else {
-                                name.clone()
-                            }
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-                self.variables.insert(target, Value::String(result));
+                if let Some(value) = self.concat(&parts, span)? {
+                    self.assign(target.node, value, span)?;
+                }
+            }
+            Statement::Call(function, args, target) => {
+                let mut values = Vec::with_capacity(args.len());
+                for arg in args {
+                    values.push(match arg {
+                        Argument::Variable(name) => self.get(&name, span)?,
+                        Argument::Literal(value) => value.into(),
+                    });
+                }
+                let result = self
+                    .natives
+                    .call(&function.node, values, &self.capabilities)
+                    .map_err(|d| d.with_span(span))?;
+                if let Some(target) = target {
+                    self.assign(target.node, result, span)?;
+                }
+            }
+            Statement::Annotated(..) => unreachable!("unwrapped above"),
+        }
+        Ok(())
+    }
+
+    /// The value of `name`, which must have been assigned.
+    fn get(&self, name: &Ident, span: Span) -> Result<Value, NepalError> {
+        self.variables.get(&name.node).cloned().ok_or_else(|| {
+            Diagnostic::new(ErrorCode::UndefinedVariable)
+                .with_arg(name)
+                .with_span(span)
+                .into()
+        })
+    }
+
+    /// Combines `sources` left to right with `op` and stores the result in `target`.
+    fn fold(&mut self, op: BinaryOp, target: Ident, sources: &[Ident], span: Span) -> Result<(), NepalError> {
+        let Some((first, rest)) = sources.split_first() else {
+            return Err(missing_operands(op, span));
+        };
+        let mut acc = self.get(first, span)?;
+        for source in rest {
+            let value = self.get(source, span)?;
+            acc = op.apply_owned(acc, &value).map_err(|d| d.with_span(span))?;
+        }
+        self.assign(target.node, acc, span)
+    }
+
+    fn assign(&mut self, name: String, value: Value, span: Span) -> Result<(), NepalError> {
+        self.meter
+            .assign(self.variables.get(&name), &value)
+            .map_err(|d| d.with_span(span))?;
+        self.variables.insert(name, value);
+        Ok(())
+    }
+
+    /// Joins `parts` with `+`, or `None` if there are none.
+    fn concat(&self, parts: &[StrSegment], span: Span) -> Result<Option<Value>, NepalError> {
+        let mut result: Option<Value> = None;
+        for part in parts {
+            let value = match part {
+                StrSegment::Literal(s) => Value::from(s.as_str()),
+                StrSegment::Identifier(name) => self.get(name, span)?,
+            };
+            result = Some(match result {
+                Some(acc) => BinaryOp::Concat.apply_owned(acc, &value).map_err(|d| d.with_span(span))?,
+                None => value,
+            });
+        }
+        Ok(result)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rejects what the compiler rejects, so both engines fail before any output.
+pub(crate) fn check(statement: &Spanned<Statement>, natives: &Natives) -> Result<(), NepalError> {
+    let op = match &statement.node {
+        Statement::Addition(_, sources) if sources.is_empty() => BinaryOp::Add,
+        Statement::Subtraction(_, sources) if sources.is_empty() => BinaryOp::Subtract,
+        Statement::Multiplication(_, sources) if sources.is_empty() => BinaryOp::Multiply,
+        Statement::Division(_, sources) if sources.is_empty() => BinaryOp::Divide,
+        Statement::StringConcat(_, parts) if parts.is_empty() => {
+            return Err(Diagnostic::new(ErrorCode::EmptyConcatenation)
+                .with_span(statement.span)
+                .into());
+        }
+        Statement::If(_, statements, else_branch) => {
+            for stmt in statements.iter().chain(else_branch.as_deref()) {
+                check(stmt, natives)?;
             }
+            return Ok(());
+        }
+        Statement::Call(function, args, _) => {
+            return natives
+                .check_call(&function.node, args.len())
+                .map_err(|d| d.with_span(statement.span).into());
         }
+        Statement::Annotated(_, statement) => return check(statement, natives),
+        _ => return Ok(()),
+    };
+    Err(missing_operands(op, statement.span))
+}
+
+fn missing_operands(op: BinaryOp, span: Span) -> NepalError {
+    Diagnostic::new(ErrorCode::MissingOperands)
+        .with_arg(op.keyword())
+        .with_span(span)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str, input: &str) -> (Result<(), NepalError>, String) {
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+        let (console, output) = Console::memory(input);
+        let result = Interpreter::new().with_console(console).with_capabilities(Capabilities::console()).run(stmts);
+        (result, output.contents())
+    }
+
+    #[test]
+    fn reads_scripted_input() {
+        let (result, output) = run("oi mug bhan naam\nbol mug \"Namaste \" + naam\n", "Sita\n");
+        result.unwrap();
+        assert_eq!(output, "> Namaste Sita\n");
+    }
+
+    #[test]
+    fn compile_errors_are_reported_before_any_output() {
+        let (result, output) = run("bol mug \"first\"\noi mug x = jod\n", "");
+        assert_eq!(result.unwrap_err().diagnostic().unwrap().code, ErrorCode::MissingOperands);
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn stops_at_resource_limits() {
+        let source = "bol mug \"a\"\nbol mug \"b\"\nbol mug \"c\"\n";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let (console, output) = Console::memory("");
+        let limits = Limits::new().with_max_steps(2);
+        let err = Interpreter::new()
+            .with_console(console)
+            .with_capabilities(Capabilities::console())
+            .with_limits(limits)
+            .run(crate::parser::Parser::new(tokens).parse().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, NepalError::ResourceLimit(_)));
+        assert_eq!(err.diagnostic().unwrap().span.unwrap().line, 3);
+        assert_eq!(output.contents(), "a\nb\n");
     }
 }
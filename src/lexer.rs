@@ -1,97 +1,166 @@
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
 use crate::error::NepalError;
-use crate::token::Token;
+use crate::span::{Span, Spanned};
+use crate::token::{Lexeme, Token};
 
 pub struct Lexer {
     input: Vec<char>,
+    /// Byte offset, line and column of every character, plus one entry for the end of input.
+    locations: Vec<(usize, usize, usize)>,
     position: usize,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        let mut locations = Vec::with_capacity(input.len() + 1);
+        let (mut line, mut column) = (1, 0);
+        for (offset, c) in input.char_indices() {
+            locations.push((offset, line, column));
+            if c == '\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += 1;
+            }
+        }
+        locations.push((input.len(), line, column));
         Lexer {
             input: input.chars().collect(),
+            locations,
             position: 0,
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, NepalError> {
+    pub fn tokenize(&mut self) -> Result<Vec<Spanned<Token>>, NepalError> {
         let mut tokens = Vec::new();
         while self.position < self.input.len() {
             self.skip_whitespace();
             if self.position >= self.input.len() {
                 break;
             }
-
-            // Skip comments
-            if self.position + 1 < self.input.len() 
-                && self.input[self.position] == '/' 
-                && self.input[self.position + 1] == '/' {
-                self.skip_until_newline();
-                continue;
+            let start = self.position;
+            if let Some(token) = self.next_token()? {
+                tokens.push(Spanned::new(token, self.span_from(start)));
             }
+        }
+        Ok(tokens)
+    }
 
-            // Try to match keywords from longest to shortest to avoid partial matches
-            match self.peek_char() {
-                'b' if self.match_keyword("bhane") => tokens.push(Token::Bhane),
-                'b' if self.match_keyword("bhag") => tokens.push(Token::Bhag),
-                'b' if self.match_keyword("babaal") => tokens.push(Token::Babaal),
-                'b' if self.match_keyword("bol mug") => tokens.push(Token::BolMug),
-                'b' if self.match_keyword("bhan") => tokens.push(Token::Bhan),
-                'o' if self.match_keyword("oi mug") => tokens.push(Token::OiMug),
-                'm' if self.match_keyword("mug") => tokens.push(Token::Mug),
-                'j' if self.match_keyword("jod") => tokens.push(Token::Jod),
-                'g' if self.match_keyword("ghata") => tokens.push(Token::Ghata),
-                'g' if self.match_keyword("guna") => tokens.push(Token::Guna),
-                'l' if self.match_keyword("lai") => tokens.push(Token::Lai),
-                'l' if self.match_keyword("laamo") => tokens.push(Token::Laamo),
-                'y' if self.match_keyword("yadi") => tokens.push(Token::Yedi),
-                'y' if self.match_keyword("yedi") => tokens.push(Token::Yedi),
-                's' if self.match_keyword("sakiyo") => tokens.push(Token::Sakiyo),
-                'a' if self.match_keyword("aile") => tokens.push(Token::Aile),
-                'f' if self.match_keyword("feri") => tokens.push(Token::Feri),
-                '=' => {
+    /// Like [`Lexer::tokenize`], but keeps comments and line breaks too.
+    pub fn tokenize_with_trivia(&mut self) -> Result<Vec<Spanned<Lexeme>>, NepalError> {
+        let mut lexemes = Vec::new();
+        while self.position < self.input.len() {
+            let start = self.position;
+            let lexeme = match self.peek_char() {
+                '\n' => {
                     self.position += 1;
-                    if self.position < self.input.len() && self.input[self.position] == '=' {
-                        self.position += 1;
-                        tokens.push(Token::IsEquals);
-                    } else {
-                        tokens.push(Token::Equals);
-                    }
+                    Some(Lexeme::Newline)
                 }
-                '!' => {
+                c if c.is_whitespace() => {
                     self.position += 1;
-                    if self.position < self.input.len() && self.input[self.position] == '=' {
-                        self.position += 1;
-                        tokens.push(Token::NotEquals);
-                    } else {
-                        return Err(NepalError::LexError("Expected '=' after '!'"));
-                    }
+                    None
                 }
-                '+' => {
-                    self.position += 1;
-                    tokens.push(Token::Plus);
+                _ if self.starts_with("//") || self.starts_with("/*") || self.starts_with("#") => {
+                    self.next_token()?;
+                    let comment: String = self.input[start..self.position].iter().collect();
+                    Some(Lexeme::Comment(comment.trim_end().to_string()))
                 }
-                ',' => {
+                _ => self.next_token()?.map(Lexeme::Token),
+            };
+            if let Some(lexeme) = lexeme {
+                lexemes.push(Spanned::new(lexeme, self.span_from(start)));
+            }
+        }
+        Ok(lexemes)
+    }
+
+    /// Reads one token, or `None` if only a comment or line break was consumed.
+    fn next_token(&mut self) -> Result<Option<Token>, NepalError> {
+        // Skip comments
+        if self.starts_with("//") || self.peek_char() == '#' {
+            self.skip_until_newline();
+            return Ok(None);
+        }
+        if self.starts_with("/*") {
+            self.skip_block_comment()?;
+            return Ok(None);
+        }
+
+        // Try to match keywords from longest to shortest to avoid partial matches
+        let token = match self.peek_char() {
+            'b' if self.match_keyword("bhane") => Token::Bhane,
+            'b' if self.match_keyword("bhag") => Token::Bhag,
+            'b' if self.match_keyword("babaal") => Token::Babaal,
+            'b' if self.match_keyword("bol mug") => Token::BolMug,
+            'b' if self.match_keyword("bhan") => Token::Bhan,
+            'o' if self.match_keyword("oi mug") => Token::OiMug,
+            'm' if self.match_keyword("mug") => Token::Mug,
+            'j' if self.match_keyword("jod") => Token::Jod,
+            'g' if self.match_keyword("ghata") => Token::Ghata,
+            'g' if self.match_keyword("guna") => Token::Guna,
+            'l' if self.match_keyword("lai") => Token::Lai,
+            'l' if self.match_keyword("laamo") => Token::Laamo,
+            'y' if self.match_keyword("yadi") => Token::Yedi,
+            'y' if self.match_keyword("yedi") => Token::Yedi,
+            's' if self.match_keyword("sakiyo") => Token::Sakiyo,
+            'a' if self.match_keyword("aile") => Token::Aile,
+            'f' if self.match_keyword("feri") => Token::Feri,
+            '=' => {
+                self.position += 1;
+                if self.position < self.input.len() && self.input[self.position] == '=' {
                     self.position += 1;
-                    tokens.push(Token::Comma);
-                }
-                '"' => {
-                    tokens.push(Token::String(self.read_string()?));
-                }
-                c if c.is_alphabetic() || c == '_' => {
-                    tokens.push(Token::Identifier(self.read_identifier()));
-                }
-                c if c.is_numeric() => {
-                    tokens.push(Token::Number(self.read_number()?));
+                    Token::IsEquals
+                } else {
+                    Token::Equals
                 }
-                '\n' | '\r' => {
+            }
+            '!' => {
+                let start = self.position;
+                self.position += 1;
+                if self.position < self.input.len() && self.input[self.position] == '=' {
                     self.position += 1;
-                    continue;
+                    Token::NotEquals
+                } else {
+                    return Err(Diagnostic::new(ErrorCode::ExpectedEqualsAfterBang)
+                        .with_span(self.span_from(start))
+                        .into());
                 }
-                _ => return Err(NepalError::LexError("Invalid character found")),
             }
-        }
-        Ok(tokens)
+            '+' => {
+                self.position += 1;
+                Token::Plus
+            }
+            ',' => {
+                self.position += 1;
+                Token::Comma
+            }
+            ':' => {
+                self.position += 1;
+                Token::Colon
+            }
+            '"' => Token::String(self.read_string()?),
+            c if c.is_alphabetic() || c == '_' => Token::Identifier(self.read_identifier()),
+            c if c.is_numeric() => Token::Number(self.read_number()?),
+            '\n' | '\r' => {
+                self.position += 1;
+                return Ok(None);
+            }
+            c => {
+                let start = self.position;
+                self.position += 1;
+                return Err(Diagnostic::new(ErrorCode::UnexpectedCharacter)
+                    .with_arg(c)
+                    .with_span(self.span_from(start))
+                    .into());
+            }
+        };
+        Ok(Some(token))
+    }
+
+    /// Span from character position `start` up to the current position.
+    fn span_from(&self, start: usize) -> Span {
+        let (offset, line, column) = self.locations[start];
+        Span::new(offset, self.locations[self.position].0, line, column)
     }
 
     fn peek_char(&self) -> char {
@@ -133,23 +202,66 @@ impl Lexer {
             self.position += 1;
         }
         let num_str: String = self.input[start..self.position].iter().collect();
-        num_str.parse().map_err(|_| NepalError::LexError("Invalid number"))
+        num_str.parse().map_err(|_| {
+            Diagnostic::new(ErrorCode::NumberTooLarge)
+                .with_arg(&num_str)
+                .with_span(self.span_from(start))
+                .into()
+        })
     }
 
     fn read_string(&mut self) -> Result<String, NepalError> {
+        let opened_at = self.position;
         self.position += 1; // Skip opening quote
         let start = self.position;
         while self.position < self.input.len() && self.input[self.position] != '"' {
             self.position += 1;
         }
         if self.position >= self.input.len() {
-            return Err(NepalError::LexError("Unterminated string"));
+            self.position = opened_at + 1;
+            return Err(Diagnostic::new(ErrorCode::UnterminatedString)
+                .with_span(self.span_from(opened_at))
+                .into());
         }
         let result = self.input[start..self.position].iter().collect();
         self.position += 1; // Skip closing quote
         Ok(result)
     }
 
+    fn starts_with(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        self.input[self.position..].starts_with(&chars)
+    }
+
+    /// Skips a `/* ... */` comment, which may contain nested block comments.
+    fn skip_block_comment(&mut self) -> Result<(), NepalError> {
+        let mut open = Vec::new();
+        while self.position < self.input.len() {
+            if self.starts_with("/*") {
+                open.push(self.position);
+                self.position += 2;
+            } else if self.starts_with("*/") {
+                open.pop();
+                self.position += 2;
+                if open.is_empty() {
+                    return Ok(());
+                }
+            } else {
+                self.position += 1;
+            }
+        }
+
+        let opening_span = |position: usize| {
+            let (offset, line, column) = self.locations[position];
+            Span::new(offset, self.locations[position + 2].0, line, column)
+        };
+        let mut diagnostic = Diagnostic::new(ErrorCode::UnterminatedBlockComment).with_span(opening_span(open[0]));
+        for &nested in &open[1..] {
+            diagnostic = diagnostic.with_label(opening_span(nested), Note::NestedCommentOpened);
+        }
+        Err(diagnostic.into())
+    }
+
     fn skip_until_newline(&mut self) {
         while self.position < self.input.len() && !matches!(self.input[self.position], '\n' | '\r') {
             self.position += 1;
@@ -161,19 +273,32 @@ impl Lexer {
 mod tests {
     use super::*;
 
+    fn tokens(source: &str) -> Vec<Token> {
+        Lexer::new(source)
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.node)
+            .collect()
+    }
+
+    fn error_code(source: &str) -> ErrorCode {
+        match Lexer::new(source).tokenize() {
+            Err(e) => e.diagnostic().unwrap().code,
+            Ok(t) => panic!("expected an error, got {:?}", t),
+        }
+    }
+
     #[test]
     fn yadi_maps_to_same_token_as_yedi() {
-        let mut a = Lexer::new("yadi");
-        let mut b = Lexer::new("yedi");
-        assert_eq!(a.tokenize().unwrap(), vec![Token::Yedi]);
-        assert_eq!(b.tokenize().unwrap(), vec![Token::Yedi]);
+        assert_eq!(tokens("yadi"), vec![Token::Yedi]);
+        assert_eq!(tokens("yedi"), vec![Token::Yedi]);
     }
 
     #[test]
     fn identifier_allows_digits_and_underscore() {
-        let mut l = Lexer::new("num_1 num2");
         assert_eq!(
-            l.tokenize().unwrap(),
+            tokens("num_1 num2"),
             vec![
                 Token::Identifier("num_1".into()),
                 Token::Identifier("num2".into()),
@@ -181,9 +306,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hash_and_block_comments_are_skipped() {
+        assert_eq!(
+            tokens("# heading\nbol mug /* inline */ x // trailing\n"),
+            vec![Token::BolMug, Token::Identifier("x".into())]
+        );
+    }
+
+    #[test]
+    fn trivia_keeps_comments_and_line_breaks() {
+        let lexemes: Vec<Lexeme> = Lexer::new("# heading\r\nbol mug /* inline */ x // trailing \n")
+            .tokenize_with_trivia()
+            .unwrap()
+            .into_iter()
+            .map(|l| l.node)
+            .collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                Lexeme::Comment("# heading".into()),
+                Lexeme::Newline,
+                Lexeme::Token(Token::BolMug),
+                Lexeme::Comment("/* inline */".into()),
+                Lexeme::Token(Token::Identifier("x".into())),
+                Lexeme::Comment("// trailing".into()),
+                Lexeme::Newline,
+            ]
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(tokens("/* outer /* inner */ still comment */ mug"), vec![Token::Mug]);
+    }
+
+    #[test]
+    fn unterminated_block_comment_reports_where_it_opened() {
+        let err = Lexer::new("bol mug x\n  /* never /* closed */\n").tokenize().unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, ErrorCode::UnterminatedBlockComment);
+        let span = diagnostic.span.unwrap();
+        assert_eq!((span.line, span.column), (2, 2));
+    }
+
+    #[test]
+    fn tokens_carry_line_and_column() {
+        let spanned = Lexer::new("bol mug\n  naam").tokenize().unwrap();
+        assert_eq!((spanned[1].span.line, spanned[1].span.column), (2, 2));
+        assert_eq!((spanned[1].span.start, spanned[1].span.end), (10, 14));
+    }
+
+    #[test]
+    fn lexical_errors_have_codes() {
+        assert_eq!(error_code("oi mug x = @"), ErrorCode::UnexpectedCharacter);
+        assert_eq!(error_code("a ! b"), ErrorCode::ExpectedEqualsAfterBang);
+        assert_eq!(error_code("99999999999"), ErrorCode::NumberTooLarge);
+        assert_eq!(error_code("bol mug \"open"), ErrorCode::UnterminatedString);
+    }
+
     #[test]
     fn guna_bhag_keywords() {
-        let mut l = Lexer::new("guna bhag");
-        assert_eq!(l.tokenize().unwrap(), vec![Token::Guna, Token::Bhag]);
+        assert_eq!(tokens("guna bhag"), vec![Token::Guna, Token::Bhag]);
     }
 }
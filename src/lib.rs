@@ -0,0 +1,69 @@
+//! LyangLang, a programming language with Nepali keywords, as a library.
+//!
+//! The pipeline is [`Lexer`] → [`Parser`] → [`Compiler`] → [`VM`]. Most
+//! applications only need [`Engine`], which runs that pipeline and keeps
+//! variables between scripts:
+//!
+//! ```
+//! use lyangpiler::{Engine, Value};
+//!
+//! let mut engine = Engine::new();
+//! engine.set_global("mulya", 45);
+//! engine.eval("oi mug sankhya = 3\nmug guna mulya, sankhya lai jamma").unwrap();
+//! assert_eq!(engine.get_global("jamma"), Some(&Value::Number(135)));
+//! ```
+
+pub mod token;
+pub mod span;
+pub mod diagnostic;
+mod messages;
+mod error;
+pub mod ast;
+mod lexer;
+mod parser;
+mod formatter;
+mod infer;
+mod lint;
+mod symbols;
+mod interpreter;
+mod typecheck;
+pub mod value;
+pub mod console;
+pub mod limits;
+pub mod capability;
+pub mod native;
+pub mod bytecode;
+mod nbc;
+mod verifier;
+mod optimizer;
+mod disasm;
+pub mod vm;
+pub mod register;
+mod debugger;
+mod protocol;
+mod dap;
+mod lsp;
+mod compiler;
+mod engine;
+mod repl;
+#[doc(hidden)]
+pub mod cli;
+
+#[cfg(test)]
+#[path = "../tests/common/mod.rs"]
+mod samples;
+
+pub use bytecode::BytecodeProgram;
+pub use capability::Capabilities;
+pub use compiler::Compiler;
+pub use console::Console;
+pub use engine::Engine;
+pub use error::NepalError;
+pub use interpreter::Interpreter;
+pub use lexer::Lexer;
+pub use messages::Lang;
+pub use native::Natives;
+pub use parser::Parser;
+pub use register::RegisterVM;
+pub use value::Value;
+pub use vm::VM;
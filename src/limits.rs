@@ -0,0 +1,198 @@
+//! Caps on the resources a program may use while it runs.
+//!
+//! Meant for running untrusted programs, e.g. grading submissions: a program
+//! that goes over a cap stops with a `ResourceLimit` error instead of running
+//! forever or exhausting memory. Every cap is off unless set.
+//!
+//! - **Steps**: instructions executed on the VMs, statements executed on the
+//!   interpreter.
+//! - **Time**: wall-clock time from the start of `run`. A program waiting for
+//!   input is stopped when the input arrives, not while it waits.
+//! - **Stack**: values on the stack VM's operand stack, temporaries on the
+//!   register VM, and `yedi` blocks nested inside each other on the
+//!   interpreter.
+//! - **Heap**: bytes of text held in variables, counting each variable
+//!   separately even when two share the same text.
+
+use crate::diagnostic::{Diagnostic, ErrorCode, Word};
+use crate::value::Value;
+use std::time::{Duration, Instant};
+
+/// Which caps apply. The default has none.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub max_stack: Option<usize>,
+    pub max_heap: Option<usize>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_max_stack(mut self, depth: usize) -> Self {
+        self.max_stack = Some(depth);
+        self
+    }
+
+    pub fn with_max_heap(mut self, bytes: usize) -> Self {
+        self.max_heap = Some(bytes);
+        self
+    }
+}
+
+/// Reading the clock on every step would be slow; this many steps run
+/// between checks.
+const STEPS_PER_CLOCK_CHECK: u64 = 256;
+
+/// What a running program has used so far, checked against its [`Limits`].
+/// Errors have no span; the engine adds the current location.
+#[derive(Debug, Clone)]
+pub(crate) struct Meter {
+    limits: Limits,
+    // The caps with "no cap" as the largest value, so that the checks on
+    // every step are a single comparison
+    max_steps: u64,
+    max_stack: usize,
+    steps: u64,
+    started: Instant,
+    heap: usize,
+}
+
+impl Meter {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            max_steps: limits.max_steps.unwrap_or(u64::MAX),
+            max_stack: limits.max_stack.unwrap_or(usize::MAX),
+            steps: 0,
+            started: Instant::now(),
+            heap: 0,
+        }
+    }
+
+    /// Starts timing and counting steps again, keeping the heap in use.
+    pub(crate) fn restart(&mut self) {
+        self.steps = 0;
+        self.started = Instant::now();
+    }
+
+    /// Counts one step.
+    #[inline]
+    pub(crate) fn step(&mut self) -> Result<(), Diagnostic> {
+        self.steps += 1;
+        if self.steps > self.max_steps || self.steps.is_multiple_of(STEPS_PER_CLOCK_CHECK) {
+            return self.check_steps();
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn check_steps(&self) -> Result<(), Diagnostic> {
+        if self.steps > self.max_steps {
+            return Err(exceeded(self.max_steps, Word::Steps));
+        }
+        match self.limits.timeout {
+            Some(timeout) if self.started.elapsed() > timeout => Err(exceeded(timeout.as_secs_f64(), Word::Seconds)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a stack that is `depth` deep.
+    #[inline]
+    pub(crate) fn stack(&self, depth: usize) -> Result<(), Diagnostic> {
+        if depth > self.max_stack {
+            return Err(exceeded(self.max_stack, Word::StackSlots));
+        }
+        Ok(())
+    }
+
+    /// Checks blocks nested `depth` deep, which is the interpreter's stack.
+    #[inline]
+    pub(crate) fn nesting(&self, depth: usize) -> Result<(), Diagnostic> {
+        if depth > self.max_stack {
+            return Err(exceeded(self.max_stack, Word::NestedBlocks));
+        }
+        Ok(())
+    }
+
+    /// Accounts for a variable changing from `old` to `new`.
+    #[inline]
+    pub(crate) fn assign(&mut self, old: Option<&Value>, new: &Value) -> Result<(), Diagnostic> {
+        let Some(max) = self.limits.max_heap else {
+            return Ok(());
+        };
+        // Variables set before the program started were never counted
+        let heap = self.heap.saturating_sub(old.map_or(0, size)) + size(new);
+        if heap > max {
+            return Err(exceeded(max, Word::Bytes));
+        }
+        self.heap = heap;
+        Ok(())
+    }
+
+    /// Accounts for a variable giving up `value` without a new one yet.
+    #[inline]
+    pub(crate) fn release(&mut self, value: &Value) {
+        self.heap = self.heap.saturating_sub(size(value));
+    }
+
+    /// Forgets every variable.
+    pub(crate) fn clear_heap(&mut self) {
+        self.heap = 0;
+    }
+}
+
+/// Bytes of text in `value`.
+fn size(value: &Value) -> usize {
+    match value {
+        Value::String(text) => text.len(),
+        _ => 0,
+    }
+}
+
+#[cold]
+fn exceeded(limit: impl ToString, unit: Word) -> Diagnostic {
+    Diagnostic::new(ErrorCode::ResourceLimit).with_arg(limit.to_string()).with_word(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_steps_and_heap_bytes() {
+        let mut meter = Meter::new(Limits::new().with_max_steps(2).with_max_heap(5));
+        meter.step().unwrap();
+        meter.step().unwrap();
+        assert_eq!(meter.step().unwrap_err().message(), "the program went over its limit of 2 steps");
+
+        meter.assign(None, &Value::from("abc")).unwrap();
+        meter.assign(None, &Value::Number(1_000_000)).unwrap();
+        let err = meter.assign(None, &Value::from("abc")).unwrap_err();
+        assert_eq!(err.message(), "the program went over its limit of 5 bytes of text");
+        // Replacing a value frees its bytes
+        meter.assign(Some(&Value::from("abc")), &Value::from("abcde")).unwrap();
+    }
+
+    #[test]
+    fn stops_after_the_timeout() {
+        let mut meter = Meter::new(Limits::new().with_timeout(Duration::from_millis(1)));
+        std::thread::sleep(Duration::from_millis(5));
+        let err = (0..STEPS_PER_CLOCK_CHECK).try_for_each(|_| meter.step()).unwrap_err();
+        assert_eq!(err.message(), "the program went over its limit of 0.001 seconds");
+        assert!(Meter::new(Limits::new()).stack(1_000).is_ok());
+    }
+}
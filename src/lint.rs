@@ -0,0 +1,504 @@
+//! Likely mistakes in programs that run but do not do what was meant,
+//! behind `lyangpiler lint`.
+//!
+//! Each [`Rule`] reports at a [`Severity`] that a project can change in its
+//! [`Config::FILE_NAME`]. Where removing a statement is a safe fix, the
+//! finding carries it so `lyangpiler lint --fix` can apply it.
+//!
+//! What each variable may hold comes from the same [inference](crate::infer)
+//! as `check --types`: a variable given a value only inside a `yedi` body
+//! may or may not have one afterwards, and an annotated one is taken to hold
+//! its declared type.
+
+use crate::ast::{Condition, Statement};
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
+use crate::error::NepalError;
+use crate::infer::{identifiers, Access, Inference, Known, Occurrence};
+use crate::lexer::Lexer;
+use crate::messages::{Heading, Lang};
+use crate::native::Type;
+use crate::parser::Parser;
+use crate::span::{Span, Spanned};
+use serde_json::{json, Map, Value as Json};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Something the linter looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// A variable is given a value that is never read anywhere
+    UnusedVariable,
+    /// A value is replaced, or the program ends, before it is read
+    UnreadAssignment,
+    /// `oi mug` inside a `yedi` body for a variable from outside it
+    ShadowedDeclaration,
+    /// `yedi` compares a number variable to a string, which always fails
+    NumberComparedToString,
+    /// Statements after one that always stops the program
+    UnreachableCode,
+    /// A `yedi` with nothing in its body
+    EmptyYedi,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::UnusedVariable,
+        Rule::UnreadAssignment,
+        Rule::ShadowedDeclaration,
+        Rule::NumberComparedToString,
+        Rule::UnreachableCode,
+        Rule::EmptyYedi,
+    ];
+
+    /// The name used in project files, e.g. `unused-variable`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::UnreadAssignment => "unread-assignment",
+            Rule::ShadowedDeclaration => "shadowed-declaration",
+            Rule::NumberComparedToString => "number-compared-to-string",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::EmptyYedi => "empty-yedi",
+        }
+    }
+
+    /// How the rule reports unless a project file says otherwise.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            // The program is certain to stop with an error when it gets there
+            Rule::NumberComparedToString => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| format!("unknown rule `{}`", s))
+    }
+}
+
+/// How a rule's findings are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Not reported
+    Allow,
+    /// Reported without failing `lyangpiler lint`
+    Warning,
+    /// Reported and makes `lyangpiler lint` fail
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Severity::Allow),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            other => Err(format!("unknown severity `{}` (expected allow, warning or error)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Allow => "allow",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The severity of each rule, from the `"lint"` object of a project file:
+///
+/// ```json
+/// { "lint": { "unused-variable": "error", "empty-yedi": "allow" } }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    severities: HashMap<Rule, Severity>,
+}
+
+impl Config {
+    /// The project file, looked for next to a program and in the directories above it.
+    pub const FILE_NAME: &'static str = "lyangpiler.json";
+
+    /// Reads the `"lint"` settings of a project file; other keys are left for other tools.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let project: Json = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut config = Config::default();
+        let Some(lint) = project.get("lint") else {
+            return Ok(config);
+        };
+        let lint = lint.as_object().ok_or("`lint` must be an object")?;
+        for (name, severity) in lint {
+            let severity = severity.as_str().ok_or_else(|| format!("the severity of `{}` must be a string", name))?;
+            config = config.with_severity(name.parse()?, severity.parse()?);
+        }
+        Ok(config)
+    }
+
+    /// A project file with every rule at its current severity, for new projects.
+    pub fn to_json(&self) -> Json {
+        let lint: Map<String, Json> = Rule::ALL
+            .into_iter()
+            .map(|rule| (rule.name().to_string(), json!(self.severity(rule).to_string())))
+            .collect();
+        json!({ "lint": lint })
+    }
+
+    pub fn with_severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.severities.get(&rule).copied().unwrap_or(rule.default_severity())
+    }
+}
+
+/// One problem the linter found.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub diagnostic: Diagnostic,
+    /// Byte ranges of the source to delete to fix it; empty if there is no safe fix
+    pub fix: Vec<Range<usize>>,
+}
+
+impl Finding {
+    /// Renders the finding like an error, headed by its severity.
+    pub fn render(&self, source: &str, file_name: &str, lang: Lang) -> String {
+        let heading = match self.severity {
+            Severity::Error => Heading::Error,
+            Severity::Warning | Severity::Allow => Heading::Warning,
+        };
+        self.diagnostic.render_as(heading, source, file_name, lang)
+    }
+}
+
+/// Lints `source`, whose syntax must be valid. Findings are in source order.
+pub fn lint(source: &str, config: &Config) -> Result<Vec<Finding>, NepalError> {
+    let statements = Parser::new(Lexer::new(source).tokenize()?).parse()?;
+    let mut linter = Linter::new(source, &statements);
+    linter.block(&statements);
+    linter.unreachable();
+    linter.usage();
+
+    let mut findings: Vec<Finding> = linter
+        .findings
+        .into_iter()
+        .filter_map(|(rule, diagnostic, fix)| {
+            let severity = config.severity(rule);
+            (severity != Severity::Allow).then_some(Finding { severity, diagnostic, fix })
+        })
+        .collect();
+    findings.sort_by_key(|finding| finding.diagnostic.span.map(|span| span.start));
+    Ok(findings)
+}
+
+/// Applies the fixes of `findings` to `source`, skipping any that overlap.
+pub fn fix(source: &str, findings: &[Finding]) -> String {
+    let mut ranges: Vec<&Range<usize>> = findings.iter().flat_map(|finding| &finding.fix).collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut fixed = String::new();
+    let mut end = 0;
+    for range in ranges {
+        if range.start >= end {
+            fixed.push_str(&source[end..range.start]);
+            end = range.end;
+        }
+    }
+    fixed.push_str(&source[end..]);
+    fixed
+}
+
+type Report = (Rule, Diagnostic, Vec<Range<usize>>);
+
+struct Linter<'a> {
+    source: &'a str,
+    inference: Inference,
+    findings: Vec<Report>,
+    /// Writes whose statement can be removed without changing anything
+    /// else, by where their name starts
+    removable: HashSet<usize>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(source: &'a str, statements: &[Spanned<Statement>]) -> Self {
+        Self {
+            source,
+            inference: Inference::new(statements),
+            findings: Vec::new(),
+            removable: HashSet::new(),
+        }
+    }
+
+    fn block(&mut self, statements: &[Spanned<Statement>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Spanned<Statement>) {
+        let span = statement.span;
+        match &statement.node {
+            Statement::Declaration(name, _) => {
+                self.redeclared(name);
+                self.removable.insert(name.span.start);
+            }
+            Statement::Input(name) => self.redeclared(name),
+            // Joining text that is sure to be there cannot fail
+            Statement::StringConcat(target, parts)
+                if identifiers(parts).all(|name| self.known(name).is_some_and(|known| known.always)) =>
+            {
+                self.removable.insert(target.span.start);
+            }
+            Statement::If(condition, body, else_branch) => {
+                let (Condition::Equals(name, literal) | Condition::NotEquals(name, literal)) = condition;
+                if self.inference.of(name).is_some_and(|occurrence| occurrence.reachable) {
+                    let known = self.known(name);
+                    if let Some(known) = known.filter(|known| known.kind == Type::Number) {
+                        let diagnostic = Diagnostic::new(ErrorCode::NumberComparedToString)
+                            .with_arg(&name.node)
+                            .with_arg(format!("\"{}\"", literal))
+                            .with_span(name.span)
+                            .with_label(known.origin, Note::TypeGivenHere);
+                        self.report(Rule::NumberComparedToString, diagnostic, Vec::new());
+                    }
+                    if body.is_empty() && else_branch.is_none() {
+                        // Removing it is safe only if the comparison cannot fail
+                        let safe = known.is_some_and(|known| known.always && known.kind != Type::Number);
+                        let fix = if safe { vec![self.lines(span)] } else { Vec::new() };
+                        let diagnostic = Diagnostic::new(ErrorCode::EmptyYedi).with_span(span);
+                        self.report(Rule::EmptyYedi, diagnostic, fix);
+                    }
+                }
+                self.block(body);
+                if let Some(other) = else_branch {
+                    self.statement(other);
+                }
+            }
+            Statement::Annotated(_, statement) => self.statement(statement),
+            _ => {}
+        }
+    }
+
+    /// Reports `oi mug` in a `yedi` body for a variable from outside it.
+    fn redeclared(&mut self, name: &Spanned<String>) {
+        let outside = self
+            .inference
+            .of(name)
+            .is_some_and(|occurrence| occurrence.outside && occurrence.reachable);
+        if outside {
+            let diagnostic = Diagnostic::new(ErrorCode::ShadowedDeclaration)
+                .with_arg(&name.node)
+                .with_span(name.span);
+            self.report(Rule::ShadowedDeclaration, diagnostic, Vec::new());
+        }
+    }
+
+    /// Reports code after statements that always stop the program.
+    fn unreachable(&mut self) {
+        let stops = self.inference.stops().to_vec();
+        for stop in stops {
+            let diagnostic = Diagnostic::new(ErrorCode::UnreachableCode)
+                .with_span(stop.unreachable)
+                .with_label(stop.statement, Note::ProgramStopsHere);
+            self.report(Rule::UnreachableCode, diagnostic, Vec::new());
+        }
+    }
+
+    /// Reports values that are never read, once the whole program is followed.
+    fn usage(&mut self) {
+        // Names read only where the program cannot get still count as used,
+        // but values given there are never there to be read
+        let events = self.inference.occurrences().iter().filter(|occurrence| match occurrence.access {
+            Access::Read => true,
+            Access::Write => occurrence.reachable,
+            Access::Call => false,
+        });
+        let mut events_of: HashMap<&str, Vec<&Occurrence>> = HashMap::new();
+        let mut written = HashSet::new();
+        let mut names: Vec<&str> = Vec::new();
+        for event in events {
+            events_of.entry(&event.name).or_default().push(event);
+            if event.access == Access::Write && written.insert(event.name.as_str()) {
+                names.push(&event.name);
+            }
+        }
+
+        let mut findings = Vec::new();
+        for name in names {
+            let events = &events_of[name];
+            if events.iter().all(|event| event.access == Access::Write) {
+                let first = events[0];
+                let fix = if events.iter().all(|write| self.removable.contains(&write.span.start)) {
+                    events.iter().map(|write| self.lines(write.statement)).collect()
+                } else {
+                    Vec::new()
+                };
+                let diagnostic = Diagnostic::new(ErrorCode::UnusedVariable).with_arg(name).with_span(first.span);
+                findings.push((Rule::UnusedVariable, diagnostic, fix));
+                continue;
+            }
+            for (i, write) in events.iter().enumerate().filter(|(_, event)| event.access == Access::Write) {
+                // Replaced before any read, on every path the first value took
+                let unread = match events.get(i + 1) {
+                    None => true,
+                    Some(next) => next.access == Access::Write && write.blocks.starts_with(&next.blocks),
+                };
+                if unread {
+                    let fix = if self.removable.contains(&write.span.start) { vec![self.lines(write.statement)] } else { Vec::new() };
+                    let diagnostic = Diagnostic::new(ErrorCode::UnreadAssignment).with_arg(name).with_span(write.span);
+                    findings.push((Rule::UnreadAssignment, diagnostic, fix));
+                }
+            }
+        }
+        self.findings.extend(findings);
+    }
+
+    /// What the variable read at `name` may hold there.
+    fn known(&self, name: &Spanned<String>) -> Option<Known> {
+        self.inference.of(name).and_then(|occurrence| occurrence.known)
+    }
+
+    fn report(&mut self, rule: Rule, diagnostic: Diagnostic, fix: Vec<Range<usize>>) {
+        self.findings.push((rule, diagnostic, fix));
+    }
+
+    /// The source to delete to remove a statement: its whole lines when
+    /// nothing else is on them, otherwise just the statement.
+    fn lines(&self, span: Span) -> Range<usize> {
+        let source = self.source;
+        let start = source[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[span.end..].find('\n').map_or(source.len(), |i| span.end + i + 1);
+        if source[start..span.start].trim().is_empty() && source[span.end..end].trim().is_empty() {
+            start..end
+        } else {
+            span.start..span.end
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::samples::SAMPLES;
+
+    /// `(code, the source its span starts at, whether it has a fix)` for each finding.
+    fn findings(source: &str) -> Vec<(ErrorCode, String, bool)> {
+        lint(source, &Config::default())
+            .unwrap()
+            .into_iter()
+            .map(|finding| {
+                let start = finding.diagnostic.span.unwrap().start;
+                let line = source[start..].lines().next().unwrap().to_string();
+                (finding.diagnostic.code, line, !finding.fix.is_empty())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_values_that_are_never_read() {
+        let source = "\
+oi mug a = 1
+oi mug a = 2
+oi mug bhan naam
+oi mug b = 3
+yedi naam babaal \"Sita\" bhane
+    mug jod b, b lai b
+sakiyo
+bol mug b
+mug lambai naam lai n
+";
+        assert_eq!(
+            findings(source),
+            [
+                (ErrorCode::UnusedVariable, "a = 1".to_string(), true),
+                (ErrorCode::UnusedVariable, "n".to_string(), false),
+            ]
+        );
+
+        // Both values of `a` go; the call might fail, so it stays
+        let fixed = fix(source, &lint(source, &Config::default()).unwrap());
+        assert!(fixed.starts_with("oi mug bhan naam\n"));
+        assert_eq!(findings(&fixed), [(ErrorCode::UnusedVariable, "n".to_string(), false)]);
+        assert_eq!(findings("oi mug a = 1\noi mug a = 2\nbol mug a\n"), [(ErrorCode::UnreadAssignment, "a = 1".to_string(), true)]);
+    }
+
+    #[test]
+    fn finds_blocks_that_do_not_do_what_they_seem_to() {
+        let source = "\
+oi mug a = 4
+oi mug bhan naam
+yedi naam babaal \"Sita\" bhane
+    oi mug bhan naam
+    oi mug naya = \"x\"
+    bol mug naya
+sakiyo
+yedi naam laamo \"Ram\" bhane
+sakiyo
+yedi a babaal \"4\" bhane
+    bol mug \"char\"
+sakiyo
+bol mug naam
+";
+        let found = findings(source);
+        assert_eq!(
+            found,
+            [
+                (ErrorCode::ShadowedDeclaration, "naam".to_string(), false),
+                (ErrorCode::EmptyYedi, "yedi naam laamo \"Ram\" bhane".to_string(), true),
+                (ErrorCode::NumberComparedToString, "a babaal \"4\" bhane".to_string(), false),
+                (ErrorCode::UnreachableCode, "bol mug naam".to_string(), false),
+            ]
+        );
+        // `check --types` reports the comparison the same way
+        let statements = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        let error = crate::typecheck::check(&statements, &crate::native::Natives::stdlib()).unwrap_err();
+        assert_eq!(error.diagnostic().unwrap().code, ErrorCode::NumberComparedToString);
+
+        let fixed = fix(source, &lint(source, &Config::default()).unwrap());
+        assert!(!fixed.contains("Ram"));
+        assert!(fixed.contains("bol mug naam"));
+
+        // A variable with no value on any path stops the program too
+        assert_eq!(findings("bol mug kohi\nbol mug \"pachhi\"\n")[0].0, ErrorCode::UnreachableCode);
+        // ...but one given a value in a `yedi` body might have one
+        assert!(findings("oi mug bhan x\nyedi x babaal \"a\" bhane\n    oi mug y = \"b\"\nsakiyo\nbol mug y\n").is_empty());
+    }
+
+    #[test]
+    fn reads_severities_from_the_project_file() {
+        let config = Config::from_json(r#"{ "name": "app", "lint": { "unused-variable": "error", "empty-yedi": "allow" } }"#).unwrap();
+        assert_eq!(config.severity(Rule::UnusedVariable), Severity::Error);
+        assert_eq!(config.severity(Rule::NumberComparedToString), Severity::Error);
+        assert_eq!(config.severity(Rule::UnreadAssignment), Severity::Warning);
+
+        let found = lint("oi mug a = 1\nyedi a laamo \"1\" bhane\nsakiyo\n", &config.clone().with_severity(Rule::NumberComparedToString, Severity::Allow)).unwrap();
+        assert!(found.iter().all(|finding| finding.diagnostic.code != ErrorCode::EmptyYedi && finding.diagnostic.code != ErrorCode::NumberComparedToString));
+
+        assert!(Config::from_json(r#"{ "lint": { "unused": "error" } }"#).unwrap_err().contains("unknown rule"));
+        assert!(Config::from_json(r#"{ "lint": { "empty-yedi": "deny" } }"#).unwrap_err().contains("unknown severity"));
+        let defaults = Config::from_json(&Config::default().to_json().to_string()).unwrap();
+        assert!(Rule::ALL.into_iter().all(|rule| defaults.severity(rule) == rule.default_severity()));
+    }
+
+    #[test]
+    fn samples_have_no_findings() {
+        for sample in SAMPLES {
+            assert_eq!(findings(sample), []);
+        }
+    }
+}
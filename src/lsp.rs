@@ -0,0 +1,493 @@
+//! A Language Server Protocol server, so editors can show LyangLang errors
+//! while a program is typed and help move around it.
+//!
+//! The server speaks LSP over a pair of streams, normally stdio, and keeps
+//! the full text of each open document. It offers:
+//!
+//! - diagnostics from the lexer, the parser and the compiler's checks,
+//!   published whenever a document changes
+//! - hover with a variable's inferred types and the statement that first
+//!   gives it a value, or a native function's parameters
+//! - go-to-definition of variables
+//! - completion of keywords, variables given a value earlier, and native
+//!   functions
+//! - a document symbol for each variable
+//!
+//! Positions are counted in UTF-16 code units, as LSP requires.
+
+use crate::diagnostic::Word;
+use crate::error::NepalError;
+use crate::interpreter;
+use crate::lexer::Lexer;
+use crate::messages::{self, Lang};
+use crate::native::{Natives, Type};
+use crate::parser::Parser;
+use crate::protocol;
+use crate::span::Span;
+use crate::symbols::{Access, Symbols};
+use serde_json::{json, Value as Json};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+/// JSON-RPC error code for a request sent after `shutdown`.
+const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code for a request the server does not handle.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// LSP `CompletionItemKind` and `SymbolKind` values
+const FUNCTION_ITEM: i64 = 3;
+const VARIABLE_ITEM: i64 = 6;
+const KEYWORD_ITEM: i64 = 14;
+const VARIABLE_SYMBOL: i64 = 13;
+
+/// Keywords offered by completion, with what each does.
+const KEYWORDS: &[(&str, &str)] = &[
+    ("oi mug", "give a variable a value"),
+    ("oi mug bhan", "read a line of input into a variable"),
+    ("bol mug", "print a variable or text"),
+    ("mug jod", "add"),
+    ("mug ghata", "subtract"),
+    ("mug guna", "multiply"),
+    ("mug bhag", "divide"),
+    ("lai", "store the result in"),
+    ("yedi", "run a block if a condition holds"),
+    ("babaal", "is equal to"),
+    ("laamo", "is not equal to"),
+    ("bhane", "start the block of a condition"),
+    ("aile feri", "otherwise, if"),
+    ("sakiyo", "end a yedi block"),
+];
+
+/// An open document and what is known about it.
+struct Document {
+    text: String,
+    symbols: Symbols,
+}
+
+/// An LSP server for the documents one editor opens.
+pub struct Server {
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    documents: HashMap<String, Document>,
+    natives: Natives,
+    lang: Lang,
+    shutting_down: bool,
+}
+
+impl Server {
+    /// Reads messages from `input` and writes to `output`.
+    pub fn new(input: impl BufRead + 'static, output: impl Write + 'static) -> Self {
+        Self {
+            input: Box::new(input),
+            output: Box::new(output),
+            documents: HashMap::new(),
+            natives: Natives::stdlib(),
+            lang: Lang::default(),
+            shutting_down: false,
+        }
+    }
+
+    /// Reports errors in `lang`.
+    pub fn with_lang(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+
+    /// Serves the editor until it sends `exit` or closes the input.
+    pub fn run(mut self) -> io::Result<()> {
+        while let Some(message) = protocol::read(&mut self.input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+            let Some(id) = message.get("id") else {
+                match method {
+                    "exit" => return Ok(()),
+                    "textDocument/didOpen" => {
+                        let document = &params["textDocument"];
+                        self.update(document["uri"].as_str().unwrap_or_default(), text(&document["text"]))?;
+                    }
+                    "textDocument/didChange" => {
+                        // Full sync: the last change holds the whole text
+                        if let Some(change) = params["contentChanges"].as_array().and_then(|c| c.last()) {
+                            self.update(uri(params), text(&change["text"]))?;
+                        }
+                    }
+                    "textDocument/didClose" => {
+                        self.documents.remove(uri(params));
+                        self.publish(uri(params), Vec::new())?;
+                    }
+                    _ => {}
+                }
+                continue;
+            };
+            if method.is_empty() {
+                // A response to a request the server never sends
+                continue;
+            }
+
+            let result = if self.shutting_down {
+                Err((INVALID_REQUEST, "the server is shutting down".to_string()))
+            } else {
+                self.request(method, params)
+            };
+            let response = match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => {
+                    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+                }
+            };
+            protocol::write(&mut self.output, &response)?;
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<Json, (i64, String)> {
+        if method == "initialize" {
+            return Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "lyangpiler", "version": env!("CARGO_PKG_VERSION") },
+            }));
+        }
+        if method == "shutdown" {
+            self.shutting_down = true;
+            return Ok(Json::Null);
+        }
+
+        let uri = uri(params);
+        let Some(document) = self.documents.get(uri) else {
+            return match method.starts_with("textDocument/") {
+                true => Ok(Json::Null),
+                false => Err((METHOD_NOT_FOUND, format!("`{}` is not supported", method))),
+            };
+        };
+        let offset = offset(&document.text, &params["position"]);
+        match method {
+            "textDocument/hover" => Ok(self.hover(document, offset)),
+            "textDocument/definition" => Ok(definition(document, uri, offset)),
+            "textDocument/completion" => Ok(self.completion(document, offset)),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(document)),
+            _ => Err((METHOD_NOT_FOUND, format!("`{}` is not supported", method))),
+        }
+    }
+
+    /// Analyses the new `text` of `uri` and publishes its diagnostics.
+    fn update(&mut self, uri: &str, text: String) -> io::Result<()> {
+        let (statements, mut errors) = match Lexer::new(&text).tokenize() {
+            Ok(tokens) => Parser::new(tokens).parse_with_errors(),
+            Err(error) => (Vec::new(), vec![error]),
+        };
+        errors.extend(statements.iter().filter_map(|statement| interpreter::check(statement, &self.natives).err()));
+
+        let diagnostics = errors.iter().flat_map(NepalError::errors).map(|error| self.diagnostic(&text, error)).collect();
+        let symbols = Symbols::new(&statements);
+        self.documents.insert(uri.to_string(), Document { text, symbols });
+        self.publish(uri, diagnostics)
+    }
+
+    fn diagnostic(&self, text: &str, error: &NepalError) -> Json {
+        let Some(diagnostic) = error.diagnostic() else {
+            return json!({ "range": range(text, Span::default()), "severity": 1, "source": "lyangpiler", "message": error.to_string() });
+        };
+        let mut message = diagnostic.message_in(self.lang);
+        if let Some(help) = diagnostic.help_in(self.lang) {
+            message = format!("{}\nhelp: {}", message, help);
+        }
+        json!({
+            "range": range(text, diagnostic.span.unwrap_or_default()),
+            "severity": 1,
+            "code": diagnostic.code.as_str(),
+            "source": "lyangpiler",
+            "message": message,
+        })
+    }
+
+    fn publish(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        });
+        protocol::write(&mut self.output, &notification)
+    }
+
+    fn hover(&self, document: &Document, offset: usize) -> Json {
+        let Some(occurrence) = document.symbols.at(offset) else {
+            return Json::Null;
+        };
+        let name = &occurrence.name;
+        let value = if occurrence.access == Access::Call {
+            let Some(params) = self.natives.params(name) else {
+                return Json::Null;
+            };
+            format!("```lyanglang\n{}\n```\n{}", self.signature(name, params), messages::builtin_function(self.lang))
+        } else {
+            match document.symbols.variable(name) {
+                Some(variable) => {
+                    let statement = &document.text[variable.statement.start..variable.statement.end];
+                    format!(
+                        "```lyanglang\n{}: {}\n```\n{}\n```lyanglang\n{}\n```",
+                        name,
+                        self.types(&variable.types),
+                        messages::first_given_on_line(variable.statement.line, self.lang),
+                        statement.lines().next().unwrap_or_default().trim(),
+                    )
+                }
+                None => messages::never_given_a_value(name, self.lang),
+            }
+        };
+        json!({
+            "contents": { "kind": "markdown", "value": value },
+            "range": range(&document.text, occurrence.span),
+        })
+    }
+
+    fn completion(&self, document: &Document, offset: usize) -> Json {
+        let keywords = KEYWORDS
+            .iter()
+            .map(|(keyword, detail)| json!({ "label": keyword, "kind": KEYWORD_ITEM, "detail": detail }));
+        // Only variables that already have a value where the cursor is
+        let variables = document
+            .symbols
+            .variables()
+            .iter()
+            .filter(|variable| variable.statement.end <= offset)
+            .map(|variable| json!({ "label": variable.name, "kind": VARIABLE_ITEM, "detail": self.types(&variable.types) }));
+        let functions = self.natives.names().into_iter().map(|name| {
+            let params = self.natives.params(name).unwrap_or_default();
+            json!({ "label": name, "kind": FUNCTION_ITEM, "detail": self.signature(name, params) })
+        });
+        Json::Array(keywords.chain(variables).chain(functions).collect())
+    }
+
+    fn document_symbols(&self, document: &Document) -> Json {
+        let symbols = document.symbols.variables().iter().map(|variable| {
+            json!({
+                "name": variable.name,
+                "kind": VARIABLE_SYMBOL,
+                "detail": self.types(&variable.types),
+                "range": range(&document.text, variable.statement),
+                "selectionRange": range(&document.text, variable.definition),
+            })
+        });
+        Json::Array(symbols.collect())
+    }
+
+    /// `types` as hover and completion show them, e.g. "number or string".
+    fn types(&self, types: &[Type]) -> String {
+        let names: Vec<&str> = types.iter().map(|&kind| self.type_name(kind)).collect();
+        messages::one_of(&names, self.lang)
+    }
+
+    fn type_name(&self, kind: Type) -> &'static str {
+        messages::word(kind.word().unwrap_or(Word::AnyType), self.lang)
+    }
+
+    /// How a call to `name` is written, e.g. "mug shesh number, number".
+    fn signature(&self, name: &str, params: &[Type]) -> String {
+        let params: Vec<&str> = params.iter().map(|&kind| self.type_name(kind)).collect();
+        format!("mug {} {}", name, params.join(", ")).trim_end().to_string()
+    }
+}
+
+/// Where the variable at `offset` is first given a value.
+fn definition(document: &Document, uri: &str, offset: usize) -> Json {
+    let variable = document
+        .symbols
+        .at(offset)
+        .filter(|occurrence| occurrence.access != Access::Call)
+        .and_then(|occurrence| document.symbols.variable(&occurrence.name));
+    match variable {
+        Some(variable) => json!({ "uri": uri, "range": range(&document.text, variable.definition) }),
+        None => Json::Null,
+    }
+}
+
+fn uri(params: &Json) -> &str {
+    params["textDocument"]["uri"].as_str().unwrap_or_default()
+}
+
+fn text(text: &Json) -> String {
+    text.as_str().unwrap_or_default().to_string()
+}
+
+/// The LSP position of byte `offset` in `text`.
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    json!({ "line": before.matches('\n').count(), "character": before[line_start..].encode_utf16().count() })
+}
+
+fn range(text: &str, span: Span) -> Json {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+/// The byte offset in `text` of an LSP position, clamped to its line.
+fn offset(text: &str, position: &Json) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+    let start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (index, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' {
+            return start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::OutputBuffer;
+    use crate::protocol::tests::{frame, unframe};
+
+    const URI: &str = "file:///namaste.nbh";
+
+    /// Plays `messages` to a server, numbering the requests, and returns
+    /// every message it sent back.
+    fn transcript(messages: &[Json]) -> Vec<Json> {
+        transcript_in(Lang::En, messages)
+    }
+
+    fn transcript_in(lang: Lang, messages: &[Json]) -> Vec<Json> {
+        let mut all = vec![json!({ "method": "initialize", "id": 0, "params": {} }), json!({ "method": "initialized" })];
+        all.extend(messages.iter().cloned());
+        for message in &mut all {
+            message["jsonrpc"] = json!("2.0");
+        }
+        let output = OutputBuffer::default();
+        Server::new(frame(&all), output.clone()).with_lang(lang).run().unwrap();
+        unframe(&output)
+    }
+
+    fn open(text: &str) -> Json {
+        json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "uri": URI, "languageId": "lyanglang", "version": 1, "text": text } } })
+    }
+
+    fn at(method: &str, id: i64, line: usize, character: usize) -> Json {
+        json!({ "method": method, "id": id, "params": { "textDocument": { "uri": URI }, "position": { "line": line, "character": character } } })
+    }
+
+    fn result(messages: &[Json], id: i64) -> &Json {
+        &messages.iter().find(|m| m["id"] == id).unwrap()["result"]
+    }
+
+    fn diagnostics(messages: &[Json]) -> Vec<&Json> {
+        messages
+            .iter()
+            .filter(|m| m["method"] == "textDocument/publishDiagnostics")
+            .map(|m| &m["params"]["diagnostics"])
+            .collect()
+    }
+
+    #[test]
+    fn publishes_diagnostics_as_the_document_changes() {
+        let change = |text: &str| {
+            json!({ "method": "textDocument/didChange", "params": { "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": text }] } })
+        };
+        let messages = transcript(&[
+            open("bol mug\noi mug x 2\n"),
+            change("oi mug y = jod\nmug jhol x\n"),
+            change("oi mug x = 2\n"),
+            change("bol mug \"namaste\n"),
+            json!({ "method": "textDocument/didClose", "params": { "textDocument": { "uri": URI } } }),
+        ]);
+        assert_eq!(result(&messages, 0)["capabilities"]["hoverProvider"], true);
+
+        let published = diagnostics(&messages);
+        let codes = |diagnostics: &Json| -> Vec<String> {
+            diagnostics.as_array().unwrap().iter().map(|d| d["code"].as_str().unwrap().to_string()).collect()
+        };
+        assert_eq!(codes(published[0]), ["LY0111", "LY0108"]);
+        assert_eq!(published[0][1]["range"], json!({ "start": { "line": 1, "character": 9 }, "end": { "line": 1, "character": 10 } }));
+        // The compiler's checks run on statements that parsed
+        assert_eq!(codes(published[1]), ["LY0201", "LY0203"]);
+        assert!(published[1][1]["message"].as_str().unwrap().starts_with("no function named `jhol`"));
+        assert_eq!(published[2], &json!([]));
+        assert_eq!(published[3][0]["message"], "unterminated string\nhelp: add a closing `\"`");
+        assert_eq!(published[4], &json!([]));
+    }
+
+    const SOURCE: &str = "\
+oi mug bhan naam
+bol mug \"नमस्ते \" + naam
+mug lambai naam lai n
+yedi naam babaal \"Sita\" bhane
+  oi mug n = \"dui\"
+sakiyo
+";
+
+    #[test]
+    fn hover_definition_and_symbols() {
+        let messages = transcript(&[
+            open(SOURCE),
+            // `naam` after the Devanagari, which is one UTF-16 unit a letter
+            at("textDocument/hover", 1, 1, 20),
+            at("textDocument/hover", 2, 2, 6),
+            at("textDocument/hover", 3, 4, 10),
+            at("textDocument/definition", 4, 1, 20),
+            at("textDocument/definition", 5, 2, 6),
+            json!({ "method": "textDocument/documentSymbol", "id": 6, "params": { "textDocument": { "uri": URI } } }),
+        ]);
+        let naam = result(&messages, 1);
+        assert_eq!(
+            naam["contents"]["value"],
+            "```lyanglang\nnaam: string\n```\nFirst given a value on line 1:\n```lyanglang\noi mug bhan naam\n```"
+        );
+        assert_eq!(naam["range"], json!({ "start": { "line": 1, "character": 20 }, "end": { "line": 1, "character": 24 } }));
+        assert_eq!(result(&messages, 2)["contents"]["value"], "```lyanglang\nmug lambai string\n```\nBuilt-in function");
+        assert!(result(&messages, 3)["contents"]["value"].as_str().unwrap().starts_with("```lyanglang\nn: any or string\n```\nFirst given a value on line 3:"));
+
+        assert_eq!(result(&messages, 4), &json!({ "uri": URI, "range": { "start": { "line": 0, "character": 12 }, "end": { "line": 0, "character": 16 } } }));
+        assert_eq!(result(&messages, 5), &Json::Null);
+
+        let symbols = result(&messages, 6).as_array().unwrap();
+        let names: Vec<&Json> = symbols.iter().map(|symbol| &symbol["name"]).collect();
+        assert_eq!(names, ["naam", "n"]);
+        assert_eq!(symbols[1]["range"]["start"], json!({ "line": 2, "character": 0 }));
+    }
+
+    #[test]
+    fn hovers_in_the_chosen_language() {
+        let messages = transcript_in(
+            Lang::NeRom,
+            &[open(SOURCE), at("textDocument/hover", 1, 1, 20), at("textDocument/hover", 2, 2, 6), at("textDocument/hover", 3, 4, 10)],
+        );
+        assert!(result(&messages, 1)["contents"]["value"].as_str().unwrap().contains("pahilo patak line 1 ma maan diiyo:"));
+        assert!(result(&messages, 2)["contents"]["value"].as_str().unwrap().ends_with("\nbhitri function"));
+        assert!(result(&messages, 3)["contents"]["value"].as_str().unwrap().contains(" wa "));
+    }
+
+    #[test]
+    fn completes_keywords_variables_and_functions() {
+        let messages = transcript(&[
+            open(SOURCE),
+            at("textDocument/completion", 1, 1, 0),
+            at("textDocument/completion", 2, 6, 0),
+            json!({ "method": "shutdown", "id": 3 }),
+            at("textDocument/hover", 4, 0, 0),
+            json!({ "method": "exit" }),
+            at("textDocument/hover", 5, 0, 0),
+        ]);
+        let labels = |id| -> Vec<String> {
+            result(&messages, id).as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap().to_string()).collect()
+        };
+        let early = labels(1);
+        assert!(early.contains(&"oi mug bhan".to_string()) && early.contains(&"sakiyo".to_string()));
+        assert!(early.contains(&"naam".to_string()) && !early.contains(&"n".to_string()));
+        let late = labels(2);
+        assert!(late.contains(&"n".to_string()) && late.contains(&"shesh".to_string()));
+        let shesh = result(&messages, 2).as_array().unwrap().iter().find(|item| item["label"] == "shesh").unwrap();
+        assert_eq!(shesh["detail"], "mug shesh number, number");
+
+        assert_eq!(messages.iter().find(|m| m["id"] == 4).unwrap()["error"]["code"], INVALID_REQUEST);
+        // Nothing is read after `exit`
+        assert!(messages.iter().all(|m| m["id"] != 5));
+    }
+}
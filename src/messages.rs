@@ -0,0 +1,981 @@
+//! Message catalogue: the text of every diagnostic in each supported language.
+//!
+//! Templates use `{0}`, `{1}`, ... for the diagnostic's arguments. A
+//! translation must use exactly the same placeholders as the English text.
+
+use crate::diagnostic::{ErrorCode, Note, Word};
+use std::fmt;
+use std::str::FromStr;
+
+/// Language diagnostics are shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    /// Nepali in Devanagari script
+    Ne,
+    /// Nepali written in Latin script
+    NeRom,
+}
+
+impl Lang {
+    /// Environment variable consulted when `--lang` is not given.
+    pub const ENV_VAR: &'static str = "LYANGPILER_LANG";
+
+    /// The language named by `LYANGPILER_LANG`, if it is set to a known value.
+    pub fn from_env() -> Option<Lang> {
+        std::env::var(Self::ENV_VAR).ok()?.parse().ok()
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "en" => Ok(Lang::En),
+            "ne" => Ok(Lang::Ne),
+            "ne-rom" | "ne_rom" => Ok(Lang::NeRom),
+            other => Err(format!("unknown language `{}` (expected en, ne or ne-rom)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Lang::En => "en",
+            Lang::Ne => "ne",
+            Lang::NeRom => "ne-rom",
+        })
+    }
+}
+
+/// One piece of text in every supported language.
+#[derive(Clone, Copy)]
+struct Text {
+    en: &'static str,
+    ne: &'static str,
+    rom: &'static str,
+}
+
+impl Text {
+    fn get(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => self.en,
+            Lang::Ne => self.ne,
+            Lang::NeRom => self.rom,
+        }
+    }
+}
+
+macro_rules! text {
+    ($en:expr, $ne:expr, $rom:expr $(,)?) => {
+        Text { en: $en, ne: $ne, rom: $rom }
+    };
+}
+
+/// Everything the catalogue knows about one error code.
+struct Entry {
+    message: Text,
+    label: Option<Text>,
+    help: Option<Text>,
+}
+
+fn entry(code: ErrorCode) -> Entry {
+    let (message, label, help) = match code {
+        ErrorCode::UnexpectedCharacter => (
+            text!("unexpected character `{0}`", "अनपेक्षित अक्षर `{0}`", "anapekshit akshar `{0}`"),
+            Some(text!("not valid in LyangLang", "LyangLang मा मान्य छैन", "LyangLang ma manya chhaina")),
+            None,
+        ),
+        ErrorCode::ExpectedEqualsAfterBang => (
+            text!("expected `=` after `!`", "`!` पछि `=` चाहिन्छ", "`!` pachhi `=` chahinchha"),
+            Some(text!(
+                "`!` must be followed by `=`",
+                "`!` पछि `=` हुनुपर्छ",
+                "`!` pachhi `=` hunuparchha",
+            )),
+            Some(text!(
+                "use `!=` to compare for inequality",
+                "असमानता जाँच्न `!=` प्रयोग गर्नुहोस्",
+                "asamanta janchna `!=` prayog garnuhos",
+            )),
+        ),
+        ErrorCode::NumberTooLarge => (
+            text!("number `{0}` is too large", "संख्या `{0}` धेरै ठूलो छ", "sankhya `{0}` dherai thulo chha"),
+            Some(text!(
+                "does not fit in a 32-bit number",
+                "३२-बिट संख्यामा अटाउँदैन",
+                "32-bit sankhya ma autaudaina",
+            )),
+            Some(text!(
+                "numbers must be between 0 and 2147483647",
+                "संख्या 0 देखि 2147483647 सम्म हुनुपर्छ",
+                "sankhya 0 dekhi 2147483647 samma hunuparchha",
+            )),
+        ),
+        ErrorCode::UnterminatedString => (
+            text!("unterminated string", "स्ट्रिङ बन्द गरिएको छैन", "string banda gariyeko chhaina"),
+            Some(text!("string starts here", "स्ट्रिङ यहाँ सुरु हुन्छ", "string yaha suru hunchha")),
+            Some(text!("add a closing `\"`", "अन्त्यमा `\"` थप्नुहोस्", "antyama `\"` thapnuhos")),
+        ),
+        ErrorCode::UnterminatedBlockComment => (
+            text!(
+                "unterminated block comment",
+                "ब्लक टिप्पणी बन्द गरिएको छैन",
+                "block tippani banda gariyeko chhaina",
+            ),
+            Some(text!("comment opened here", "टिप्पणी यहाँ खोलिएको हो", "tippani yaha kholiyeko ho")),
+            Some(text!(
+                "close the comment with `*/`",
+                "टिप्पणीलाई `*/` ले बन्द गर्नुहोस्",
+                "tippani lai `*/` le banda garnuhos",
+            )),
+        ),
+
+        ErrorCode::UnexpectedToken => (
+            text!(
+                "unexpected {0} at the start of a statement",
+                "वाक्यको सुरुमा अनपेक्षित {0}",
+                "vakya ko suruma anapekshit {0}",
+            ),
+            Some(text!("not a statement", "यो वाक्य होइन", "yo vakya hoina")),
+            Some(text!(
+                "statements start with `oi mug`, `bol mug`, `mug`, `yedi` or `aile feri`",
+                "वाक्य `oi mug`, `bol mug`, `mug`, `yedi` वा `aile feri` बाट सुरु हुन्छ",
+                "vakya `oi mug`, `bol mug`, `mug`, `yedi` wa `aile feri` bata suru hunchha",
+            )),
+        ),
+        ErrorCode::ExpectedMugOperation => (
+            text!(
+                "expected `jod`, `ghata`, `guna`, `bhag` or a function name after `mug`, found {0}",
+                "`mug` पछि `jod`, `ghata`, `guna`, `bhag` वा फङ्सनको नाम चाहिन्छ, तर {0} भेटियो",
+                "`mug` pachhi `jod`, `ghata`, `guna`, `bhag` wa function ko naam chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected an operation", "यहाँ क्रिया चाहिन्छ", "yaha kriya chahinchha")),
+            None,
+        ),
+        ErrorCode::ExpectedFeri => (
+            text!(
+                "expected `feri` after `aile`, found {0}",
+                "`aile` पछि `feri` चाहिन्छ, तर {0} भेटियो",
+                "`aile` pachhi `feri` chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected `feri`", "`feri` चाहिन्छ", "`feri` chahinchha")),
+            Some(text!(
+                "write `aile feri` to check another condition",
+                "अर्को सर्त जाँच्न `aile feri` लेख्नुहोस्",
+                "arko sarta janchna `aile feri` lekhnuhos",
+            )),
+        ),
+        ErrorCode::ExpectedIdentifier => (
+            text!(
+                "expected a variable name after {1}, found {0}",
+                "{1} पछि चरको नाम चाहिन्छ, तर {0} भेटियो",
+                "{1} pachhi char ko naam chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected a variable name", "चरको नाम चाहिन्छ", "char ko naam chahinchha")),
+            None,
+        ),
+        ErrorCode::ExpectedComparison => (
+            text!(
+                "expected `babaal` or `laamo`, found {0}",
+                "`babaal` वा `laamo` चाहिन्छ, तर {0} भेटियो",
+                "`babaal` wa `laamo` chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!(
+                "expected `babaal` or `laamo`",
+                "`babaal` वा `laamo` चाहिन्छ",
+                "`babaal` wa `laamo` chahinchha",
+            )),
+            Some(text!(
+                "`babaal` checks for equality and `laamo` for inequality",
+                "`babaal` ले बराबरी र `laamo` ले असमानता जाँच्छ",
+                "`babaal` le barabari ra `laamo` le asamanta janchha",
+            )),
+        ),
+        ErrorCode::ExpectedStringLiteral => (
+            text!(
+                "expected a string to compare with, found {0}",
+                "तुलना गर्न स्ट्रिङ चाहिन्छ, तर {0} भेटियो",
+                "tulana garna string chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!(
+                "expected a quoted string",
+                "उद्धरण चिन्हभित्रको स्ट्रिङ चाहिन्छ",
+                "udharan chinha bhitra ko string chahinchha",
+            )),
+            Some(text!(
+                "conditions compare with a quoted string, e.g. `yedi rang babaal \"rato\" bhane`",
+                "सर्तले उद्धरण चिन्हभित्रको स्ट्रिङसँग तुलना गर्छ, जस्तै `yedi rang babaal \"rato\" bhane`",
+                "sarta le udharan chinha bhitra ko string sanga tulana garchha, jastai `yedi rang babaal \"rato\" bhane`",
+            )),
+        ),
+        ErrorCode::ExpectedBhane => (
+            text!(
+                "expected `bhane` after the condition, found {0}",
+                "सर्तपछि `bhane` चाहिन्छ, तर {0} भेटियो",
+                "sarta pachhi `bhane` chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected `bhane`", "`bhane` चाहिन्छ", "`bhane` chahinchha")),
+            None,
+        ),
+        ErrorCode::ExpectedEquals => (
+            text!(
+                "expected `=` after `{1}`, found {0}",
+                "`{1}` पछि `=` चाहिन्छ, तर {0} भेटियो",
+                "`{1}` pachhi `=` chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected `=`", "`=` चाहिन्छ", "`=` chahinchha")),
+            Some(text!(
+                "declare variables as `oi mug {1} = ...`",
+                "चर यसरी घोषणा गर्नुहोस्: `oi mug {1} = ...`",
+                "char yasari ghoshana garnuhos: `oi mug {1} = ...`",
+            )),
+        ),
+        ErrorCode::ExpectedValue => (
+            text!(
+                "expected a value after `=`, found {0}",
+                "`=` पछि मान चाहिन्छ, तर {0} भेटियो",
+                "`=` pachhi maan chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected a value", "मान चाहिन्छ", "maan chahinchha")),
+            Some(text!(
+                "a value is a number, a string, a variable, or `jod`/`ghata`/`guna`/`bhag` with variables",
+                "मान भनेको संख्या, स्ट्रिङ, चर, वा चरहरूसँग `jod`/`ghata`/`guna`/`bhag` हो",
+                "maan bhaneko sankhya, string, char, wa char haru sanga `jod`/`ghata`/`guna`/`bhag` ho",
+            )),
+        ),
+        ErrorCode::ExpectedCommaOrLai => (
+            text!(
+                "expected `,` or `lai` after `{1}`, found {0}",
+                "`{1}` पछि `,` वा `lai` चाहिन्छ, तर {0} भेटियो",
+                "`{1}` pachhi `,` wa `lai` chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected `,` or `lai`", "`,` वा `lai` चाहिन्छ", "`,` wa `lai` chahinchha")),
+            Some(text!(
+                "write it as `mug jod a, b lai total`",
+                "यसरी लेख्नुहोस्: `mug jod a, b lai total`",
+                "yasari lekhnuhos: `mug jod a, b lai total`",
+            )),
+        ),
+        ErrorCode::ExpectedPrintable => (
+            text!(
+                "expected a variable or string after `bol mug`, found {0}",
+                "`bol mug` पछि चर वा स्ट्रिङ चाहिन्छ, तर {0} भेटियो",
+                "`bol mug` pachhi char wa string chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected something to print", "छाप्ने कुरा चाहिन्छ", "chhapne kura chahinchha")),
+            None,
+        ),
+        ErrorCode::UnexpectedEndOfFile => (
+            text!(
+                "unexpected end of file, {0}",
+                "फाइल अनपेक्षित रूपमा सकियो, {0}",
+                "file anapekshit rup ma sakiyo, {0}",
+            ),
+            Some(text!("the file ends here", "फाइल यहीँ सकिन्छ", "file yahi sakinchha")),
+            Some(text!(
+                "the last statement is incomplete; finish it or remove it",
+                "अन्तिम वाक्य अधुरो छ; यसलाई पूरा गर्नुहोस् वा हटाउनुहोस्",
+                "antim vakya adhuro chha; yeslai pura garnuhos wa hataunuhos",
+            )),
+        ),
+        ErrorCode::ExpectedArgument => (
+            text!(
+                "expected an argument after `,`, found {0}",
+                "`,` पछि आर्गुमेन्ट चाहिन्छ, तर {0} भेटियो",
+                "`,` pachhi argument chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected an argument", "आर्गुमेन्ट चाहिन्छ", "argument chahinchha")),
+            Some(text!(
+                "arguments are variables, numbers or strings",
+                "आर्गुमेन्ट चर, संख्या वा स्ट्रिङ हुन्छ",
+                "argument char, sankhya wa string hunchha",
+            )),
+        ),
+        ErrorCode::ExpectedTypeName => (
+            text!(
+                "expected a type after `:`, found {0}",
+                "`:` पछि प्रकार चाहिन्छ, तर {0} भेटियो",
+                "`:` pachhi prakar chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("expected `sankhya` or `shabda`", "`sankhya` वा `shabda` चाहिन्छ", "`sankhya` wa `shabda` chahinchha")),
+            Some(text!(
+                "use `sankhya` for a number or `shabda` for a string",
+                "संख्याका लागि `sankhya` वा स्ट्रिङका लागि `shabda` प्रयोग गर्नुहोस्",
+                "sankhya ko lagi `sankhya` wa string ko lagi `shabda` prayog garnuhos",
+            )),
+        ),
+        ErrorCode::InputAnnotation => (
+            text!(
+                "`oi mug bhan` always gives a string, so `{0}` cannot be declared as a number",
+                "`oi mug bhan` ले सधैं स्ट्रिङ दिन्छ, त्यसैले `{0}` लाई संख्या भनी घोषणा गर्न मिल्दैन",
+                "`oi mug bhan` le sadhai string dinchha, tyesaile `{0}` lai sankhya bhani ghoshana garna mildaina",
+            ),
+            Some(text!("input is a string", "इनपुट स्ट्रिङ हो", "input string ho")),
+            Some(text!(
+                "declare it as `{0}: shabda`, or leave out the type",
+                "यसलाई `{0}: shabda` भनी घोषणा गर्नुहोस्, वा प्रकार नलेख्नुहोस्",
+                "yeslai `{0}: shabda` bhani ghoshana garnuhos, wa prakar nalekhnuhos",
+            )),
+        ),
+
+        ErrorCode::NestingTooDeep => (
+            text!(
+                "`yedi` blocks are nested more than {0} deep",
+                "`yedi` ब्लकहरू {0} तहभन्दा गहिरो राखिएका छन्",
+                "`yedi` block haru {0} taha bhanda gahiro rakhiyeka chhan",
+            ),
+            Some(text!("this block is too deep", "यो ब्लक धेरै गहिरो छ", "yo block dherai gahiro chha")),
+            Some(text!(
+                "nest fewer `yedi` blocks inside one another",
+                "`yedi` ब्लकहरू एकभित्र अर्कोमा कम राख्नुहोस्",
+                "`yedi` block haru ek bhitra arko ma kam rakhnuhos",
+            )),
+        ),
+        ErrorCode::MissingOperands => (
+            text!(
+                "`{0}` needs at least one variable",
+                "`{0}` लाई कम्तीमा एउटा चर चाहिन्छ",
+                "`{0}` lai kamtima euta char chahinchha",
+            ),
+            Some(text!("no variables given", "कुनै चर दिइएको छैन", "kunai char diiyeko chhaina")),
+            Some(text!(
+                "list the variables separated by commas, e.g. `oi mug total = jod a, b`",
+                "चरहरू अल्पविरामले छुट्याएर लेख्नुहोस्, जस्तै `oi mug total = jod a, b`",
+                "char haru alpaviram le chhutyayera lekhnuhos, jastai `oi mug total = jod a, b`",
+            )),
+        ),
+        ErrorCode::EmptyConcatenation => (
+            text!(
+                "string joining needs at least one part",
+                "स्ट्रिङ जोड्न कम्तीमा एउटा भाग चाहिन्छ",
+                "string jodna kamtima euta bhag chahinchha",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::UnknownFunction => (
+            text!("no function named `{0}`", "`{0}` नामको कुनै फङ्सन छैन", "`{0}` naam ko kunai function chhaina"),
+            Some(text!("unknown function", "अज्ञात फङ्सन", "agyat function")),
+            Some(text!(
+                "after `mug` write `jod`, `ghata`, `guna`, `bhag` or the name of a function",
+                "`mug` पछि `jod`, `ghata`, `guna`, `bhag` वा फङ्सनको नाम लेख्नुहोस्",
+                "`mug` pachhi `jod`, `ghata`, `guna`, `bhag` wa function ko naam lekhnuhos",
+            )),
+        ),
+        ErrorCode::WrongArgumentCount => (
+            text!(
+                "`{0}` takes {1} arguments, found {2}",
+                "`{0}` ले {1} वटा आर्गुमेन्ट लिन्छ, तर {2} वटा भेटियो",
+                "`{0}` le {1} wata argument linchha, tara {2} wata bhetiyo",
+            ),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            None,
+        ),
+        ErrorCode::OperandType => (
+            text!(
+                "`{0}` needs numbers, but `{1}` is a {2}",
+                "`{0}` लाई संख्या चाहिन्छ, तर `{1}` {2} हो",
+                "`{0}` lai sankhya chahinchha, tara `{1}` {2} ho",
+            ),
+            Some(text!("not a number", "संख्या होइन", "sankhya hoina")),
+            Some(text!(
+                "turn it into a number first, e.g. `mug sankhya {1} lai {1}_sankhya`",
+                "पहिले यसलाई संख्यामा बदल्नुहोस्, जस्तै `mug sankhya {1} lai {1}_sankhya`",
+                "pahile yeslai sankhya ma badalnuhos, jastai `mug sankhya {1} lai {1}_sankhya`",
+            )),
+        ),
+        ErrorCode::AnnotationMismatch => (
+            text!(
+                "`{0}` is declared as a {1}, but is given a {2}",
+                "`{0}` लाई {1} भनी घोषणा गरिएको छ, तर {2} दिइयो",
+                "`{0}` lai {1} bhani ghoshana gariyeko chha, tara {2} diiyo",
+            ),
+            Some(text!("given a {2}", "{2} दिइयो", "{2} diiyo")),
+            Some(text!(
+                "give `{0}` a {1}, or declare it as `{0}: {3}`",
+                "`{0}` लाई {1} दिनुहोस्, वा `{0}: {3}` भनी घोषणा गर्नुहोस्",
+                "`{0}` lai {1} dinuhos, wa `{0}: {3}` bhani ghoshana garnuhos",
+            )),
+        ),
+
+        ErrorCode::StackUnderflow => (
+            text!("stack underflow", "स्ट्याक खाली भयो", "stack khali bhayo"),
+            None,
+            None,
+        ),
+        ErrorCode::InvalidStringIndex => (
+            text!(
+                "string constant {0} does not exist",
+                "स्ट्रिङ स्थिरांक {0} अवस्थित छैन",
+                "string sthirank {0} chhaina",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::InvalidVariableIndex => (
+            text!(
+                "variable slot {0} does not exist",
+                "चर स्थान {0} अवस्थित छैन",
+                "char sthan {0} chhaina",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::InvalidOperands => (
+            text!(
+                "cannot use `{0}` on a {1} and a {2}",
+                "{1} र {2} मा `{0}` प्रयोग गर्न सकिँदैन",
+                "{1} ra {2} ma `{0}` prayog garna sakidaina",
+            ),
+            Some(text!("in this statement", "यो वाक्यमा", "yo vakya ma")),
+            None,
+        ),
+        ErrorCode::DivisionByZero => (
+            text!("division by zero", "शून्यले भाग", "shunya le bhag"),
+            Some(text!("divides by zero", "शून्यले भाग गर्छ", "shunya le bhag garchha")),
+            Some(text!(
+                "check that the divisor is not 0 before dividing",
+                "भाग गर्नु अघि भाजक 0 छैन भनी जाँच गर्नुहोस्",
+                "bhag garnu aghi bhajak 0 chhaina bhani jaanch garnuhos",
+            )),
+        ),
+        ErrorCode::ConditionNotBoolean => (
+            text!(
+                "condition must be true or false, found a {0}",
+                "सर्त सत्य वा असत्य हुनुपर्छ, तर {0} भेटियो",
+                "sarta satya wa asatya hunuparchha, tara {0} bhetiyo",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::InvalidComparison => (
+            text!(
+                "cannot compare a {0} with a {1}",
+                "{0} लाई {1} सँग तुलना गर्न सकिँदैन",
+                "{0} lai {1} sanga tulana garna sakidaina",
+            ),
+            Some(text!("in this comparison", "यो तुलनामा", "yo tulana ma")),
+            None,
+        ),
+        ErrorCode::InputFailed => (
+            text!("failed to read input: {0}", "इनपुट पढ्न सकिएन: {0}", "input padhna sakiyena: {0}"),
+            None,
+            None,
+        ),
+        ErrorCode::OutputFailed => (
+            text!("failed to write output: {0}", "आउटपुट लेख्न सकिएन: {0}", "output lekhna sakiyena: {0}"),
+            None,
+            None,
+        ),
+        ErrorCode::NumberOverflow => (
+            text!(
+                "the result of `{0}` does not fit in a number",
+                "`{0}` को नतिजा संख्यामा अटाएन",
+                "`{0}` ko natija sankhya ma atayena",
+            ),
+            Some(text!("overflows here", "यहाँ सीमा नाघ्यो", "yaha sima naghyo")),
+            Some(text!(
+                "numbers must stay between -2147483648 and 2147483647",
+                "संख्या -2147483648 र 2147483647 बीच हुनुपर्छ",
+                "sankhya -2147483648 ra 2147483647 bich hunuparchha",
+            )),
+        ),
+        ErrorCode::UndefinedVariable => (
+            text!(
+                "`{0}` is used before it has a value",
+                "`{0}` लाई मान दिनुअघि नै प्रयोग गरियो",
+                "`{0}` lai maan dinu aghi nai prayog gariyo",
+            ),
+            Some(text!("not defined yet", "अझै परिभाषित छैन", "ajhai paribhashit chhaina")),
+            Some(text!(
+                "give it a value first, e.g. `oi mug {0} = 0`",
+                "पहिले मान दिनुहोस्, जस्तै `oi mug {0} = 0`",
+                "pahile maan dinuhos, jastai `oi mug {0} = 0`",
+            )),
+        ),
+
+        ErrorCode::ArgumentType => (
+            text!(
+                "argument {0} of `{1}` must be a {2}, found a {3}",
+                "`{1}` को आर्गुमेन्ट {0} {2} हुनुपर्छ, तर {3} भेटियो",
+                "`{1}` ko argument {0} {2} hunuparchha, tara {3} bhetiyo",
+            ),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            None,
+        ),
+        ErrorCode::NativeFailed => (
+            text!("`{0}` failed: {1}", "`{0}` असफल भयो: {1}", "`{0}` asafal bhayo: {1}"),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            None,
+        ),
+        ErrorCode::InvalidNativeIndex => (
+            text!(
+                "native function slot {0} does not exist",
+                "नेटिभ फङ्सन स्थान {0} अवस्थित छैन",
+                "native function sthan {0} chhaina",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::ResourceLimit => (
+            text!(
+                "the program went over its limit of {0} {1}",
+                "प्रोग्रामले {0} {1} को सीमा नाघ्यो",
+                "program le {0} {1} ko sima naghyo",
+            ),
+            Some(text!("stopped here", "यहाँ रोकियो", "yaha rokiyo")),
+            None,
+        ),
+        ErrorCode::PermissionDenied => (
+            text!(
+                "permission denied: this needs the `{0}` capability",
+                "अनुमति छैन: यसका लागि `{0}` क्षमता चाहिन्छ",
+                "anumati chhaina: yesko lagi `{0}` kshamata chahinchha",
+            ),
+            Some(text!("not allowed", "अनुमति छैन", "anumati chhaina")),
+            Some(text!("grant it with `{1}`", "`{1}` ले अनुमति दिनुहोस्", "`{1}` le anumati dinuhos")),
+        ),
+        ErrorCode::NotANumber => (
+            text!("\"{0}\" is not a number", "\"{0}\" संख्या होइन", "\"{0}\" sankhya hoina"),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            Some(text!(
+                "`sankhya` reads whole numbers such as \"42\" or \"-7\"",
+                "`sankhya` ले \"42\" वा \"-7\" जस्ता पूर्ण संख्या पढ्छ",
+                "`sankhya` le \"42\" wa \"-7\" jasta purna sankhya padhchha",
+            )),
+        ),
+        ErrorCode::FileReadFailed => (
+            text!("could not read `{0}`: {1}", "`{0}` पढ्न सकिएन: {1}", "`{0}` padhna sakiyena: {1}"),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            None,
+        ),
+        ErrorCode::FileWriteFailed => (
+            text!("could not write `{0}`: {1}", "`{0}` लेख्न सकिएन: {1}", "`{0}` lekhna sakiyena: {1}"),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            None,
+        ),
+        ErrorCode::EmptyRange => (
+            text!(
+                "`sanyog` needs a number above 0, found {0}",
+                "`sanyog` लाई 0 भन्दा ठूलो संख्या चाहिन्छ, तर {0} भेटियो",
+                "`sanyog` lai 0 bhanda thulo sankhya chahinchha, tara {0} bhetiyo",
+            ),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            Some(text!(
+                "`sanyog n` picks a number from 0 to n - 1",
+                "`sanyog n` ले 0 देखि n - 1 सम्मको संख्या छान्छ",
+                "`sanyog n` le 0 dekhi n - 1 samma ko sankhya chhanchha",
+            )),
+        ),
+        ErrorCode::ClockBeforeEpoch => (
+            text!(
+                "the system clock is set before 1970",
+                "प्रणालीको घडी 1970 भन्दा अघि मिलाइएको छ",
+                "pranali ko ghadi 1970 bhanda aghi milaiyeko chha",
+            ),
+            Some(text!("in this call", "यो कलमा", "yo call ma")),
+            Some(text!("set the system clock", "प्रणालीको घडी मिलाउनुहोस्", "pranali ko ghadi milaunuhos")),
+        ),
+
+        ErrorCode::NotBytecode => (
+            text!(
+                "not a LyangLang bytecode file",
+                "यो LyangLang बाइटकोड फाइल होइन",
+                "yo LyangLang bytecode file hoina",
+            ),
+            None,
+            Some(text!(
+                "create one with `lyangpiler build main.nbh`",
+                "`lyangpiler build main.nbh` ले बनाउनुहोस्",
+                "`lyangpiler build main.nbh` le banaunuhos",
+            )),
+        ),
+        ErrorCode::UnsupportedBytecodeVersion => (
+            text!(
+                "bytecode format version {0} is not supported; this lyangpiler reads version {1}",
+                "बाइटकोड ढाँचा संस्करण {0} समर्थित छैन; यो lyangpiler ले संस्करण {1} पढ्छ",
+                "bytecode dhaancha sanskaran {0} samarthit chhaina; yo lyangpiler le sanskaran {1} padhchha",
+            ),
+            None,
+            Some(text!(
+                "build the file again from its source",
+                "फाइललाई यसको स्रोतबाट फेरि build गर्नुहोस्",
+                "file lai yasko srot bata feri build garnuhos",
+            )),
+        ),
+        ErrorCode::CorruptBytecode => (
+            text!(
+                "the bytecode file's {1} is damaged: {0}",
+                "बाइटकोड फाइलको {1} बिग्रिएको छ: {0}",
+                "bytecode file ko {1} bigriyeko chha: {0}",
+            ),
+            None,
+            Some(text!(
+                "build the file again from its source",
+                "फाइललाई यसको स्रोतबाट फेरि build गर्नुहोस्",
+                "file lai yasko srot bata feri build garnuhos",
+            )),
+        ),
+        ErrorCode::InvalidJumpTarget => (
+            text!(
+                "instruction {0} jumps to {1}, which is outside the program",
+                "निर्देशन {0} ले {1} मा जम्प गर्छ, जुन प्रोग्रामभन्दा बाहिर छ",
+                "nirdeshan {0} le {1} ma jump garchha, jun program bhanda bahira chha",
+            ),
+            None,
+            None,
+        ),
+        ErrorCode::StackMismatch => (
+            text!(
+                "the stack depth at instruction {0} is {1} on one path but {2} on another",
+                "निर्देशन {0} मा स्ट्याकको गहिराइ एउटा बाटोमा {1} तर अर्कोमा {2} छ",
+                "nirdeshan {0} ma stack ko gahirai euta bato ma {1} tara arko ma {2} chha",
+            ),
+            None,
+            None,
+        ),
+
+        ErrorCode::UnusedVariable => (
+            text!(
+                "`{0}` is given a value but never used",
+                "`{0}` लाई मान दिइयो तर कहिल्यै प्रयोग गरिएन",
+                "`{0}` lai maan diiyo tara kahilyai prayog gariyena",
+            ),
+            Some(text!("never read", "कहिल्यै पढिएन", "kahilyai padhiyena")),
+            Some(text!(
+                "remove it, or use it, e.g. with `bol mug {0}`",
+                "यसलाई हटाउनुहोस्, वा प्रयोग गर्नुहोस्, जस्तै `bol mug {0}` ले",
+                "yeslai hataunuhos, wa prayog garnuhos, jastai `bol mug {0}` le",
+            )),
+        ),
+        ErrorCode::UnreadAssignment => (
+            text!(
+                "the value given to `{0}` here is never read",
+                "यहाँ `{0}` लाई दिइएको मान कहिल्यै पढिँदैन",
+                "yaha `{0}` lai diiyeko maan kahilyai padhidaina",
+            ),
+            Some(text!("this value is never used", "यो मान कहिल्यै प्रयोग हुँदैन", "yo maan kahilyai prayog hudaina")),
+            Some(text!(
+                "remove this statement, or read `{0}` before giving it another value",
+                "यो वाक्य हटाउनुहोस्, वा `{0}` लाई अर्को मान दिनुअघि पढ्नुहोस्",
+                "yo vakya hataunuhos, wa `{0}` lai arko maan dinu aghi padhnuhos",
+            )),
+        ),
+        ErrorCode::ShadowedDeclaration => (
+            text!(
+                "`{0}` is declared again inside a `yedi` block",
+                "`{0}` लाई `yedi` ब्लकभित्र फेरि घोषणा गरियो",
+                "`{0}` lai `yedi` block bhitra feri ghoshana gariyo",
+            ),
+            Some(text!(
+                "this changes the `{0}` from outside the block",
+                "यसले ब्लकबाहिरको `{0}` नै बदल्छ",
+                "yesle block bahira ko `{0}` nai badalchha",
+            )),
+            Some(text!(
+                "blocks do not make new variables; use another name to keep the outer `{0}`",
+                "ब्लकले नयाँ चर बनाउँदैन; बाहिरको `{0}` राख्न अर्को नाम प्रयोग गर्नुहोस्",
+                "block le naya char banaudaina; bahira ko `{0}` rakhna arko naam prayog garnuhos",
+            )),
+        ),
+        ErrorCode::NumberComparedToString => (
+            text!(
+                "`{0}` holds a number but is compared to the string {1}",
+                "`{0}` मा संख्या छ तर स्ट्रिङ {1} सँग तुलना गरियो",
+                "`{0}` ma sankhya chha tara string {1} sanga tulana gariyo",
+            ),
+            Some(text!(
+                "always stops the program",
+                "यसले सधैं प्रोग्राम रोक्छ",
+                "yesle sadhai program rokchha",
+            )),
+            Some(text!(
+                "compare a string copy instead, e.g. `oi mug {0}_text = \"\" + {0}`",
+                "बरु स्ट्रिङ प्रतिलिपि तुलना गर्नुहोस्, जस्तै `oi mug {0}_text = \"\" + {0}`",
+                "baru string pratilipi tulana garnuhos, jastai `oi mug {0}_text = \"\" + {0}`",
+            )),
+        ),
+        ErrorCode::UnreachableCode => (
+            text!("unreachable code", "कहिल्यै नपुगिने कोड", "kahilyai napugine code"),
+            Some(text!("never runs", "कहिल्यै चल्दैन", "kahilyai chaldaina")),
+            Some(text!(
+                "remove it, or fix the statement that stops the program",
+                "यसलाई हटाउनुहोस्, वा प्रोग्राम रोक्ने वाक्य सच्याउनुहोस्",
+                "yeslai hataunuhos, wa program rokne vakya sachyaunuhos",
+            )),
+        ),
+        ErrorCode::EmptyYedi => (
+            text!("empty `yedi` block", "खाली `yedi` ब्लक", "khali `yedi` block"),
+            Some(text!("does nothing", "केही गर्दैन", "kehi gardaina")),
+            Some(text!(
+                "remove the block, or add statements after `bhane`",
+                "ब्लक हटाउनुहोस्, वा `bhane` पछि वाक्य थप्नुहोस्",
+                "block hataunuhos, wa `bhane` pachhi vakya thapnuhos",
+            )),
+        ),
+
+        ErrorCode::FileRead => (
+            text!("could not read `{0}`: {1}", "`{0}` पढ्न सकिएन: {1}", "`{0}` padhna sakiyena: {1}"),
+            None,
+            None,
+        ),
+        ErrorCode::ProjectExists => (
+            text!("directory `{0}` already exists", "फोल्डर `{0}` पहिले नै छ", "folder `{0}` pahile nai chha"),
+            None,
+            Some(text!(
+                "choose another project name",
+                "अर्को परियोजनाको नाम छान्नुहोस्",
+                "arko pariyojana ko naam chhannuhos",
+            )),
+        ),
+        ErrorCode::NoInputFile => (
+            text!("no input file specified", "कुनै इनपुट फाइल दिइएको छैन", "kunai input file diiyeko chhaina"),
+            None,
+            Some(text!(
+                "run a program with `lyangpiler run main.nbh --allow-stdin --allow-stdout`",
+                "`lyangpiler run main.nbh --allow-stdin --allow-stdout` ले प्रोग्राम चलाउनुहोस्",
+                "`lyangpiler run main.nbh --allow-stdin --allow-stdout` le program chalaunuhos",
+            )),
+        ),
+        ErrorCode::FileWrite => (
+            text!("could not write `{0}`: {1}", "`{0}` लेख्न सकिएन: {1}", "`{0}` lekhna sakiyena: {1}"),
+            None,
+            None,
+        ),
+        ErrorCode::NotFormatted => (
+            text!("`{0}` is not formatted", "`{0}` फर्म्याट गरिएको छैन", "`{0}` format gariyeko chhaina"),
+            None,
+            Some(text!(
+                "run `lyangpiler fmt {0}` to format it",
+                "फर्म्याट गर्न `lyangpiler fmt {0}` चलाउनुहोस्",
+                "format garna `lyangpiler fmt {0}` chalaunuhos",
+            )),
+        ),
+        ErrorCode::InvalidLintConfig => (
+            text!(
+                "invalid lint settings in `{0}`: {1}",
+                "`{0}` मा अमान्य lint सेटिङ: {1}",
+                "`{0}` ma amanya lint setting: {1}",
+            ),
+            None,
+            Some(text!(
+                "set each rule under `\"lint\"` to `\"allow\"`, `\"warning\"` or `\"error\"`",
+                "`\"lint\"` अन्तर्गत हरेक नियमलाई `\"allow\"`, `\"warning\"` वा `\"error\"` राख्नुहोस्",
+                "`\"lint\"` antargat harek niyam lai `\"allow\"`, `\"warning\"` wa `\"error\"` rakhnuhos",
+            )),
+        ),
+    };
+    Entry { message, label, help }
+}
+
+pub(crate) fn message(code: ErrorCode, lang: Lang) -> &'static str {
+    entry(code).message.get(lang)
+}
+
+pub(crate) fn label(code: ErrorCode, lang: Lang) -> Option<&'static str> {
+    entry(code).label.map(|text| text.get(lang))
+}
+
+pub(crate) fn help(code: ErrorCode, lang: Lang) -> Option<&'static str> {
+    entry(code).help.map(|text| text.get(lang))
+}
+
+pub(crate) fn note(note: Note, lang: Lang) -> &'static str {
+    let text = match note {
+        Note::ConditionStartsHere => {
+            text!("condition starts here", "सर्त यहाँ सुरु हुन्छ", "sarta yaha suru hunchha")
+        }
+        Note::NestedCommentOpened => text!(
+            "this nested comment also needs its own `*/`",
+            "यो भित्री टिप्पणीलाई पनि आफ्नै `*/` चाहिन्छ",
+            "yo bhitri tippani lai pani aaphnai `*/` chahinchha",
+        ),
+        Note::InternalError => text!(
+            "this is a bug in Lyangpiler or a corrupted bytecode program",
+            "यो Lyangpiler को त्रुटि वा बिग्रिएको बाइटकोड प्रोग्राम हो",
+            "yo Lyangpiler ko truti wa bigriyeko bytecode program ho",
+        ),
+        Note::ProgramStopsHere => text!(
+            "the program always stops with an error here",
+            "प्रोग्राम यहाँ सधैं त्रुटिसहित रोकिन्छ",
+            "program yaha sadhai truti sahit rokinchha",
+        ),
+        Note::TypeGivenHere => text!(
+            "its type comes from here",
+            "यसको प्रकार यहाँबाट आउँछ",
+            "yesko prakar yaha bata aauchha",
+        ),
+        Note::ConvertToNumber => text!(
+            "to read a number, convert the text: `mug sankhya {0} lai {0}_sankhya`",
+            "संख्या पढ्न पाठलाई बदल्नुहोस्: `mug sankhya {0} lai {0}_sankhya`",
+            "sankhya padhna text lai badalnuhos: `mug sankhya {0} lai {0}_sankhya`",
+        ),
+    };
+    text.get(lang)
+}
+
+pub(crate) fn word(word: Word, lang: Lang) -> &'static str {
+    let text = match word {
+        Word::Number => text!("number", "संख्या", "sankhya"),
+        Word::String => text!("string", "स्ट्रिङ", "string"),
+        Word::Boolean => text!("boolean", "बुलियन", "boolean"),
+        Word::AnyType => text!("any", "जुनसुकै", "junsukai"),
+        Word::Steps => text!("steps", "चरण", "charan"),
+        Word::Seconds => text!("seconds", "सेकेन्ड", "second"),
+        Word::StackSlots => text!("stack slots", "स्ट्याक स्थान", "stack sthan"),
+        Word::NestedBlocks => text!("nested blocks", "भित्री ब्लक", "bhitri block"),
+        Word::Bytes => text!("bytes of text", "बाइट पाठ", "byte text"),
+        Word::BytecodeVersion => text!("version", "संस्करण", "sanskaran"),
+        Word::StringPool => text!("string pool", "स्ट्रिङ सूची", "string suchi"),
+        Word::VariableTable => text!("variable table", "चर तालिका", "char talika"),
+        Word::NativeTable => text!("native function table", "नेटिभ फङ्सन तालिका", "native function talika"),
+        Word::InstructionList => text!("instruction list", "निर्देशन सूची", "nirdeshan suchi"),
+        Word::LineTable => text!("line table", "लाइन तालिका", "line talika"),
+        Word::EndsInside => text!("the file ends inside it", "फाइल यसभित्रै सकिन्छ", "file yes bhitrai sakinchha"),
+        Word::NumberTooLarge => text!(
+            "a number in it is too large",
+            "यसमा भएको एउटा संख्या धेरै ठूलो छ",
+            "yesma bhayeko euta sankhya dherai thulo chha",
+        ),
+        Word::LongerThanFile => text!("it is longer than the file", "यो फाइलभन्दा लामो छ", "yo file bhanda lamo chha"),
+        Word::InvalidUtf8 => text!("it contains invalid UTF-8", "यसमा अमान्य UTF-8 छ", "yesma amanya UTF-8 chha"),
+        Word::UnknownOpcode => text!("it has an unknown opcode", "यसमा अज्ञात opcode छ", "yesma agyat opcode chha"),
+        Word::WrongInstructionCount => text!(
+            "it covers a different number of instructions",
+            "यसले फरक संख्याका निर्देशन समेट्छ",
+            "yesle pharak sankhya ka nirdeshan sametchha",
+        ),
+        Word::DataAfter => text!("more data follows it", "यसपछि थप डाटा छ", "yespachhi thap data chha"),
+    };
+    text.get(lang)
+}
+
+/// Headings used when rendering a diagnostic.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Heading {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+pub(crate) fn heading(heading: Heading, lang: Lang) -> &'static str {
+    let text = match heading {
+        Heading::Error => text!("error", "त्रुटि", "truti"),
+        Heading::Warning => text!("warning", "चेतावनी", "chetawani"),
+        Heading::Note => text!("note", "नोट", "note"),
+        Heading::Help => text!("help", "सहायता", "sahayata"),
+    };
+    text.get(lang)
+}
+
+/// Summary printed after several errors were reported at once.
+pub fn error_count(count: usize, lang: Lang) -> String {
+    text!("found {0} errors", "{0} वटा त्रुटि भेटिए", "{0} wata truti bhetiye")
+        .get(lang)
+        .replace("{0}", &count.to_string())
+}
+
+/// Summary printed after `lyangpiler lint` reports problems.
+pub fn lint_count(errors: usize, warnings: usize, lang: Lang) -> String {
+    text!(
+        "found {0} errors and {1} warnings",
+        "{0} वटा त्रुटि र {1} वटा चेतावनी भेटिए",
+        "{0} wata truti ra {1} wata chetawani bhetiye",
+    )
+    .get(lang)
+    .replace("{0}", &errors.to_string())
+    .replace("{1}", &warnings.to_string())
+}
+
+/// Hint printed when some reported problems have automatic fixes.
+pub fn fixable_count(count: usize, lang: Lang) -> String {
+    text!(
+        "{0} of them can be fixed with `lyangpiler lint --fix`",
+        "तीमध्ये {0} वटा `lyangpiler lint --fix` ले सच्याउन सकिन्छ",
+        "timadhye {0} wata `lyangpiler lint --fix` le sachyauna sakinchha",
+    )
+    .get(lang)
+    .replace("{0}", &count.to_string())
+}
+
+/// Editor hover text for a built-in function, below its signature.
+pub(crate) fn builtin_function(lang: Lang) -> &'static str {
+    text!("Built-in function", "भित्री फङ्सन", "bhitri function").get(lang)
+}
+
+/// Editor hover text introducing the statement that first gives a variable a value.
+pub(crate) fn first_given_on_line(line: usize, lang: Lang) -> String {
+    text!(
+        "First given a value on line {0}:",
+        "पहिलो पटक लाइन {0} मा मान दिइयो:",
+        "pahilo patak line {0} ma maan diiyo:",
+    )
+    .get(lang)
+    .replace("{0}", &line.to_string())
+}
+
+/// Editor hover text for a variable that no statement gives a value.
+pub(crate) fn never_given_a_value(name: &str, lang: Lang) -> String {
+    text!(
+        "`{0}` is never given a value",
+        "`{0}` लाई कहिल्यै मान दिइँदैन",
+        "`{0}` lai kahilyai maan diidaina",
+    )
+    .get(lang)
+    .replace("{0}", name)
+}
+
+/// Types a variable can hold, e.g. "number or string".
+pub(crate) fn one_of(names: &[&str], lang: Lang) -> String {
+    names.join(text!(" or ", " वा ", " wa ").get(lang))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LANGS: [Lang; 3] = [Lang::En, Lang::Ne, Lang::NeRom];
+
+    /// Sorted `{n}` placeholders used by a template.
+    fn placeholders(template: &str) -> Vec<String> {
+        let mut found: Vec<String> = (0..10)
+            .map(|i| format!("{{{}}}", i))
+            .filter(|p| template.contains(p.as_str()))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn every_code_is_translated_with_matching_placeholders() {
+        for code in ErrorCode::ALL {
+            let entry = entry(code);
+            let texts = [Some(entry.message), entry.label, entry.help];
+            for text in texts.iter().flatten() {
+                for lang in LANGS {
+                    assert!(!text.get(lang).is_empty(), "{} has no {} text", code, lang);
+                    assert_eq!(
+                        placeholders(text.get(lang)),
+                        placeholders(text.en),
+                        "{} {} text uses different placeholders",
+                        code,
+                        lang
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_language_names() {
+        assert_eq!("ne".parse::<Lang>(), Ok(Lang::Ne));
+        assert_eq!("NE-ROM".parse::<Lang>(), Ok(Lang::NeRom));
+        assert_eq!("en".parse::<Lang>(), Ok(Lang::En));
+        assert!("fr".parse::<Lang>().is_err());
+    }
+}
@@ -0,0 +1,333 @@
+//! Functions written in Rust that scripts can call.
+//!
+//! A script calls a native function with `mug <name> a, 5, "x" lai target`.
+//! The compiler checks that the function exists and gets the right number of
+//! arguments; argument types are checked when the call runs. Both engines
+//! call through [`Natives::call`], so they report the same errors.
+//!
+//! Functions that reach outside the program, such as reading a file, name
+//! the [`Capability`] each call needs, and fail unless it was granted.
+
+use crate::capability::{Capabilities, Capability};
+use crate::diagnostic::{Diagnostic, ErrorCode, Word};
+use crate::value::Value;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The type a native function expects for one of its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Number,
+    String,
+    Boolean,
+    Any,
+}
+
+impl Type {
+    pub fn accepts(self, value: &Value) -> bool {
+        matches!(
+            (self, value),
+            (Type::Any, _)
+                | (Type::Number, Value::Number(_))
+                | (Type::String, Value::String(_))
+                | (Type::Boolean, Value::Boolean(_))
+        )
+    }
+
+    /// The type's name in diagnostics, or `None` for `Any`.
+    pub fn word(self) -> Option<Word> {
+        match self {
+            Type::Number => Some(Word::Number),
+            Type::String => Some(Word::String),
+            Type::Boolean => Some(Word::Boolean),
+            Type::Any => None,
+        }
+    }
+}
+
+/// The Rust side of a native function, which fails with the diagnostic to
+/// report. The diagnostic has no span yet.
+pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, Diagnostic>;
+
+/// The capability a call needs, given its type-checked arguments.
+pub type NeedsFn = fn(&[Value]) -> Capability;
+
+#[derive(Clone)]
+struct Native {
+    params: Vec<Type>,
+    needs: Option<NeedsFn>,
+    function: Rc<NativeFn>,
+}
+
+/// A set of native functions by name.
+#[derive(Clone, Default)]
+pub struct Natives {
+    functions: HashMap<String, Native>,
+}
+
+impl Natives {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The functions every program can use:
+    ///
+    /// - `lambai s`: number of characters in `s`
+    /// - `thulo_akshar s` / `sano_akshar s`: `s` in upper / lower case
+    /// - `sankhya s`: the number written in `s`
+    /// - `shesh a, b`: remainder of `a` divided by `b`
+    ///
+    /// and, when their capability is granted:
+    ///
+    /// - `padh path`: the text of the file at `path` (read)
+    /// - `lekh path, text`: writes `text` to the file at `path` and gives the
+    ///   number of characters written (write)
+    /// - `samay`: seconds since 1 January 1970 (clock)
+    /// - `sanyog n`: a random number from 0 to `n - 1` (random)
+    pub fn stdlib() -> Self {
+        let mut natives = Self::new();
+        natives.define("lambai", &[Type::String], None, |args| {
+            Ok(Value::Number(args[0].to_string().chars().count() as i32))
+        });
+        natives.define("thulo_akshar", &[Type::String], None, |args| {
+            Ok(Value::from(args[0].to_string().to_uppercase()))
+        });
+        natives.define("sano_akshar", &[Type::String], None, |args| {
+            Ok(Value::from(args[0].to_string().to_lowercase()))
+        });
+        natives.define("sankhya", &[Type::String], None, |args| {
+            let text = args[0].to_string();
+            text.trim()
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| Diagnostic::new(ErrorCode::NotANumber).with_arg(text))
+        });
+        natives.define("shesh", &[Type::Number, Type::Number], None, |args| match (&args[0], &args[1]) {
+            (Value::Number(_), Value::Number(0)) => Err(Diagnostic::new(ErrorCode::DivisionByZero)),
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a.wrapping_rem(*b))),
+            _ => unreachable!(),
+        });
+
+        natives.define(
+            "padh",
+            &[Type::String],
+            Some(|args| Capability::Read(path(&args[0]))),
+            |args| {
+                let path = path(&args[0]);
+                std::fs::read_to_string(&path)
+                    .map(Value::from)
+                    .map_err(|e| Diagnostic::new(ErrorCode::FileReadFailed).with_arg(path.display()).with_arg(e))
+            },
+        );
+        natives.define(
+            "lekh",
+            &[Type::String, Type::Any],
+            Some(|args| Capability::Write(path(&args[0]))),
+            |args| {
+                let (path, text) = (path(&args[0]), args[1].to_string());
+                std::fs::write(&path, &text)
+                    .map_err(|e| Diagnostic::new(ErrorCode::FileWriteFailed).with_arg(path.display()).with_arg(e))?;
+                Ok(Value::Number(text.chars().count() as i32))
+            },
+        );
+        natives.define("samay", &[], Some(|_| Capability::Clock), |_| {
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|_| Diagnostic::new(ErrorCode::ClockBeforeEpoch))?
+                .as_secs();
+            i32::try_from(seconds)
+                .map(Value::Number)
+                .map_err(|_| Diagnostic::new(ErrorCode::NumberOverflow).with_arg("samay"))
+        });
+        natives.define("sanyog", &[Type::Number], Some(|_| Capability::Random), |args| match args[0] {
+            Value::Number(n) if n > 0 => {
+                // Each `RandomState` is seeded differently, which is
+                // random enough for scripts
+                let random = RandomState::new().hash_one(());
+                Ok(Value::Number((random % n as u64) as i32))
+            }
+            Value::Number(n) => Err(Diagnostic::new(ErrorCode::EmptyRange).with_arg(n)),
+            _ => unreachable!(),
+        });
+        natives
+    }
+
+    /// Adds or replaces `name`. `function` is only called with as many
+    /// arguments as `params` has, each of the declared type. An `Err` message
+    /// is shown to the script author as the reason the call failed.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        params: &[Type],
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let name = name.into();
+        let function = failing_as(&name, function);
+        self.define(name, params, None, function);
+    }
+
+    /// Like [`Natives::register`], for a function that reaches outside the
+    /// program. `function` is only called if the capability `needs` returns
+    /// for the arguments has been granted.
+    pub fn register_with_capability(
+        &mut self,
+        name: impl Into<String>,
+        params: &[Type],
+        needs: NeedsFn,
+        function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+    ) {
+        let name = name.into();
+        let function = failing_as(&name, function);
+        self.define(name, params, Some(needs), function);
+    }
+
+    fn define(
+        &mut self,
+        name: impl Into<String>,
+        params: &[Type],
+        needs: Option<NeedsFn>,
+        function: impl Fn(&[Value]) -> Result<Value, Diagnostic> + 'static,
+    ) {
+        self.functions.insert(
+            name.into(),
+            Native {
+                params: params.to_vec(),
+                needs,
+                function: Rc::new(function),
+            },
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Every function's name, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The types of `name`'s parameters, if it exists.
+    pub fn params(&self, name: &str) -> Option<&[Type]> {
+        self.functions.get(name).map(|native| native.params.as_slice())
+    }
+
+    /// Checks that `name` exists and takes `arg_count` arguments. The error
+    /// has no span yet.
+    pub fn check_call(&self, name: &str, arg_count: usize) -> Result<(), Diagnostic> {
+        let native = self.lookup(name)?;
+        if native.params.len() != arg_count {
+            return Err(Diagnostic::new(ErrorCode::WrongArgumentCount)
+                .with_arg(name)
+                .with_arg(native.params.len())
+                .with_arg(arg_count));
+        }
+        Ok(())
+    }
+
+    /// Calls `name` after checking its arguments and that `capabilities`
+    /// grants what it needs. The error has no span yet.
+    pub fn call(&self, name: &str, args: Vec<Value>, capabilities: &Capabilities) -> Result<Value, Diagnostic> {
+        self.check_call(name, args.len())?;
+        let native = self.lookup(name)?;
+        for (position, (param, arg)) in native.params.iter().zip(&args).enumerate() {
+            if !param.accepts(arg) {
+                let mut diagnostic = Diagnostic::new(ErrorCode::ArgumentType)
+                    .with_arg(position + 1)
+                    .with_arg(name);
+                if let Some(word) = param.word() {
+                    diagnostic = diagnostic.with_word(word);
+                }
+                return Err(diagnostic.with_word(arg.type_name()));
+            }
+        }
+        if let Some(needs) = native.needs {
+            capabilities.check(&needs(&args))?;
+        }
+        (native.function)(&args)
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Native, Diagnostic> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| Diagnostic::new(ErrorCode::UnknownFunction).with_arg(name))
+    }
+}
+
+/// `function` failing with [`ErrorCode::NativeFailed`] and its reason.
+fn failing_as(
+    name: &str,
+    function: impl Fn(&[Value]) -> Result<Value, String> + 'static,
+) -> impl Fn(&[Value]) -> Result<Value, Diagnostic> + 'static {
+    let name = name.to_string();
+    move |args| function(args).map_err(|reason| Diagnostic::new(ErrorCode::NativeFailed).with_arg(&name).with_arg(reason))
+}
+
+/// The path a script passed as text.
+fn path(value: &Value) -> PathBuf {
+    PathBuf::from(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_arity_and_argument_types() {
+        let natives = Natives::stdlib();
+        let err = natives.check_call("shesh", 1).unwrap_err();
+        assert_eq!(err.message(), "`shesh` takes 2 arguments, found 1");
+        let err = natives.call("lambai", vec![Value::Number(5)], &Capabilities::none()).unwrap_err();
+        assert_eq!(err.message(), "argument 1 of `lambai` must be a string, found a number");
+        let err = natives.check_call("udnu", 0).unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownFunction);
+    }
+
+    #[test]
+    fn host_functions_can_fail() {
+        let natives = Natives::stdlib();
+        assert_eq!(natives.call("sankhya", vec![" 42 ".into()], &Capabilities::none()).unwrap(), Value::Number(42));
+        let err = natives.call("sankhya", vec!["bis".into()], &Capabilities::none()).unwrap_err();
+        assert_eq!(err.message(), "\"bis\" is not a number");
+        let err = natives.call("shesh", vec![Value::Number(7), Value::Number(0)], &Capabilities::none()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::DivisionByZero);
+
+        let mut natives = Natives::new();
+        natives.register("dollar", &[], |_| Err("no exchange rate".to_string()));
+        let err = natives.call("dollar", vec![], &Capabilities::none()).unwrap_err();
+        assert_eq!(err.message(), "`dollar` failed: no exchange rate");
+    }
+
+    #[test]
+    fn reaching_outside_needs_a_capability() {
+        let natives = Natives::stdlib();
+        let err = natives.call("samay", vec![], &Capabilities::default()).unwrap_err();
+        assert_eq!(err.code, ErrorCode::PermissionDenied);
+        assert_eq!(err.message(), "permission denied: this needs the `clock` capability");
+        assert!(matches!(natives.call("samay", vec![], &Capabilities::none().with_clock()), Ok(Value::Number(_))));
+
+        let random = Capabilities::none().with_random();
+        let Value::Number(n) = natives.call("sanyog", vec![Value::Number(6)], &random).unwrap() else {
+            panic!("expected a number");
+        };
+        assert!((0..6).contains(&n));
+        let err = natives.call("sanyog", vec![Value::Number(0)], &random).unwrap_err();
+        assert_eq!(err.message(), "`sanyog` needs a number above 0, found 0");
+
+        let dir = std::env::temp_dir().join(format!("lyangpiler-native-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt").display().to_string();
+        let err = natives.call("lekh", vec![file.as_str().into(), Value::Number(42)], &Capabilities::none()).unwrap_err();
+        assert_eq!(err.message(), format!("permission denied: this needs the `write {}` capability", file));
+        let files = Capabilities::none().with_read(&dir).with_write(&dir);
+        assert_eq!(natives.call("lekh", vec![file.as_str().into(), Value::Number(42)], &files).unwrap(), Value::Number(2));
+        assert_eq!(natives.call("padh", vec![file.as_str().into()], &files).unwrap(), Value::from("42"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
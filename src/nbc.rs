@@ -0,0 +1,331 @@
+//! The `.nbc` file format for compiled programs.
+//!
+//! A file is the magic bytes `NBC\0`, a little-endian `u16` format version,
+//! then these sections in order:
+//!
+//! 1. the string pool,
+//! 2. the variable table,
+//! 3. the names of the native functions the program calls,
+//! 4. the instructions, each an opcode byte followed by its operands,
+//! 5. the line table: runs of consecutive instructions that share a span.
+//!
+//! Lists and strings are prefixed with their length. All integers are
+//! LEB128 varints; `PushNumber` operands are zigzag-encoded first.
+//!
+//! Decoding checks the structure of the file but not whether the program
+//! makes sense; see [`crate::verifier`] for that.
+
+use crate::bytecode::{BytecodeProgram, Instruction, Opcode};
+use crate::diagnostic::{Diagnostic, ErrorCode, Word};
+use crate::span::Span;
+
+pub const MAGIC: [u8; 4] = *b"NBC\0";
+
+/// Bumped whenever the layout changes; older and newer files are rejected.
+pub const VERSION: u16 = 1;
+
+/// Serializes `program`.
+pub fn encode(program: &BytecodeProgram) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+
+    for table in [&program.string_pool, &program.variable_names, &program.native_names] {
+        let table: &[String] = table;
+        write_varint(&mut out, table.len() as u64);
+        for string in table {
+            write_varint(&mut out, string.len() as u64);
+            out.extend_from_slice(string.as_bytes());
+        }
+    }
+
+    write_varint(&mut out, program.instructions.len() as u64);
+    for instruction in &program.instructions {
+        write_opcode(&mut out, instruction.opcode);
+    }
+
+    let runs = span_runs(&program.instructions);
+    write_varint(&mut out, runs.len() as u64);
+    for (count, span) in runs {
+        for value in [count, span.start, span.end, span.line, span.column] {
+            write_varint(&mut out, value as u64);
+        }
+    }
+    out
+}
+
+/// Reads a program written by [`encode`]. The error has no span.
+pub fn decode(bytes: &[u8]) -> Result<BytecodeProgram, Diagnostic> {
+    if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+        return Err(Diagnostic::new(ErrorCode::NotBytecode));
+    }
+    let mut reader = Reader { bytes, position: MAGIC.len() };
+    let version = u16::from_le_bytes([reader.byte(Word::BytecodeVersion)?, reader.byte(Word::BytecodeVersion)?]);
+    if version != VERSION {
+        return Err(Diagnostic::new(ErrorCode::UnsupportedBytecodeVersion)
+            .with_arg(version)
+            .with_arg(VERSION));
+    }
+
+    let mut program = BytecodeProgram::new();
+    program.string_pool = reader.strings(Word::StringPool)?.into();
+    program.variable_names = reader.strings(Word::VariableTable)?.into();
+    program.native_names = reader.strings(Word::NativeTable)?.into();
+
+    let count = reader.length(Word::InstructionList)?;
+    let mut opcodes = Vec::with_capacity(count);
+    for _ in 0..count {
+        opcodes.push(reader.opcode()?);
+    }
+
+    let runs = reader.length(Word::LineTable)?;
+    let mut opcodes = opcodes.into_iter();
+    for _ in 0..runs {
+        let count = reader.usize(Word::LineTable)?;
+        let span = Span::new(
+            reader.usize(Word::LineTable)?,
+            reader.usize(Word::LineTable)?,
+            reader.usize(Word::LineTable)?,
+            reader.usize(Word::LineTable)?,
+        );
+        for _ in 0..count {
+            let opcode = opcodes.next().ok_or_else(|| corrupt(Word::WrongInstructionCount, Word::LineTable))?;
+            program.instructions.push(Instruction::new(opcode, span));
+        }
+    }
+    if opcodes.next().is_some() {
+        return Err(corrupt(Word::WrongInstructionCount, Word::LineTable));
+    }
+    if reader.position != bytes.len() {
+        return Err(corrupt(Word::DataAfter, Word::LineTable));
+    }
+    Ok(program)
+}
+
+/// Groups consecutive instructions with the same span.
+fn span_runs(instructions: &[Instruction]) -> Vec<(usize, Span)> {
+    let mut runs: Vec<(usize, Span)> = Vec::new();
+    for instruction in instructions {
+        match runs.last_mut() {
+            Some((count, span)) if *span == instruction.span => *count += 1,
+            _ => runs.push((1, instruction.span)),
+        }
+    }
+    runs
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_opcode(out: &mut Vec<u8>, opcode: Opcode) {
+    let (tag, operands): (u8, &[usize]) = match opcode {
+        Opcode::PushNumber(n) => {
+            out.push(0);
+            write_varint(out, ((n << 1) ^ (n >> 31)) as u32 as u64);
+            return;
+        }
+        Opcode::PushString(index) => (1, &[index]),
+        Opcode::PushVariable(index) => (2, &[index]),
+        Opcode::Pop => (3, &[]),
+        Opcode::StoreVariable(index) => (4, &[index]),
+        Opcode::LoadVariable(index) => (5, &[index]),
+        Opcode::Add => (6, &[]),
+        Opcode::Subtract => (7, &[]),
+        Opcode::Multiply => (8, &[]),
+        Opcode::Divide => (9, &[]),
+        Opcode::Concat => (10, &[]),
+        Opcode::Print => (11, &[]),
+        Opcode::Input => (12, &[]),
+        Opcode::JumpIfTrue(address) => (13, &[address]),
+        Opcode::JumpIfFalse(address) => (14, &[address]),
+        Opcode::Jump(address) => (15, &[address]),
+        Opcode::CallNative(index, argc) => (16, &[index, argc]),
+        Opcode::Equal => (17, &[]),
+        Opcode::NotEqual => (18, &[]),
+        Opcode::Return => (19, &[]),
+        Opcode::Halt => (20, &[]),
+    };
+    out.push(tag);
+    for &operand in operands {
+        write_varint(out, operand as u64);
+    }
+}
+
+/// An error saying how `part` of the file is damaged.
+fn corrupt(damage: Word, part: Word) -> Diagnostic {
+    Diagnostic::new(ErrorCode::CorruptBytecode).with_word(damage).with_word(part)
+}
+
+/// Reads values from a file, failing instead of reading past its end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self, what: Word) -> Result<u8, Diagnostic> {
+        let byte = *self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| corrupt(Word::EndsInside, what))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self, what: Word) -> Result<u64, Diagnostic> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte(what)?;
+            let bits = u64::from(byte & 0x7f);
+            if shift == 63 && bits > 1 {
+                break;
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(corrupt(Word::NumberTooLarge, what))
+    }
+
+    fn usize(&mut self, what: Word) -> Result<usize, Diagnostic> {
+        usize::try_from(self.varint(what)?).map_err(|_| corrupt(Word::NumberTooLarge, what))
+    }
+
+    /// A count of items that each take at least one byte, so a damaged count
+    /// cannot make us allocate more than the file could hold.
+    fn length(&mut self, what: Word) -> Result<usize, Diagnostic> {
+        let length = self.usize(what)?;
+        if length > self.bytes.len() - self.position {
+            return Err(corrupt(Word::LongerThanFile, what));
+        }
+        Ok(length)
+    }
+
+    fn strings(&mut self, what: Word) -> Result<Vec<String>, Diagnostic> {
+        let count = self.length(what)?;
+        let mut strings = Vec::with_capacity(count);
+        for _ in 0..count {
+            let length = self.length(what)?;
+            let bytes = &self.bytes[self.position..self.position + length];
+            self.position += length;
+            let string = std::str::from_utf8(bytes)
+                .map_err(|_| corrupt(Word::InvalidUtf8, what))?;
+            strings.push(string.to_string());
+        }
+        Ok(strings)
+    }
+
+    fn opcode(&mut self) -> Result<Opcode, Diagnostic> {
+        const WHAT: Word = Word::InstructionList;
+        let opcode = match self.byte(WHAT)? {
+            0 => {
+                let encoded = u32::try_from(self.varint(WHAT)?)
+                    .map_err(|_| corrupt(Word::NumberTooLarge, WHAT))?;
+                Opcode::PushNumber((encoded >> 1) as i32 ^ -((encoded & 1) as i32))
+            }
+            1 => Opcode::PushString(self.usize(WHAT)?),
+            2 => Opcode::PushVariable(self.usize(WHAT)?),
+            3 => Opcode::Pop,
+            4 => Opcode::StoreVariable(self.usize(WHAT)?),
+            5 => Opcode::LoadVariable(self.usize(WHAT)?),
+            6 => Opcode::Add,
+            7 => Opcode::Subtract,
+            8 => Opcode::Multiply,
+            9 => Opcode::Divide,
+            10 => Opcode::Concat,
+            11 => Opcode::Print,
+            12 => Opcode::Input,
+            13 => Opcode::JumpIfTrue(self.usize(WHAT)?),
+            14 => Opcode::JumpIfFalse(self.usize(WHAT)?),
+            15 => Opcode::Jump(self.usize(WHAT)?),
+            16 => Opcode::CallNative(self.usize(WHAT)?, self.usize(WHAT)?),
+            17 => Opcode::Equal,
+            18 => Opcode::NotEqual,
+            19 => Opcode::Return,
+            20 => Opcode::Halt,
+            _ => return Err(corrupt(Word::UnknownOpcode, WHAT)),
+        };
+        Ok(opcode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::messages::Lang;
+    use crate::parser::Parser;
+
+    fn example() -> BytecodeProgram {
+        let tokens = Lexer::new(include_str!("../example.nbh")).tokenize().unwrap();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Compiler::new().compile(statements).unwrap()
+    }
+
+    #[test]
+    fn round_trips_a_program() {
+        let mut program = example();
+        program.add_instruction(Opcode::PushNumber(i32::MIN), Span::default());
+        program.add_instruction(Opcode::PushNumber(-1), Span::default());
+        program.add_instruction(Opcode::PushNumber(i32::MAX), Span::default());
+        assert_eq!(decode(&encode(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn rejects_other_files_and_versions() {
+        assert_eq!(decode(b"bol mug \"hi\"").unwrap_err().code, ErrorCode::NotBytecode);
+        assert_eq!(decode(b"").unwrap_err().code, ErrorCode::NotBytecode);
+
+        let mut bytes = encode(&example());
+        bytes[4] = 9;
+        let err = decode(&bytes).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "bytecode format version 9 is not supported; this lyangpiler reads version 1"
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_and_damaged_files_without_panicking() {
+        let bytes = encode(&example());
+        for length in MAGIC.len()..bytes.len() {
+            let err = decode(&bytes[..length]).unwrap_err();
+            assert_eq!(err.code, ErrorCode::CorruptBytecode, "truncated to {} bytes", length);
+        }
+
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert_eq!(decode(&extended).unwrap_err().code, ErrorCode::CorruptBytecode);
+
+        // Any single damaged byte must be either rejected or still decode
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..5000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let mut damaged = bytes.clone();
+            let index = MAGIC.len() + 2 + (state as usize) % (bytes.len() - MAGIC.len() - 2);
+            damaged[index] = (state >> 32) as u8;
+            let _ = decode(&damaged);
+        }
+
+        let huge = [&MAGIC[..], &VERSION.to_le_bytes(), &[0xff; 12]].concat();
+        let err = decode(&huge).unwrap_err();
+        assert_eq!(err.message(), "the bytecode file's string pool is damaged: a number in it is too large");
+        assert_eq!(
+            err.message_in(Lang::NeRom),
+            "bytecode file ko string suchi bigriyeko chha: yesma bhayeko euta sankhya dherai thulo chha"
+        );
+        assert_eq!(
+            decode(&extended).unwrap_err().message(),
+            "the bytecode file's line table is damaged: more data follows it"
+        );
+    }
+}
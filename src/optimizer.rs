@@ -0,0 +1,384 @@
+//! Bytecode optimizations enabled with `-O`.
+//!
+//! Each pass rewrites the program without changing what it prints, reads or
+//! reports; the passes run until none of them finds anything more to do:
+//!
+//! - constant folding: arithmetic and concatenation of two literals, and
+//!   comparisons of two literals followed by a conditional jump
+//! - merging `+ "a" + "b"` into `+ "ab"`
+//! - jump threading: a jump to a `Jump` goes straight to its target, a jump
+//!   to `Halt` becomes `Halt`, and a jump to the next instruction is removed
+//! - removing a `StoreVariable` immediately followed by a `LoadVariable` of
+//!   the same variable when nothing else reads it
+//! - removing instructions that can never run
+//!
+//! Folding never hides an error: an operation that would fail is left for
+//! the VM to report. Variables removed by the store/load pass no longer hold
+//! their value after the program ends, so programs whose variables are read
+//! afterwards (the REPL, [`crate::Engine`]) are not optimized.
+
+use crate::bytecode::{BytecodeProgram, Instruction, Opcode};
+use crate::value::{BinaryOp, Value};
+
+/// Optimizes a program produced by the compiler.
+pub fn optimize(mut program: BytecodeProgram) -> BytecodeProgram {
+    loop {
+        let mut changed = fold_constants(&mut program);
+        changed |= merge_concatenations(&mut program);
+        changed |= thread_jumps(&mut program);
+        changed |= remove_store_load_pairs(&mut program);
+        changed |= remove_unreachable(&mut program);
+        if !changed {
+            break;
+        }
+    }
+    remove_unused_strings(&mut program);
+    program
+}
+
+/// Which instructions some jump lands on. Code is only rewritten across
+/// instruction boundaries that no jump can enter.
+fn jump_targets(program: &BytecodeProgram) -> Vec<bool> {
+    let mut targets = vec![false; program.instructions.len() + 1];
+    for instruction in &program.instructions {
+        if let Some(target) = instruction.opcode.jump_target().filter(|&t| t < targets.len()) {
+            targets[target] = true;
+        }
+    }
+    targets
+}
+
+/// Deletes the instructions marked in `removed`. Jumps to a deleted
+/// instruction go to the next one that is kept, so only instructions that
+/// together have no effect may be deleted where a jump lands.
+fn remove(program: &mut BytecodeProgram, removed: &[bool]) -> bool {
+    if !removed.contains(&true) {
+        return false;
+    }
+    // new_offsets[i] is the number of instructions kept before `i`
+    let mut new_offsets = Vec::with_capacity(removed.len() + 1);
+    let mut kept = 0;
+    for &gone in removed {
+        new_offsets.push(kept);
+        if !gone {
+            kept += 1;
+        }
+    }
+    new_offsets.push(kept);
+
+    let instructions = std::mem::take(&mut program.instructions);
+    program.instructions = instructions
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, &gone)| !gone)
+        .map(|(mut instruction, _)| {
+            if let Some(target) = instruction.opcode.jump_target() {
+                let target = new_offsets.get(target).copied().unwrap_or(target);
+                instruction.opcode = instruction.opcode.with_jump_target(target);
+            }
+            instruction
+        })
+        .collect();
+    true
+}
+
+/// The value a push instruction puts on the stack.
+fn constant(program: &BytecodeProgram, opcode: Opcode) -> Option<Value> {
+    match opcode {
+        Opcode::PushNumber(n) => Some(Value::Number(n)),
+        Opcode::PushString(index) => program.string_pool.get(index).map(|s| Value::from(s.as_str())),
+        _ => None,
+    }
+}
+
+/// An instruction that pushes `value`, if there is one.
+fn push(program: &mut BytecodeProgram, value: Value) -> Option<Opcode> {
+    match value {
+        Value::Number(n) => Some(Opcode::PushNumber(n)),
+        Value::String(s) => Some(Opcode::PushString(program.add_string(s.to_string()))),
+        Value::Boolean(_) => None,
+    }
+}
+
+fn binary_op(opcode: Opcode) -> Option<BinaryOp> {
+    match opcode {
+        Opcode::Add => Some(BinaryOp::Add),
+        Opcode::Subtract => Some(BinaryOp::Subtract),
+        Opcode::Multiply => Some(BinaryOp::Multiply),
+        Opcode::Divide => Some(BinaryOp::Divide),
+        Opcode::Concat => Some(BinaryOp::Concat),
+        _ => None,
+    }
+}
+
+/// `push a, push b, op` becomes `push (a op b)`; `push a, push b, Equal,
+/// JumpIfFalse t` becomes `Jump t` or nothing.
+fn fold_constants(program: &mut BytecodeProgram) -> bool {
+    let targets = jump_targets(program);
+    let mut removed = vec![false; program.instructions.len()];
+    let mut i = 0;
+    while i + 2 < program.instructions.len() {
+        let opcodes: Vec<Opcode> = program.instructions[i..].iter().take(4).map(|ins| ins.opcode).collect();
+        let (Some(a), Some(b)) = (constant(program, opcodes[0]), constant(program, opcodes[1])) else {
+            i += 1;
+            continue;
+        };
+        if targets[i + 1] || targets[i + 2] {
+            i += 1;
+            continue;
+        }
+
+        if let Some(op) = binary_op(opcodes[2]) {
+            if let Some(opcode) = op.apply(&a, &b).ok().and_then(|value| push(program, value)) {
+                program.instructions[i].opcode = opcode;
+                removed[i + 1] = true;
+                removed[i + 2] = true;
+                i += 3;
+                continue;
+            }
+        } else if let (Opcode::Equal | Opcode::NotEqual, Some(&jump), Ok(equal)) =
+            (opcodes[2], opcodes.get(3), a.equals(&b))
+        {
+            let condition = equal == (opcodes[2] == Opcode::Equal);
+            let branch = match jump {
+                Opcode::JumpIfTrue(target) => Some((target, condition)),
+                Opcode::JumpIfFalse(target) => Some((target, !condition)),
+                _ => None,
+            };
+            if let (Some((target, taken)), false) = (branch, targets[i + 3]) {
+                if taken {
+                    program.instructions[i] = Instruction::new(Opcode::Jump(target), program.instructions[i + 3].span);
+                } else {
+                    removed[i] = true;
+                }
+                removed[i + 1..i + 4].fill(true);
+                i += 4;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    remove(program, &removed)
+}
+
+/// `PushString a, Concat, PushString b, Concat` becomes
+/// `PushString ab, Concat`. Joining onto a string never fails, so this is
+/// the same whatever is below the first literal on the stack.
+fn merge_concatenations(program: &mut BytecodeProgram) -> bool {
+    let targets = jump_targets(program);
+    let mut removed = vec![false; program.instructions.len()];
+    let mut i = 0;
+    while i + 3 < program.instructions.len() {
+        let window: Vec<Opcode> = program.instructions[i..i + 4].iter().map(|ins| ins.opcode).collect();
+        if let [Opcode::PushString(a), Opcode::Concat, Opcode::PushString(b), Opcode::Concat] = window[..] {
+            if !targets[i + 1] && !targets[i + 2] && !targets[i + 3] {
+                if let (Some(a), Some(b)) = (program.string_pool.get(a), program.string_pool.get(b)) {
+                    let joined = format!("{}{}", a, b);
+                    program.instructions[i].opcode = Opcode::PushString(program.add_string(joined));
+                    removed[i + 2] = true;
+                    removed[i + 3] = true;
+                    i += 4;
+                    continue;
+                }
+            }
+        }
+        i += 1;
+    }
+    remove(program, &removed)
+}
+
+fn thread_jumps(program: &mut BytecodeProgram) -> bool {
+    let count = program.instructions.len();
+    let mut changed = false;
+    let mut removed = vec![false; count];
+    for (i, gone) in removed.iter_mut().enumerate() {
+        let opcode = program.instructions[i].opcode;
+        let Some(mut target) = opcode.jump_target() else {
+            continue;
+        };
+        // Bounded so that a loop of jumps cannot hang the optimizer
+        for _ in 0..count {
+            match program.instructions.get(target).map(|ins| ins.opcode) {
+                Some(Opcode::Jump(next)) if next != target => target = next,
+                _ => break,
+            }
+        }
+        if Some(target) != opcode.jump_target() {
+            program.instructions[i].opcode = opcode.with_jump_target(target);
+            changed = true;
+        }
+
+        if let Opcode::Jump(_) = opcode {
+            if target == i + 1 {
+                *gone = true;
+            } else if let Some(Opcode::Halt) = program.instructions.get(target).map(|ins| ins.opcode) {
+                program.instructions[i].opcode = Opcode::Halt;
+                changed = true;
+            }
+        }
+    }
+    remove(program, &removed) || changed
+}
+
+/// `StoreVariable x, LoadVariable x` leaves the value on the stack either
+/// way, so when no other instruction reads `x` both can go.
+fn remove_store_load_pairs(program: &mut BytecodeProgram) -> bool {
+    let targets = jump_targets(program);
+    let mut loads = vec![0usize; program.variable_names.len()];
+    for instruction in &program.instructions {
+        if let Opcode::LoadVariable(index) = instruction.opcode {
+            if let Some(count) = loads.get_mut(index) {
+                *count += 1;
+            }
+        }
+    }
+
+    let mut removed = vec![false; program.instructions.len()];
+    for i in 1..program.instructions.len() {
+        if let (Opcode::StoreVariable(stored), Opcode::LoadVariable(loaded)) =
+            (program.instructions[i - 1].opcode, program.instructions[i].opcode)
+        {
+            if stored == loaded && loads.get(loaded) == Some(&1) && !targets[i] && !removed[i - 1] {
+                removed[i - 1] = true;
+                removed[i] = true;
+            }
+        }
+    }
+    remove(program, &removed)
+}
+
+fn remove_unreachable(program: &mut BytecodeProgram) -> bool {
+    let count = program.instructions.len();
+    let mut reachable = vec![false; count];
+    let mut pending = vec![0];
+    while let Some(offset) = pending.pop() {
+        if offset >= count || reachable[offset] {
+            continue;
+        }
+        reachable[offset] = true;
+        pending.extend(program.instructions[offset].opcode.successors(offset));
+    }
+    let removed: Vec<bool> = reachable.iter().map(|&r| !r).collect();
+    remove(program, &removed)
+}
+
+/// Drops strings that folding replaced and renumbers the rest.
+fn remove_unused_strings(program: &mut BytecodeProgram) {
+    let mut new_indexes = vec![None; program.string_pool.len()];
+    let mut pool = Vec::new();
+    for instruction in &mut program.instructions {
+        if let Opcode::PushString(index) = instruction.opcode {
+            let Some(slot) = new_indexes.get_mut(index) else {
+                continue;
+            };
+            let new_index = *slot.get_or_insert_with(|| {
+                pool.push(program.string_pool[index].clone());
+                pool.len() - 1
+            });
+            instruction.opcode = Opcode::PushString(new_index);
+        }
+    }
+    program.string_pool = pool.into();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capability::Capabilities;
+    use crate::compiler::Compiler;
+    use crate::console::Console;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::samples::{SAMPLES, SAMPLE_INPUTS};
+    use crate::verifier;
+    use crate::vm::VM;
+
+    fn compile(source: &str) -> BytecodeProgram {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap()
+    }
+
+    fn opcodes(program: &BytecodeProgram) -> Vec<Opcode> {
+        program.instructions.iter().map(|ins| ins.opcode).collect()
+    }
+
+    /// Output of the program and the error it stopped with, if any.
+    fn run(program: BytecodeProgram, input: &str) -> (String, Option<String>) {
+        let (console, output) = Console::memory(input);
+        let result = VM::new(program).with_console(console).with_capabilities(Capabilities::console()).run();
+        (output.contents(), result.err().map(|e| e.to_string()))
+    }
+
+    fn assert_same_behaviour(source: &str, input: &str) -> BytecodeProgram {
+        let program = compile(source);
+        let optimized = optimize(program.clone());
+        verifier::verify(&optimized).unwrap();
+        assert_eq!(run(optimized.clone(), input), run(program, input), "{}", source);
+        optimized
+    }
+
+    #[test]
+    fn folds_literals_and_merges_concatenations() {
+        let optimized = assert_same_behaviour("oi mug naam = \"Sita\"\nbol mug \"Namaste \" + naam + \"!\" + \" \" + \"ji\"", "");
+        let strings = &optimized.string_pool;
+        assert!(strings.contains(&"! ji".to_string()), "{:?}", strings);
+        assert!(!strings.contains(&" ".to_string()), "unused strings are dropped: {:?}", strings);
+
+        let optimized = assert_same_behaviour("bol mug \"a\" + \"b\" + \"c\"", "");
+        assert_eq!(opcodes(&optimized), [Opcode::PushString(0), Opcode::Print, Opcode::Halt]);
+        assert_eq!(optimized.string_pool[..], ["abc"]);
+    }
+
+    #[test]
+    fn removes_store_load_pairs_only_when_unobserved() {
+        let optimized = assert_same_behaviour("oi mug x = 5\nbol mug x", "");
+        assert_eq!(opcodes(&optimized), [Opcode::PushNumber(5), Opcode::Print, Opcode::Halt]);
+
+        // `x` is read twice, so it has to be stored
+        let optimized = assert_same_behaviour("oi mug x = 5\nbol mug x\nbol mug x", "");
+        assert!(opcodes(&optimized).contains(&Opcode::StoreVariable(0)));
+    }
+
+    #[test]
+    fn folds_constant_conditions() {
+        let mut program = BytecodeProgram::new();
+        let rato = program.add_string("rato".to_string());
+        let ho = program.add_string("ho".to_string());
+        let span = Default::default();
+        for opcode in [
+            Opcode::PushString(rato),
+            Opcode::PushString(rato),
+            Opcode::NotEqual,
+            Opcode::JumpIfFalse(6),
+            Opcode::PushString(ho),
+            Opcode::Print,
+            Opcode::Jump(7),
+            Opcode::Halt,
+        ] {
+            program.add_instruction(opcode, span);
+        }
+        let optimized = optimize(program.clone());
+        assert_eq!(opcodes(&optimized), [Opcode::Halt]);
+        assert_eq!(run(optimized, ""), run(program, ""));
+    }
+
+    #[test]
+    fn leaves_failing_operations_for_the_vm() {
+        let mut program = BytecodeProgram::new();
+        for opcode in [Opcode::PushNumber(i32::MAX), Opcode::PushNumber(1), Opcode::Add, Opcode::Print, Opcode::Halt] {
+            program.add_instruction(opcode, Default::default());
+        }
+        let optimized = optimize(program.clone());
+        assert_eq!(optimized, program);
+    }
+
+    #[test]
+    fn samples_behave_the_same_when_optimized() {
+        for source in SAMPLES {
+            for input in SAMPLE_INPUTS {
+                let optimized = assert_same_behaviour(source, input);
+                assert!(optimized.instructions.len() < compile(source).instructions.len());
+            }
+        }
+    }
+}
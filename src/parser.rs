@@ -1,5 +1,8 @@
-use crate::ast::{Statement, Condition, Value, StrSegment};
+use crate::ast::{Annotation, Argument, Ident, Statement, Condition, Value, StrSegment};
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
 use crate::error::NepalError;
+use crate::native::Type;
+use crate::span::{Span, Spanned};
 use crate::token::Token;
 
 #[derive(Clone, Copy)]
@@ -10,124 +13,246 @@ enum MugOp {
     Div,
 }
 
+impl MugOp {
+    fn from_token(token: &Token) -> Option<MugOp> {
+        match token {
+            Token::Jod => Some(MugOp::Add),
+            Token::Ghata => Some(MugOp::Sub),
+            Token::Guna => Some(MugOp::Mul),
+            Token::Bhag => Some(MugOp::Div),
+            _ => None,
+        }
+    }
+
+    fn statement(self, target: Ident, sources: Vec<Ident>) -> Statement {
+        match self {
+            MugOp::Add => Statement::Addition(target, sources),
+            MugOp::Sub => Statement::Subtraction(target, sources),
+            MugOp::Mul => Statement::Multiplication(target, sources),
+            MugOp::Div => Statement::Division(target, sources),
+        }
+    }
+}
+
 pub struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<Spanned<Token>>,
     position: usize,
+    /// Syntax errors recovered from so far
+    errors: Vec<NepalError>,
+    /// `yedi` blocks open around the current token
+    depth: usize,
 }
 
+/// How deep `yedi` blocks may nest. Parsing and every pass over the tree
+/// recurse once a level, so deeper programs could overflow the stack.
+pub const MAX_NESTING: usize = 100;
+
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<Spanned<Token>>) -> Self {
         Parser {
             tokens,
             position: 0,
+            errors: Vec::new(),
+            depth: 0,
         }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Statement>, NepalError> {
+    /// Parses the whole program, recovering from syntax errors so that every
+    /// one of them is reported, not just the first.
+    pub fn parse(&mut self) -> Result<Vec<Spanned<Statement>>, NepalError> {
+        let (statements, mut errors) = self.parse_with_errors();
+        match errors.len() {
+            0 => Ok(statements),
+            1 => Err(errors.remove(0)),
+            _ => Err(NepalError::Multiple(errors)),
+        }
+    }
+
+    /// Like [`Parser::parse`], but also keeps the statements that parsed
+    /// when others did not, as an editor needs while a program is typed.
+    pub fn parse_with_errors(&mut self) -> (Vec<Spanned<Statement>>, Vec<NepalError>) {
         let mut statements = Vec::new();
         while self.position < self.tokens.len() {
-            statements.push(self.parse_statement()?);
+            let start = self.position;
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => self.recover(error, start),
+            }
+        }
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    /// Records `error` from the statement starting at token `start` and skips
+    /// ahead to the next statement keyword (panic-mode recovery).
+    fn recover(&mut self, error: NepalError, start: usize) {
+        self.errors.push(error);
+        if self.position == start {
+            self.position += 1;
+        }
+        while let Some(token) = self.peek() {
+            if matches!(
+                token,
+                Token::OiMug | Token::BolMug | Token::Mug | Token::Yedi | Token::Aile | Token::Sakiyo
+            ) {
+                break;
+            }
+            self.position += 1;
+        }
+
+        // A `yedi` whose condition failed still owns the block up to its
+        // `sakiyo`; skip it so the `sakiyo` is not reported as stray.
+        if matches!(self.tokens.get(start).map(|t| &t.node), Some(Token::Yedi | Token::Aile)) {
+            self.skip_if_body();
+        }
+    }
+
+    /// Skips what [`Parser::parse_if_body`] would parse, counting the `yedi`
+    /// blocks nested in it rather than parsing them.
+    fn skip_if_body(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Yedi => depth += 1,
+                Token::Aile if depth == 0 => break,
+                Token::Sakiyo if depth == 0 => {
+                    self.position += 1;
+                    break;
+                }
+                Token::Sakiyo => depth -= 1,
+                _ => {}
+            }
+            self.position += 1;
         }
-        Ok(statements)
     }
 
-    fn parse_statement(&mut self) -> Result<Statement, NepalError> {
-        match &self.tokens[self.position] {
-            Token::OiMug => {
+    /// Span of the current token, or the end of the last token at end of input.
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some(token) => token.span,
+            None => self
+                .tokens
+                .last()
+                .map(|t| Span::new(t.span.end, t.span.end, t.span.line, t.span.column + (t.span.end - t.span.start)))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// An error of kind `code` pointing at the current token. At end of input
+    /// this becomes an unexpected-end-of-file error saying what `code` expected.
+    fn error(&self, code: ErrorCode) -> Diagnostic {
+        let diagnostic = match self.tokens.get(self.position) {
+            Some(token) => Diagnostic::new(code).with_arg(&token.node),
+            None => Diagnostic::new(ErrorCode::UnexpectedEndOfFile).with_expected(code),
+        };
+        diagnostic.with_span(self.current_span())
+    }
+
+    /// Span from the token at `start` through the last consumed token.
+    fn span_since(&self, start: usize) -> Span {
+        match (self.tokens.get(start), self.position.checked_sub(1).and_then(|i| self.tokens.get(i))) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default(),
+        }
+    }
+    fn parse_statement(&mut self) -> Result<Spanned<Statement>, NepalError> {
+        let start = self.position;
+        let statement = self.parse_statement_kind()?;
+        Ok(Spanned::new(statement, self.span_since(start)))
+    }
+
+    fn parse_statement_kind(&mut self) -> Result<Statement, NepalError> {
+        match self.peek() {
+            Some(Token::OiMug) => {
+                let start = self.position;
                 self.position += 1;
-                if matches!(self.tokens.get(self.position), Some(Token::Bhan)) {
+                if matches!(self.peek(), Some(Token::Bhan)) {
                     self.position += 1;
-                    self.parse_input()
+                    self.parse_input(start)
                 } else {
-                    self.parse_declaration()
+                    self.parse_declaration(start)
                 }
             },
-            Token::BolMug => self.parse_print(),
-            Token::Mug => {
+            Some(Token::BolMug) => self.parse_print(),
+            Some(Token::Mug) => {
                 self.position += 1;  // Skip 'mug'
-                match &self.tokens[self.position] {
-                    Token::Jod => {
-                        self.position += 1;
-                        self.parse_standalone_arithmetic(MugOp::Add)
-                    }
-                    Token::Ghata => {
-                        self.position += 1;
-                        self.parse_standalone_arithmetic(MugOp::Sub)
-                    }
-                    Token::Guna => {
+                match self.peek().and_then(MugOp::from_token) {
+                    Some(op) => {
                         self.position += 1;
-                        self.parse_standalone_arithmetic(MugOp::Mul)
+                        self.parse_standalone_arithmetic(op)
                     }
-                    Token::Bhag => {
-                        self.position += 1;
-                        self.parse_standalone_arithmetic(MugOp::Div)
-                    }
-                    _ => Err(NepalError::ParseError(
-                        "Expected 'jod', 'ghata', 'guna', or 'bhag' after 'mug'",
-                    )),
+                    None => match self.eat_ident() {
+                        Some(function) => self.parse_call(function),
+                        None => Err(self.error(ErrorCode::ExpectedMugOperation).into()),
+                    },
                 }
             }
-            Token::Yedi => self.parse_if_statement(),
-            Token::Aile => {
+            Some(Token::Yedi) => self.parse_if_statement(),
+            Some(Token::Aile) => {
                 self.position += 1;
-                if let Some(Token::Feri) = self.tokens.get(self.position) {
+                if let Some(Token::Feri) = self.peek() {
                     self.position += 1;
                     self.parse_if_statement()
                 } else {
-                    Err(NepalError::ParseError("Expected 'feri' after 'aile'"))
+                    Err(self.error(ErrorCode::ExpectedFeri).into())
                 }
             },
-            _ => Err(NepalError::ParseError("Unexpected token")),
+            _ => Err(self.error(ErrorCode::UnexpectedToken).into()),
         }
     }
 
     fn parse_if_statement(&mut self) -> Result<Statement, NepalError> {
-        if matches!(self.tokens.get(self.position), Some(Token::Yedi)) {
+        let condition_span = if matches!(self.peek(), Some(Token::Yedi)) {
             self.position += 1;
-        }
-        
-        let var1 = if let Token::Identifier(name) = &self.tokens[self.position] {
-            self.position += 1;
-            name.clone()
+            self.span_since(self.position - 1)
         } else {
-            return Err(NepalError::ParseError("Expected variable name after yedi"));
+            // `aile feri` has already been consumed
+            self.span_since(self.position.saturating_sub(2))
+        };
+
+        let Some(var1) = self.eat_ident() else {
+            return Err(self
+                .error(ErrorCode::ExpectedIdentifier)
+                .with_arg("`yedi`")
+                .into());
         };
 
-        let condition = match &self.tokens[self.position] {
-            Token::Babaal => {
+        let condition = match self.peek() {
+            Some(Token::Babaal) => {
                 self.position += 1;
                 true
             }
-            Token::Laamo => {
+            Some(Token::Laamo) => {
                 self.position += 1;
                 false
             }
-            _ => return Err(NepalError::ParseError("Expected babaal or laamo")),
+            _ => return Err(self.error(ErrorCode::ExpectedComparison).into()),
         };
 
-        let var2 = if let Token::String(s) = &self.tokens[self.position] {
+        let var2 = if let Some(Token::String(s)) = self.peek() {
+            let s = s.clone();
             self.position += 1;
-            s.clone()
+            s
         } else {
-            return Err(NepalError::ParseError("Expected string literal"));
+            return Err(self.error(ErrorCode::ExpectedStringLiteral).into());
         };
 
-        if !matches!(self.tokens.get(self.position), Some(Token::Bhane)) {
-            return Err(NepalError::ParseError("Expected 'bhane' after condition"));
+        if !matches!(self.peek(), Some(Token::Bhane)) {
+            return Err(self
+                .error(ErrorCode::ExpectedBhane)
+                .with_label(condition_span, Note::ConditionStartsHere)
+                .into());
         }
         self.position += 1;
 
-        let mut statements = Vec::new();
-        while self.position < self.tokens.len() {
-            match &self.tokens[self.position] {
-                Token::Sakiyo => {
-                    self.position += 1;
-                    break;
-                }
-                Token::Aile => break,
-                _ => statements.push(self.parse_statement()?),
-            }
+        if self.depth == MAX_NESTING {
+            return Err(Diagnostic::new(ErrorCode::NestingTooDeep)
+                .with_arg(MAX_NESTING)
+                .with_span(condition_span)
+                .into());
         }
+        self.depth += 1;
+        let statements = self.parse_if_body();
+        self.depth -= 1;
 
         Ok(Statement::If(
             if condition {
@@ -140,197 +265,297 @@ impl Parser {
         ))
     }
 
-    fn parse_declaration(&mut self) -> Result<Statement, NepalError> {
-        let name = if let Token::Identifier(name) = &self.tokens[self.position] {
-            self.position += 1;
-            name.clone()
-        } else {
-            return Err(NepalError::ParseError("Expected identifier"));
+    /// Parses statements up to and including `sakiyo`, or up to `aile`.
+    fn parse_if_body(&mut self) -> Vec<Spanned<Statement>> {
+        let mut statements = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Sakiyo => {
+                    self.position += 1;
+                    break;
+                }
+                Token::Aile => break,
+                _ => {
+                    let start = self.position;
+                    match self.parse_statement() {
+                        Ok(statement) => statements.push(statement),
+                        Err(error) => self.recover(error, start),
+                    }
+                }
+            }
+        }
+        statements
+    }
+
+    fn parse_declaration(&mut self, start: usize) -> Result<Statement, NepalError> {
+        let Some(name) = self.eat_ident() else {
+            return Err(self
+                .error(ErrorCode::ExpectedIdentifier)
+                .with_arg("`oi mug`")
+                .into());
         };
+        let annotation = self.parse_annotation(&name)?;
+        let statement = self.parse_declared_value(name)?;
+        Ok(self.annotated(statement, annotation, start))
+    }
 
-        if let Token::Equals = &self.tokens[self.position] {
+    /// The `= value` of an `oi mug` declaration of `name`.
+    fn parse_declared_value(&mut self, name: Ident) -> Result<Statement, NepalError> {
+        if let Some(Token::Equals) = self.peek() {
             self.position += 1;
         } else {
-            return Err(NepalError::ParseError("Expected '='"));
+            return Err(self
+                .error(ErrorCode::ExpectedEquals)
+                .with_arg(&name)
+                .into());
         }
 
-        match &self.tokens[self.position] {
-            Token::Number(value) => {
+        match self.peek() {
+            Some(Token::Number(value)) => {
+                let value = *value;
                 self.position += 1;
-                Ok(Statement::Declaration(name, Value::Number(*value)))
+                Ok(Statement::Declaration(name, Value::Number(value)))
             }
-            Token::String(value) => {
+            Some(Token::String(value)) => {
+                let value = value.clone();
                 self.position += 1;
-                if matches!(self.tokens.get(self.position), Some(Token::Plus)) {
+                if matches!(self.peek(), Some(Token::Plus)) {
                     self.position += 1;
-                    let mut parts = vec![StrSegment::Literal(value.clone())];
-                    
-                    while self.position < self.tokens.len() {
-                        match &self.tokens[self.position] {
-                            Token::String(s) => {
-                                parts.push(StrSegment::Literal(s.clone()));
-                                self.position += 1;
-                            }
-                            Token::Identifier(id) => {
-                                parts.push(StrSegment::Identifier(id.clone()));
-                                self.position += 1;
-                            }
-                            Token::Plus => {
-                                self.position += 1;
-                                continue;
-                            }
-                            _ => break,
-                        }
-                    }
+                    let mut parts = vec![StrSegment::Literal(value)];
+                    self.parse_concat_segments(&mut parts, ErrorCode::ExpectedValue)?;
                     Ok(Statement::StringConcat(name, parts))
                 } else {
-                    Ok(Statement::Declaration(name, Value::String(value.clone())))
+                    Ok(Statement::Declaration(name, Value::String(value)))
                 }
             }
-            Token::Identifier(value) => {
-                self.position += 1;
-                if matches!(self.tokens.get(self.position), Some(Token::Plus)) {
+            Some(Token::Identifier(_)) => {
+                let first = self.eat_ident().expect("current token is an identifier");
+                if matches!(self.peek(), Some(Token::Plus)) {
                     self.position += 1;
-                    let mut parts = vec![StrSegment::Identifier(value.clone())];
-                    
-                    while self.position < self.tokens.len() {
-                        match &self.tokens[self.position] {
-                            Token::String(s) => {
-                                parts.push(StrSegment::Literal(s.clone()));
-                                self.position += 1;
-                            }
-                            Token::Identifier(id) => {
-                                parts.push(StrSegment::Identifier(id.clone()));
-                                self.position += 1;
-                            }
-                            Token::Plus => {
-                                self.position += 1;
-                                continue;
-                            }
-                            _ => break,
-                        }
-                    }
+                    let mut parts = vec![StrSegment::Identifier(first)];
+                    self.parse_concat_segments(&mut parts, ErrorCode::ExpectedValue)?;
                     Ok(Statement::StringConcat(name, parts))
                 } else {
-                    Ok(Statement::Declaration(name, Value::String(value.clone())))
+                    Ok(Statement::Declaration(name, Value::String(first.node)))
                 }
             }
-            Token::Jod | Token::Ghata | Token::Guna | Token::Bhag => {
-                self.parse_arithmetic_operation(name)
-            }
-            _ => Err(NepalError::ParseError("Expected value")),
+            Some(token) => match MugOp::from_token(token) {
+                Some(op) => {
+                    self.position += 1;
+                    self.parse_arithmetic_operation(name, op)
+                }
+                None => Err(self.error(ErrorCode::ExpectedValue).into()),
+            },
+            None => Err(self.error(ErrorCode::ExpectedValue).into()),
         }
     }
 
-    fn parse_arithmetic_operation(&mut self, target: String) -> Result<Statement, NepalError> {
-        let op = self.tokens[self.position].clone();
-        self.position += 1;
-
-        let mut sources = Vec::new();
-        while self.position < self.tokens.len() {
-            match &self.tokens[self.position] {
-                Token::Identifier(id) => {
-                    sources.push(id.clone());
+    /// Collects the remaining `+`-separated strings and variables of a
+    /// concatenation. A `+` with nothing after it at the end of the file is
+    /// reported as missing what `expected` describes.
+    fn parse_concat_segments(&mut self, parts: &mut Vec<StrSegment>, expected: ErrorCode) -> Result<(), NepalError> {
+        loop {
+            match self.peek() {
+                Some(Token::String(s)) => {
+                    parts.push(StrSegment::Literal(s.clone()));
                     self.position += 1;
-                    if matches!(self.tokens.get(self.position), Some(Token::Comma)) {
-                        self.position += 1;
-                    } else {
-                        break;
-                    }
                 }
-                _ => break,
+                Some(Token::Identifier(_)) => {
+                    let ident = self.eat_ident().expect("current token is an identifier");
+                    parts.push(StrSegment::Identifier(ident));
+                }
+                Some(Token::Plus) => {
+                    self.position += 1;
+                }
+                None if matches!(self.previous(), Some(Token::Plus)) => return Err(self.error(expected).into()),
+                _ => return Ok(()),
             }
         }
+    }
 
-        match op {
-            Token::Jod => Ok(Statement::Addition(target, sources)),
-            Token::Ghata => Ok(Statement::Subtraction(target, sources)),
-            Token::Guna => Ok(Statement::Multiplication(target, sources)),
-            Token::Bhag => Ok(Statement::Division(target, sources)),
-            _ => unreachable!(),
+    fn parse_arithmetic_operation(&mut self, target: Ident, op: MugOp) -> Result<Statement, NepalError> {
+        let mut sources = Vec::new();
+        while let Some(source) = self.eat_ident() {
+            sources.push(source);
+            if matches!(self.peek(), Some(Token::Comma)) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        if self.peek().is_none() && matches!(self.previous(), Some(Token::Comma)) {
+            return Err(self.error(ErrorCode::ExpectedIdentifier).into());
         }
+
+        Ok(op.statement(target, sources))
     }
 
     fn parse_standalone_arithmetic(&mut self, op: MugOp) -> Result<Statement, NepalError> {
         let mut sources = Vec::new();
         
-        while self.position < self.tokens.len() {
-            match &self.tokens[self.position] {
-                Token::Identifier(name) => {
-                    sources.push(name.clone());
+        while self.peek().is_some() {
+            let Some(source) = self.eat_ident() else {
+                let previous = self.previous().map_or("mug", Token::keyword);
+                return Err(self
+                    .error(ErrorCode::ExpectedIdentifier)
+                    .with_arg(format!("`{}`", previous))
+                    .into());
+            };
+            let name = source.node.clone();
+            sources.push(source);
+
+            match self.peek() {
+                Some(Token::Comma) => {
                     self.position += 1;
-                    
-                    match self.tokens.get(self.position) {
-                        Some(Token::Comma) => {
-                            self.position += 1;
-                            continue;
-                        }
-                        Some(Token::Lai) => {
-                            self.position += 1;
-                            break;
-                        }
-                        _ => return Err(NepalError::ParseError("Expected comma or 'lai'")),
-                    }
+                    continue;
                 }
-                _ => return Err(NepalError::ParseError("Expected identifier")),
+                Some(Token::Lai) => {
+                    self.position += 1;
+                    break;
+                }
+                _ => {
+                    return Err(self
+                        .error(ErrorCode::ExpectedCommaOrLai)
+                        .with_arg(name)
+                        .into())
+                }
+            }
+        }
+
+        let Some(target) = self.eat_ident() else {
+            return Err(self
+                .error(ErrorCode::ExpectedIdentifier)
+                .with_arg("`lai`")
+                .into());
+        };
+
+        Ok(op.statement(target, sources))
+    }
+
+    /// Parses the arguments and optional `lai target` of a native function call.
+    fn parse_call(&mut self, function: Ident) -> Result<Statement, NepalError> {
+        let mut args = Vec::new();
+        loop {
+            let arg = match self.peek() {
+                Some(Token::Identifier(_)) => {
+                    Argument::Variable(self.eat_ident().expect("current token is an identifier"))
+                }
+                Some(Token::Number(n)) => Argument::Literal(Value::Number(*n)),
+                Some(Token::String(s)) => Argument::Literal(Value::String(s.clone())),
+                _ if args.is_empty() => break,
+                _ => return Err(self.error(ErrorCode::ExpectedArgument).into()),
+            };
+            if matches!(arg, Argument::Literal(_)) {
+                self.position += 1;
+            }
+            args.push(arg);
+
+            if matches!(self.peek(), Some(Token::Comma)) {
+                self.position += 1;
+            } else {
+                break;
             }
         }
 
-        let target = if let Token::Identifier(name) = &self.tokens[self.position] {
+        let target = if matches!(self.peek(), Some(Token::Lai)) {
             self.position += 1;
-            name.clone()
+            let Some(target) = self.eat_ident() else {
+                return Err(self
+                    .error(ErrorCode::ExpectedIdentifier)
+                    .with_arg("`lai`")
+                    .into());
+            };
+            Some(target)
         } else {
-            return Err(NepalError::ParseError("Expected target identifier after 'lai'"));
+            None
         };
 
-        match op {
-            MugOp::Add => Ok(Statement::Addition(target, sources)),
-            MugOp::Sub => Ok(Statement::Subtraction(target, sources)),
-            MugOp::Mul => Ok(Statement::Multiplication(target, sources)),
-            MugOp::Div => Ok(Statement::Division(target, sources)),
-        }
+        Ok(Statement::Call(function, args, target))
     }
 
     fn parse_print(&mut self) -> Result<Statement, NepalError> {
         self.position += 1; // Skip 'bol mug'
-        if let Token::String(_) = &self.tokens[self.position] {
+        if let Some(Token::String(_)) = self.peek() {
             let mut parts = Vec::new();
-            while self.position < self.tokens.len() {
-                match &self.tokens[self.position] {
-                    Token::String(s) => {
-                        parts.push(s.clone());
-                        self.position += 1;
-                    }
-                    Token::Plus => {
-                        self.position += 1;
-                    }
-                    Token::Identifier(name) => {
-                        parts.push(format!("{{{}}}", name));
-                        self.position += 1;
-                    }
-                    _ => break,
-                }
-            }
+            self.parse_concat_segments(&mut parts, ErrorCode::ExpectedPrintable)?;
             Ok(Statement::PrintString(parts))
         } else {
-            let name = if let Token::Identifier(name) = &self.tokens[self.position] {
-                self.position += 1;
-                name.clone()
-            } else {
-                return Err(NepalError::ParseError("Expected identifier or string"));
+            let Some(name) = self.eat_ident() else {
+                return Err(self.error(ErrorCode::ExpectedPrintable).into());
             };
             Ok(Statement::Print(name))
         }
     }
 
-    fn parse_input(&mut self) -> Result<Statement, NepalError> {
-        let name = if let Token::Identifier(name) = &self.tokens[self.position] {
-            self.position += 1;
-            name.clone()
-        } else {
-            return Err(NepalError::ParseError("Expected identifier after bhan"));
+    fn parse_input(&mut self, start: usize) -> Result<Statement, NepalError> {
+        let Some(name) = self.eat_ident() else {
+            return Err(self
+                .error(ErrorCode::ExpectedIdentifier)
+                .with_arg("`oi mug bhan`")
+                .into());
+        };
+        let annotation = self.parse_annotation(&name)?;
+        // Input is always text, so only `shabda` can be true of it
+        if let Some(annotation) = annotation.as_ref().filter(|annotation| annotation.kind.node != Type::String) {
+            return Err(Diagnostic::new(ErrorCode::InputAnnotation)
+                .with_arg(&name.node)
+                .with_span(annotation.kind.span)
+                .with_note(Note::ConvertToNumber)
+                .into());
+        }
+        Ok(self.annotated(Statement::Input(name), annotation, start))
+    }
+
+    /// Parses an optional `: sankhya` or `: shabda` after a declared name.
+    fn parse_annotation(&mut self, name: &Ident) -> Result<Option<Annotation>, NepalError> {
+        if !matches!(self.peek(), Some(Token::Colon)) {
+            return Ok(None);
+        }
+        self.position += 1;
+        let kind = self.tokens.get(self.position).and_then(|token| match &token.node {
+            Token::Identifier(word) => Annotation::NAMES
+                .iter()
+                .find(|(type_name, _)| type_name == word)
+                .map(|&(_, kind)| Spanned::new(kind, token.span)),
+            _ => None,
+        });
+        let Some(kind) = kind else {
+            return Err(self.error(ErrorCode::ExpectedTypeName).into());
         };
-        Ok(Statement::Input(name))
+        self.position += 1;
+        Ok(Some(Annotation { name: name.clone(), kind }))
+    }
+
+    /// Wraps `statement`, parsed from token `start` on, in its annotation if it has one.
+    fn annotated(&self, statement: Statement, annotation: Option<Annotation>, start: usize) -> Statement {
+        match annotation {
+            Some(annotation) => Statement::Annotated(annotation, Box::new(Spanned::new(statement, self.span_since(start)))),
+            None => statement,
+        }
+    }
+
+    /// The current token, or `None` at end of input.
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|t| &t.node)
+    }
+
+    /// The most recently consumed token.
+    fn previous(&self) -> Option<&Token> {
+        let index = self.position.checked_sub(1)?;
+        self.tokens.get(index).map(|t| &t.node)
+    }
+
+    /// Consumes the current token if it is an identifier.
+    fn eat_ident(&mut self) -> Option<Ident> {
+        let token = self.tokens.get(self.position)?;
+        match &token.node {
+            Token::Identifier(name) => {
+                let ident = Spanned::new(name.clone(), token.span);
+                self.position += 1;
+                Some(ident)
+            }
+            _ => None,
+        }
     }
 }
 
@@ -338,7 +563,9 @@ impl Parser {
 mod tests {
     use super::*;
     use crate::ast::StrSegment;
+    use crate::samples::SAMPLES;
     use crate::token::Token;
+    use crate::native::Type;
 
     #[test]
     fn string_concat_preserves_literal_segments() {
@@ -352,9 +579,9 @@ mod tests {
             Token::Plus,
             Token::Identifier("lastName".into()),
         ];
-        let mut p = Parser::new(tokens);
+        let mut p = Parser::new(tokens.into_iter().map(Spanned::from).collect());
         let stmts = p.parse().unwrap();
-        match &stmts[0] {
+        match &stmts[0].node {
             Statement::StringConcat(name, parts) => {
                 assert_eq!(name, "fullName");
                 assert_eq!(parts.len(), 3);
@@ -378,9 +605,280 @@ mod tests {
             Token::String("ok".into()),
             Token::Sakiyo,
         ];
-        let mut p = Parser::new(tokens);
+        let mut p = Parser::new(tokens.into_iter().map(Spanned::from).collect());
         let stmts = p.parse().unwrap();
         assert_eq!(stmts.len(), 1);
-        assert!(matches!(stmts[0], Statement::If(_, _, None)));
+        assert!(matches!(stmts[0].node, Statement::If(_, _, None)));
+    }
+
+    fn parse_source(source: &str) -> Result<Vec<Spanned<Statement>>, NepalError> {
+        let tokens = crate::lexer::Lexer::new(source).tokenize()?;
+        Parser::new(tokens).parse()
+    }
+
+    #[test]
+    fn statements_and_names_carry_spans() {
+        let stmts = parse_source("bol mug \"hi\"\noi mug umer = 5\n").unwrap();
+        assert_eq!(stmts[1].span.line, 2);
+        assert_eq!((stmts[1].span.start, stmts[1].span.end), (13, 28));
+        match &stmts[1].node {
+            Statement::Declaration(name, _) => assert_eq!((name.span.line, name.span.column), (2, 7)),
+            _ => panic!("expected Declaration"),
+        }
+    }
+
+    #[test]
+    fn missing_bhane_points_at_token_and_condition() {
+        let err = parse_source("yedi rang babaal \"rato\"\nbol mug rang").unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, ErrorCode::ExpectedBhane);
+        assert_eq!(diagnostic.message(), "expected `bhane` after the condition, found `bol mug`");
+        assert_eq!(diagnostic.span.unwrap().line, 2);
+        assert_eq!(diagnostic.labels[0].span.line, 1);
+    }
+
+    #[test]
+    fn missing_equals_names_the_variable() {
+        let err = parse_source("oi mug umer 5").unwrap_err();
+        assert_eq!(
+            err.diagnostic().unwrap().message(),
+            "expected `=` after `umer`, found `5`"
+        );
+    }
+
+    fn error_codes(err: &NepalError) -> Vec<ErrorCode> {
+        err.errors().iter().map(|e| e.diagnostic().unwrap().code).collect()
+    }
+
+    #[test]
+    fn reports_every_syntax_error() {
+        let source = "oi mug = 5\nbol mug \"ok\"\nmug jod a b lai c\noi mug x 3\n";
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(
+            error_codes(&err),
+            vec![ErrorCode::ExpectedIdentifier, ErrorCode::ExpectedCommaOrLai, ErrorCode::ExpectedEquals]
+        );
+        let lines: Vec<usize> = err
+            .errors()
+            .iter()
+            .map(|e| e.diagnostic().unwrap().span.unwrap().line)
+            .collect();
+        assert_eq!(lines, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn broken_condition_does_not_cascade_to_its_sakiyo() {
+        let source = "yedi rang babaal rato bhane\n  bol mug \"x\"\nsakiyo\noi mug = 1\n";
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(
+            error_codes(&err),
+            vec![ErrorCode::ExpectedStringLiteral, ErrorCode::ExpectedIdentifier]
+        );
+
+        // The skipped block ends at its own `sakiyo`, not a nested one
+        let source = "yedi rang babaal rato bhane
+  yedi rang babaal \"a\" bhane
+  sakiyo
+sakiyo
+sakiyo
+";
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(error_codes(&err), vec![ErrorCode::ExpectedStringLiteral, ErrorCode::UnexpectedToken]);
+        assert_eq!(err.errors()[1].diagnostic().unwrap().span.unwrap().line, 5);
+    }
+
+    #[test]
+    fn recovers_inside_if_body() {
+        let source = "yedi rang babaal \"rato\" bhane\n  bol mug\n  oi mug y 2\nsakiyo\nbol mug y\n";
+        let err = parse_source(source).unwrap_err();
+        assert_eq!(
+            error_codes(&err),
+            vec![ErrorCode::ExpectedPrintable, ErrorCode::ExpectedEquals]
+        );
+    }
+
+    #[test]
+    fn keeps_the_statements_around_errors() {
+        let tokens = crate::lexer::Lexer::new("oi mug x = 1\nbol mug\nbol mug x\n").tokenize().unwrap();
+        let (statements, errors) = Parser::new(tokens).parse_with_errors();
+        assert!(matches!(statements[..], [Spanned { node: Statement::Declaration(..), .. }, Spanned { node: Statement::Print(_), .. }]));
+        assert_eq!(error_codes(&NepalError::Multiple(errors)), vec![ErrorCode::ExpectedPrintable]);
+    }
+
+    #[test]
+    fn records_type_annotations() {
+        let stmts = parse_source("oi mug umer: sankhya = 18\noi mug bhan naam: shabda\nbol mug naam\n").unwrap();
+        let annotations: Vec<(&str, Type)> = stmts
+            .iter()
+            .filter_map(|statement| match &statement.node {
+                Statement::Annotated(annotation, inner) => {
+                    assert_eq!(inner.span, statement.span);
+                    Some((annotation.name.node.as_str(), annotation.kind.node))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(annotations, [("umer", Type::Number), ("naam", Type::String)]);
+        assert!(matches!(&stmts[1].node, Statement::Annotated(_, inner) if matches!(inner.node, Statement::Input(_))));
+
+        let err = parse_source("oi mug umer: number = 18\n").unwrap_err();
+        assert_eq!(error_codes(&err), vec![ErrorCode::ExpectedTypeName]);
+
+        // Input is always a string
+        let source = "oi mug bhan umer: sankhya\n";
+        let err = parse_source(source).unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, ErrorCode::InputAnnotation);
+        let span = diagnostic.span.unwrap();
+        assert_eq!(&source[span.start..span.end], "sankhya");
+    }
+
+    /// `depth` `yedi` blocks, one inside another.
+    fn nested(depth: usize) -> String {
+        "yedi x babaal \"a\" bhane\n".repeat(depth) + "bol mug x\n" + &"sakiyo\n".repeat(depth)
+    }
+
+    #[test]
+    fn limits_how_deep_blocks_nest() {
+        let statements = parse_source(&nested(MAX_NESTING)).unwrap();
+        crate::compiler::Compiler::new().compile(statements).unwrap();
+        crate::lint::lint(&nested(MAX_NESTING), &crate::lint::Config::default()).unwrap();
+
+        let err = parse_source(&format!("{}oi mug = 1\n", nested(MAX_NESTING + 1))).unwrap_err();
+        // Blocks around the deep one still close, so later errors are found
+        assert_eq!(error_codes(&err), vec![ErrorCode::NestingTooDeep, ErrorCode::ExpectedIdentifier]);
+        let diagnostic = err.errors()[0].diagnostic().unwrap();
+        assert_eq!(diagnostic.span.unwrap().line, MAX_NESTING + 1);
+        assert!(diagnostic.message().ends_with("nested more than 100 deep"));
+
+        // Used to recurse once a block and overflow the stack
+        let err = parse_source(&nested(5_000)).unwrap_err();
+        assert_eq!(error_codes(&err), vec![ErrorCode::NestingTooDeep]);
+        let err = parse_source(&"yedi ".repeat(10_000)).unwrap_err();
+        assert_eq!(err.errors().len(), 1);
+    }
+
+    #[test]
+    fn stray_sakiyo_is_reported() {
+        let err = parse_source("bol mug \"a\"\nsakiyo\n").unwrap_err();
+        assert_eq!(error_codes(&err), vec![ErrorCode::UnexpectedToken]);
+    }
+
+    #[test]
+    fn truncated_statements_report_end_of_file() {
+        let err = parse_source("oi mug x =").unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, ErrorCode::UnexpectedEndOfFile);
+        assert_eq!(diagnostic.message(), "unexpected end of file, expected a value");
+        assert_eq!(diagnostic.span.unwrap().start, 10);
+
+        let err = parse_source("bol mug").unwrap_err();
+        assert_eq!(
+            err.diagnostic().unwrap().message(),
+            "unexpected end of file, expected something to print"
+        );
+
+        // Cut off after a `+` or `,`
+        let err = parse_source("bol mug \"a\" +").unwrap_err();
+        assert_eq!(
+            err.diagnostic().unwrap().message(),
+            "unexpected end of file, expected something to print"
+        );
+        for source in ["oi mug x = \"a\" + b +", "oi mug x = jod a,", "oi mug x = ghata a, b,"] {
+            let err = parse_source(source).unwrap_err();
+            let diagnostic = err.diagnostic().unwrap();
+            assert_eq!(diagnostic.code, ErrorCode::UnexpectedEndOfFile, "{}", source);
+            assert_eq!(diagnostic.span.unwrap().start, source.len(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn parses_native_calls() {
+        let stmts = parse_source("mug shesh a, 3 lai r\nmug thulo_akshar \"x\"\nmug samaya lai t\n").unwrap();
+        match &stmts[0].node {
+            Statement::Call(function, args, Some(target)) => {
+                assert_eq!(function, "shesh");
+                assert!(matches!(&args[..], [Argument::Variable(a), Argument::Literal(Value::Number(3))] if a == "a"));
+                assert_eq!(target, "r");
+            }
+            _ => panic!("expected Call with a target"),
+        }
+        assert!(matches!(&stmts[1].node, Statement::Call(_, args, None) if args.len() == 1));
+        assert!(matches!(&stmts[2].node, Statement::Call(_, args, Some(_)) if args.is_empty()));
+
+        let err = parse_source("mug shesh a, lai r").unwrap_err();
+        assert_eq!(error_codes(&err), vec![ErrorCode::ExpectedArgument]);
+    }
+
+    /// Every token a program can contain, with sample payloads.
+    fn token_pool() -> Vec<Token> {
+        vec![
+            Token::OiMug,
+            Token::BolMug,
+            Token::Mug,
+            Token::Identifier("x".into()),
+            Token::Number(7),
+            Token::String("s".into()),
+            Token::Plus,
+            Token::Bhan,
+            Token::Jod,
+            Token::Ghata,
+            Token::Guna,
+            Token::Bhag,
+            Token::Lai,
+            Token::Equals,
+            Token::Colon,
+            Token::Comma,
+            Token::IsEquals,
+            Token::NotEquals,
+            Token::Yedi,
+            Token::Bhane,
+            Token::Sakiyo,
+            Token::Babaal,
+            Token::Laamo,
+            Token::Aile,
+            Token::Feri,
+        ]
+    }
+
+    #[test]
+    fn every_prefix_of_the_samples_parses_without_panicking() {
+        for source in SAMPLES {
+            let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+            for end in 0..=tokens.len() {
+                let _ = Parser::new(tokens[..end].to_vec()).parse();
+            }
+        }
+    }
+
+    #[test]
+    fn random_token_sequences_never_panic() {
+        // xorshift64, seeded so failures are reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let pool = token_pool();
+        for round in 0..20_200 {
+            // The last rounds are long and mostly `yedi`, so they nest deeply
+            let (length, yedi) = if round < 20_000 { (next() % 24, 0) } else { (1_000 + next() % 9_000, 2) };
+            let tokens: Vec<Spanned<Token>> = (0..length as usize)
+                .map(|i| {
+                    let token = match next() % 4 {
+                        n if n < yedi => Token::Yedi,
+                        _ => pool[(next() % pool.len() as u64) as usize].clone(),
+                    };
+                    Spanned::new(token, Span::new(i, i + 1, 1, i))
+                })
+                .collect();
+            if let Err(err) = Parser::new(tokens).parse() {
+                for error in err.errors() {
+                    assert!(error.diagnostic().is_some(), "{:?}", error);
+                }
+            }
+        }
     }
 }
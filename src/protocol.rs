@@ -0,0 +1,80 @@
+//! The base protocol shared by the DAP and LSP servers: JSON messages, each
+//! after a `Content-Length` header and a blank line.
+
+use serde_json::Value as Json;
+use std::io::{self, BufRead, Write};
+
+/// The largest message body read, far more than any program's text; a
+/// longer `Content-Length` is an error rather than an allocation that size.
+const MAX_LENGTH: usize = 64 * 1024 * 1024;
+
+/// The next message from `input`, or `None` at end of input. Headers other
+/// than `Content-Length` are ignored.
+pub(crate) fn read(input: &mut dyn BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.unwrap_or_default();
+    if length > MAX_LENGTH {
+        let error = format!("a message of {} bytes is longer than the limit of {} bytes", length, MAX_LENGTH);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn write(output: &mut dyn Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::console::OutputBuffer;
+    use std::io::Cursor;
+
+    /// `messages` framed as a client would send them.
+    pub(crate) fn frame(messages: &[Json]) -> Cursor<Vec<u8>> {
+        let mut input = Vec::new();
+        for message in messages {
+            write(&mut input, message).unwrap();
+        }
+        Cursor::new(input)
+    }
+
+    /// Every message written to `output`.
+    pub(crate) fn unframe(output: &OutputBuffer) -> Vec<Json> {
+        let mut input = Cursor::new(output.contents().into_bytes());
+        std::iter::from_fn(|| read(&mut input).unwrap()).collect()
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let messages = [serde_json::json!({ "text": "नमस्ते" }), serde_json::json!([1, 2])];
+        let mut input = frame(&messages);
+        assert_eq!(read(&mut input).unwrap().as_ref(), Some(&messages[0]));
+        assert_eq!(read(&mut input).unwrap().as_ref(), Some(&messages[1]));
+        assert_eq!(read(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_lengths_over_the_limit() {
+        let header = format!("Content-Length: {}\r\n\r\n{{}}", usize::MAX);
+        let error = read(&mut Cursor::new(header.into_bytes())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,649 @@
+//! A register-based alternative to the stack [`VM`](crate::vm::VM).
+//!
+//! [`lower`] translates compiled bytecode into instructions that name their
+//! operands and destination directly, so `mug jod a, b lai c` becomes one
+//! `Binary` instruction instead of four stack operations. Each frame holds,
+//! in order:
+//!
+//! 1. one register per variable, empty until the variable is assigned,
+//! 2. one temporary per stack slot the bytecode uses,
+//! 3. the constants the program pushes, filled in before it starts.
+//!
+//! Reads of constants, and of variables already assigned on the way to the
+//! read, are not copied into temporaries; the instruction that consumes the
+//! value reads the register itself. Any other variable is copied where it is
+//! read, so reading it unassigned fails there, as on the stack VM. The stack
+//! depth at every instruction is known from the verifier, so values waiting
+//! on the stack at a jump always live in the same temporaries.
+
+use crate::bytecode::{BytecodeProgram, Interner, Opcode};
+use crate::capability::{Capabilities, Capability};
+use crate::console::Console;
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
+use crate::error::NepalError;
+use crate::limits::{Limits, Meter};
+use crate::native::Natives;
+use crate::span::Span;
+use crate::value::{BinaryOp, Value};
+use crate::verifier;
+use std::collections::HashMap;
+
+/// Index of a register in the frame.
+pub type Register = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterOp {
+    Move { dst: Register, src: Register },
+    Binary { op: BinaryOp, dst: Register, a: Register, b: Register },
+    /// Stores whether `a` and `b` are equal is `expected`
+    Compare { dst: Register, a: Register, b: Register, expected: bool },
+    Print { src: Register },
+    Input { dst: Register },
+    Jump { target: usize },
+    JumpIf { condition: Register, expected: bool, target: usize },
+    /// Calls a native function with the `arg_count` registers from `args`
+    Call { dst: Register, native: usize, args: Register, arg_count: usize },
+    Halt,
+}
+
+impl RegisterOp {
+    /// The register the instruction writes, if any.
+    fn dst_mut(&mut self) -> Option<&mut Register> {
+        match self {
+            RegisterOp::Move { dst, .. }
+            | RegisterOp::Binary { dst, .. }
+            | RegisterOp::Compare { dst, .. }
+            | RegisterOp::Input { dst }
+            | RegisterOp::Call { dst, .. } => Some(dst),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterInstruction {
+    pub op: RegisterOp,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegisterProgram {
+    pub instructions: Vec<RegisterInstruction>,
+    pub variable_names: Interner,
+    pub native_names: Interner,
+    /// Temporaries, which follow the variables
+    pub temporaries: usize,
+    /// Values of the registers after the temporaries
+    pub constants: Vec<Value>,
+}
+
+impl RegisterProgram {
+    /// Whether `register` holds a stack slot, which is read only once.
+    pub fn is_temporary(&self, register: Register) -> bool {
+        let first = self.variable_names.len();
+        (first..first + self.temporaries).contains(&register)
+    }
+}
+
+/// Translates `program`, which must pass bytecode verification. Instructions
+/// that can never run are dropped.
+pub fn lower(program: &BytecodeProgram) -> Result<RegisterProgram, Diagnostic> {
+    verifier::verify(program)?;
+    let depths = verifier::stack_depths(program)?;
+
+    let instructions = &program.instructions;
+    let mut targets = vec![false; instructions.len()];
+    for instruction in instructions {
+        if let Some(target) = instruction.opcode.jump_target() {
+            targets[target] = true;
+        }
+    }
+
+    let variables = program.variable_names.len();
+    let temporaries = depths.iter().flatten().max().map_or(0, |depth| depth + 1);
+    let mut lowering = Lowering {
+        program,
+        out: Vec::new(),
+        stack: Vec::new(),
+        variables,
+        first_constant: variables + temporaries,
+        constants: Vec::new(),
+        constant_registers: HashMap::new(),
+        assigned: vec![0; variables],
+        section: 1,
+        span: Span::default(),
+    };
+
+    // Where each bytecode instruction starts in the output
+    let mut starts = vec![0; instructions.len()];
+    for (offset, instruction) in instructions.iter().enumerate() {
+        starts[offset] = lowering.out.len();
+        let Some(depth) = depths[offset] else { continue };
+        if targets[offset] {
+            // Values arriving by a jump are in their own temporaries
+            lowering.materialize(0);
+            starts[offset] = lowering.out.len();
+            lowering.stack = (0..depth).map(|slot| lowering.temporary(slot)).collect();
+            // Other paths arrive here, perhaps without the assignments above
+            lowering.section += 1;
+        }
+        lowering.span = instruction.span;
+        lowering.lower(instruction.opcode, targets[offset]);
+    }
+
+    let mut out = lowering.out;
+    for instruction in &mut out {
+        if let RegisterOp::Jump { target } | RegisterOp::JumpIf { target, .. } = &mut instruction.op {
+            *target = starts[*target];
+        }
+    }
+    Ok(RegisterProgram {
+        instructions: out,
+        variable_names: program.variable_names.clone(),
+        native_names: program.native_names.clone(),
+        temporaries,
+        constants: lowering.constants,
+    })
+}
+
+/// Where the values of the bytecode's stack live while it is translated.
+struct Lowering<'a> {
+    program: &'a BytecodeProgram,
+    out: Vec<RegisterInstruction>,
+    /// The register holding each stack slot: its own temporary, or the
+    /// variable or constant that was pushed
+    stack: Vec<Register>,
+    variables: usize,
+    first_constant: Register,
+    constants: Vec<Value>,
+    constant_registers: HashMap<Opcode, Register>,
+    /// The section in which each variable was last assigned
+    assigned: Vec<usize>,
+    /// Counts the runs of instructions between jump targets, which are only
+    /// entered from the top
+    section: usize,
+    span: Span,
+}
+
+impl Lowering<'_> {
+    fn temporary(&self, slot: usize) -> Register {
+        self.variables + slot
+    }
+
+    fn is_temporary(&self, register: Register) -> bool {
+        (self.variables..self.first_constant).contains(&register)
+    }
+
+    fn emit(&mut self, op: RegisterOp) {
+        self.out.push(RegisterInstruction { op, span: self.span });
+    }
+
+    /// Moves the stack slots from `from` up into their own temporaries.
+    fn materialize(&mut self, from: usize) {
+        for slot in from..self.stack.len() {
+            let temporary = self.temporary(slot);
+            if self.stack[slot] != temporary {
+                self.emit(RegisterOp::Move { dst: temporary, src: self.stack[slot] });
+                self.stack[slot] = temporary;
+            }
+        }
+    }
+
+    /// The register holding the value `opcode` pushes.
+    fn constant(&mut self, opcode: Opcode) -> Register {
+        if let Some(&register) = self.constant_registers.get(&opcode) {
+            return register;
+        }
+        let value = match opcode {
+            Opcode::PushNumber(n) => Value::Number(n),
+            Opcode::PushString(index) => Value::from(self.program.string_pool[index].as_str()),
+            Opcode::PushVariable(index) => Value::from(self.program.variable_names[index].as_str()),
+            _ => unreachable!("{:?} does not push a constant", opcode),
+        };
+        let register = self.first_constant + self.constants.len();
+        self.constants.push(value);
+        self.constant_registers.insert(opcode, register);
+        register
+    }
+
+    /// The temporary for a value pushed on top of the stack.
+    fn push_temporary(&mut self) -> Register {
+        let register = self.temporary(self.stack.len());
+        self.stack.push(register);
+        register
+    }
+
+    fn pop(&mut self) -> Register {
+        self.stack.pop().expect("the verifier checked the stack depth")
+    }
+
+    fn lower(&mut self, opcode: Opcode, at_target: bool) {
+        match opcode {
+            Opcode::PushNumber(_) | Opcode::PushString(_) | Opcode::PushVariable(_) => {
+                let register = self.constant(opcode);
+                self.stack.push(register);
+            }
+            Opcode::LoadVariable(index) if self.assigned[index] == self.section => self.stack.push(index),
+            Opcode::LoadVariable(index) => {
+                // The variable may be unassigned, which must fail here
+                let dst = self.push_temporary();
+                self.emit(RegisterOp::Move { dst, src: index });
+            }
+            Opcode::Pop => {
+                self.pop();
+            }
+            Opcode::StoreVariable(index) => {
+                let src = self.pop();
+                self.assigned[index] = self.section;
+                // Slots still waiting on the old value must keep it
+                for slot in 0..self.stack.len() {
+                    if self.stack[slot] == index {
+                        let dst = self.temporary(slot);
+                        self.emit(RegisterOp::Move { dst, src: index });
+                        self.stack[slot] = dst;
+                    }
+                }
+                // Write the result straight into the variable when possible
+                if self.is_temporary(src) && !at_target {
+                    if let Some(dst) = self.out.last_mut().and_then(|last| last.op.dst_mut()) {
+                        if *dst == src {
+                            *dst = index;
+                            return;
+                        }
+                    }
+                }
+                self.emit(RegisterOp::Move { dst: index, src });
+            }
+            Opcode::Add => self.binary(BinaryOp::Add),
+            Opcode::Subtract => self.binary(BinaryOp::Subtract),
+            Opcode::Multiply => self.binary(BinaryOp::Multiply),
+            Opcode::Divide => self.binary(BinaryOp::Divide),
+            Opcode::Concat => self.binary(BinaryOp::Concat),
+            Opcode::Equal | Opcode::NotEqual => {
+                let b = self.pop();
+                let a = self.pop();
+                let dst = self.push_temporary();
+                self.emit(RegisterOp::Compare { dst, a, b, expected: opcode == Opcode::Equal });
+            }
+            Opcode::Print => {
+                let src = self.pop();
+                self.emit(RegisterOp::Print { src });
+            }
+            Opcode::Input => {
+                let dst = self.push_temporary();
+                self.emit(RegisterOp::Input { dst });
+            }
+            Opcode::JumpIfTrue(target) | Opcode::JumpIfFalse(target) => {
+                let condition = self.pop();
+                self.materialize(0);
+                let expected = matches!(opcode, Opcode::JumpIfTrue(_));
+                self.emit(RegisterOp::JumpIf { condition, expected, target });
+            }
+            Opcode::Jump(target) => {
+                self.materialize(0);
+                self.emit(RegisterOp::Jump { target });
+                self.stack.clear();
+            }
+            Opcode::CallNative(native, arg_count) => {
+                // Arguments must be in consecutive registers
+                let base = self.stack.len() - arg_count;
+                self.materialize(base);
+                self.stack.truncate(base);
+                let dst = self.push_temporary();
+                self.emit(RegisterOp::Call { dst, native, args: dst, arg_count });
+            }
+            Opcode::Return => {}
+            Opcode::Halt => {
+                self.emit(RegisterOp::Halt);
+                self.stack.clear();
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp) {
+        let b = self.pop();
+        let a = self.pop();
+        let dst = self.push_temporary();
+        self.emit(RegisterOp::Binary { op, dst, a, b });
+    }
+}
+
+/// Runs a [`RegisterProgram`]. Programs behave exactly as they do on the
+/// stack VM, including their errors.
+pub struct RegisterVM {
+    program: RegisterProgram,
+    registers: Vec<Option<Value>>,
+    console: Console,
+    natives: Natives,
+    capabilities: Capabilities,
+    meter: Meter,
+}
+
+impl RegisterVM {
+    pub fn new(program: RegisterProgram) -> Self {
+        let mut registers = vec![None; program.variable_names.len() + program.temporaries];
+        registers.extend(program.constants.iter().cloned().map(Some));
+        Self {
+            program,
+            registers,
+            console: Console::stdio(),
+            natives: Natives::stdlib(),
+            capabilities: Capabilities::default(),
+            meter: Meter::new(Limits::default()),
+        }
+    }
+
+    /// Resolves native calls in `natives` instead of the standard library.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    /// Runs the program against `console` instead of standard input and output.
+    pub fn with_console(mut self, console: Console) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Lets the program do only what `capabilities` grants.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Stops the program with a `ResourceLimit` error if it goes over `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.meter = Meter::new(limits);
+        self
+    }
+
+    pub fn run(&mut self) -> Result<(), NepalError> {
+        let Self { program, registers, console, natives, capabilities, meter } = self;
+        meter.restart();
+        let mut ip = 0;
+        while let Some(instruction) = program.instructions.get(ip) {
+            ip += 1;
+            let locate = |diagnostic: Diagnostic| NepalError::from(diagnostic.with_span(instruction.span));
+            meter.step().map_err(locate)?;
+            match instruction.op {
+                RegisterOp::Move { dst, src } => {
+                    let value = read(program, registers, src).map_err(locate)?.clone();
+                    if program.is_temporary(src) {
+                        // Leave the moved text unshared so it can grow in place
+                        registers[src] = None;
+                    }
+                    write(program, registers, meter, dst, value).map_err(locate)?;
+                }
+                RegisterOp::Binary { op, dst, a, b } => {
+                    let left = read(program, registers, a).map_err(locate)?;
+                    let right = read(program, registers, b).map_err(locate)?;
+                    // Text is appended in place when `a` is not needed
+                    // afterwards: it is overwritten or was a temporary
+                    let reuse = a != b && (a == dst || program.is_temporary(a));
+                    let result = if reuse && matches!(left, Value::String(_)) && op.appends(right) {
+                        let left = registers[a].take().expect("the register was just read");
+                        if a < program.variable_names.len() {
+                            meter.release(&left);
+                        }
+                        op.apply_owned(left, read(program, registers, b).map_err(locate)?)
+                    } else {
+                        op.apply(left, right)
+                    };
+                    write(program, registers, meter, dst, result.map_err(locate)?).map_err(locate)?;
+                }
+                RegisterOp::Compare { dst, a, b, expected } => {
+                    let a = read(program, registers, a).map_err(locate)?;
+                    let b = read(program, registers, b).map_err(locate)?;
+                    let equal = a.equals(b).map_err(locate)?;
+                    write(program, registers, meter, dst, Value::Boolean(equal == expected)).map_err(locate)?;
+                }
+                RegisterOp::Print { src } => {
+                    let value = read(program, registers, src).map_err(locate)?;
+                    capabilities.check(&Capability::Stdout).map_err(locate)?;
+                    console.print_line(value).map_err(locate)?;
+                }
+                RegisterOp::Input { dst } => {
+                    capabilities.check(&Capability::Stdin).map_err(locate)?;
+                    let input = console.read_line().map_err(locate)?;
+                    write(program, registers, meter, dst, Value::from(input)).map_err(locate)?;
+                }
+                RegisterOp::Jump { target } => ip = target,
+                RegisterOp::JumpIf { condition, expected, target } => {
+                    match read(program, registers, condition).map_err(locate)? {
+                        Value::Boolean(value) => {
+                            if *value == expected {
+                                ip = target;
+                            }
+                        }
+                        other => {
+                            return Err(locate(
+                                Diagnostic::new(ErrorCode::ConditionNotBoolean).with_word(other.type_name()),
+                            ));
+                        }
+                    }
+                }
+                RegisterOp::Call { dst, native, args, arg_count } => {
+                    let args = (args..args + arg_count)
+                        .map(|register| read(program, registers, register).cloned())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(locate)?;
+                    let result = natives.call(&program.native_names[native], args, capabilities).map_err(locate)?;
+                    write(program, registers, meter, dst, result).map_err(locate)?;
+                }
+                RegisterOp::Halt => break,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn program(&self) -> &RegisterProgram {
+        &self.program
+    }
+
+    /// The value of the variable `name`, if the program has assigned it.
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        let index = self.program.variable_names.lookup(name)?;
+        self.registers[index].as_ref()
+    }
+
+    /// Gives back the console so it can be used for the next program.
+    pub fn into_console(self) -> Console {
+        self.console
+    }
+}
+
+/// Writes `value` to `dst`, accounting for the limits.
+#[inline]
+fn write(
+    program: &RegisterProgram,
+    registers: &mut [Option<Value>],
+    meter: &mut Meter,
+    dst: Register,
+    value: Value,
+) -> Result<(), Diagnostic> {
+    if dst < program.variable_names.len() {
+        meter.assign(registers[dst].as_ref(), &value)?;
+    } else if program.is_temporary(dst) {
+        meter.stack(dst - program.variable_names.len() + 1)?;
+    }
+    registers[dst] = Some(value);
+    Ok(())
+}
+
+/// The value in `register`, or the error for reading it before it is set.
+fn read<'a>(program: &RegisterProgram, registers: &'a [Option<Value>], register: Register) -> Result<&'a Value, Diagnostic> {
+    match &registers[register] {
+        Some(value) => Ok(value),
+        None if register < program.variable_names.len() => {
+            Err(Diagnostic::new(ErrorCode::UndefinedVariable).with_arg(&program.variable_names[register]))
+        }
+        None => Err(Diagnostic::new(ErrorCode::StackUnderflow).with_note(Note::InternalError)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::samples::{SAMPLES, SAMPLE_INPUTS};
+    use crate::vm::VM;
+
+    fn compile(source: &str) -> BytecodeProgram {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap()
+    }
+
+    /// Output and error of `program` on both VMs, which must agree.
+    fn run_both(program: &BytecodeProgram, input: &str) -> (String, Option<Diagnostic>) {
+        let (console, output) = Console::memory(input);
+        let stack = VM::new(program.clone()).with_console(console).with_capabilities(Capabilities::console()).run().err();
+        let stack = (output.contents(), stack.and_then(|e| e.diagnostic().cloned()));
+
+        let (console, output) = Console::memory(input);
+        let register = RegisterVM::new(lower(program).unwrap())
+            .with_console(console)
+            .with_capabilities(Capabilities::console())
+            .run()
+            .err();
+        let register = (output.contents(), register.and_then(|e| e.diagnostic().cloned()));
+        assert_eq!(format!("{:?}", stack), format!("{:?}", register));
+        register
+    }
+
+    #[test]
+    fn arithmetic_writes_straight_into_variables() {
+        let program = lower(&compile("oi mug a = 2\noi mug b = 3\nmug jod a, b lai c")).unwrap();
+        let ops: Vec<RegisterOp> = program.instructions.iter().map(|i| i.op).collect();
+        let constant = program.variable_names.len() + program.temporaries;
+        assert_eq!(
+            ops,
+            [
+                RegisterOp::Move { dst: 0, src: constant },
+                RegisterOp::Move { dst: 1, src: constant + 1 },
+                RegisterOp::Binary { op: BinaryOp::Add, dst: 2, a: 0, b: 1 },
+                RegisterOp::Halt,
+            ]
+        );
+        let mut vm = RegisterVM::new(program);
+        vm.run().unwrap();
+        assert_eq!(vm.get_global("c"), Some(&Value::Number(5)));
+    }
+
+    #[test]
+    fn samples_behave_as_on_the_stack_vm() {
+        for source in SAMPLES {
+            for input in SAMPLE_INPUTS {
+                run_both(&compile(source), input);
+            }
+        }
+    }
+
+    #[test]
+    fn errors_match_the_stack_vm() {
+        let (output, error) = run_both(&compile("bol mug \"x\"\nmug jod a, zero lai a"), "");
+        assert_eq!(output, "x\n");
+        let error = error.unwrap();
+        assert_eq!(error.code, ErrorCode::UndefinedVariable);
+        assert_eq!(error.span.unwrap().line, 2);
+
+        let (_, error) = run_both(&compile("oi mug a = 1\nyedi a babaal \"1\" bhane\nsakiyo"), "");
+        assert_eq!(error.unwrap().code, ErrorCode::InvalidComparison);
+        let (_, error) = run_both(&compile("oi mug a = 0\nmug bhag a, a lai a"), "");
+        assert_eq!(error.unwrap().code, ErrorCode::DivisionByZero);
+    }
+
+    #[test]
+    fn unassigned_variables_fail_where_they_are_read() {
+        let source = "oi mug b = jod c\noi mug naam = b + \"rato\"";
+        let (_, error) = run_both(&crate::optimizer::optimize(compile(source)), "");
+        let error = error.unwrap();
+        assert_eq!(error.code, ErrorCode::UndefinedVariable);
+        assert_eq!((error.span.unwrap().line, error.span.unwrap().column), (1, 0));
+
+        // Nothing is asked for before the read fails
+        let mut program = BytecodeProgram::new();
+        let a = program.add_variable("a".into());
+        for opcode in [Opcode::LoadVariable(a), Opcode::Input, Opcode::Concat, Opcode::Print, Opcode::Halt] {
+            program.add_instruction(opcode, Span::default());
+        }
+        let (output, error) = run_both(&program, "rato\n");
+        assert_eq!(output, "");
+        assert_eq!(error.unwrap().code, ErrorCode::UndefinedVariable);
+    }
+
+    #[test]
+    fn appending_in_place_keeps_earlier_copies() {
+        let source = "oi mug a = \"x\"\nmug jod a lai b\noi mug a = a + \"y\"\noi mug a = a + a\nbol mug a\nbol mug b";
+        let (output, error) = run_both(&compile(source), "");
+        assert_eq!(output, "xyxy\nx\n");
+        assert!(error.is_none());
+    }
+
+    #[test]
+    fn refuses_what_was_not_granted() {
+        let program = lower(&compile("mug samay lai t\nbol mug t")).unwrap();
+        let err = RegisterVM::new(program.clone()).with_console(Console::memory("").0).run().unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().message(), "permission denied: this needs the `clock` capability");
+
+        let clock = Capabilities::none().with_clock();
+        let mut vm = RegisterVM::new(program).with_console(Console::memory("").0).with_capabilities(clock);
+        let err = vm.run().unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().message(), "permission denied: this needs the `stdout` capability");
+        assert_eq!(err.diagnostic().unwrap().span.unwrap().line, 2);
+    }
+
+    #[test]
+    fn stops_at_resource_limits() {
+        let source = "oi mug s = \"ab\"\noi mug s = s + s\noi mug s = s + s\noi mug t = s";
+        let program = lower(&compile(source)).unwrap();
+        let err = RegisterVM::new(program.clone()).with_limits(Limits::new().with_max_heap(8)).run().unwrap_err();
+        assert!(matches!(err, NepalError::ResourceLimit(_)));
+        assert_eq!(err.diagnostic().unwrap().span.unwrap().line, 4);
+        // Appending in place frees the old text
+        RegisterVM::new(program.clone()).with_limits(Limits::new().with_max_heap(9)).run().unwrap();
+
+        let err = RegisterVM::new(program).with_limits(Limits::new().with_max_steps(2)).run().unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().span.unwrap().line, 3);
+    }
+
+    #[test]
+    fn runs_loops_and_keeps_values_read_before_a_store() {
+        // i = 0; s = 0; loop { s = s + i; i = i + 1 } while i != 10
+        let mut program = BytecodeProgram::new();
+        let (i, s) = (program.add_variable("i".into()), program.add_variable("s".into()));
+        let span = Span::default();
+        for opcode in [
+            Opcode::PushNumber(0),
+            Opcode::StoreVariable(i),
+            Opcode::PushNumber(0),
+            Opcode::StoreVariable(s),
+            Opcode::LoadVariable(s),
+            Opcode::LoadVariable(i),
+            Opcode::Add,
+            Opcode::StoreVariable(s),
+            Opcode::LoadVariable(i),
+            Opcode::PushNumber(1),
+            Opcode::Add,
+            Opcode::StoreVariable(i),
+            Opcode::LoadVariable(i),
+            Opcode::PushNumber(10),
+            Opcode::NotEqual,
+            Opcode::JumpIfTrue(4),
+            // Prints the old `s` although `s` changes before the print
+            Opcode::LoadVariable(s),
+            Opcode::PushNumber(7),
+            Opcode::StoreVariable(s),
+            Opcode::Print,
+            Opcode::LoadVariable(s),
+            Opcode::Print,
+            Opcode::Halt,
+        ] {
+            program.add_instruction(opcode, span);
+        }
+        let (output, error) = run_both(&program, "");
+        assert_eq!(output, "45\n7\n");
+        assert!(error.is_none());
+    }
+}
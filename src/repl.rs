@@ -0,0 +1,198 @@
+//! State behind `lyangpiler repl`.
+//!
+//! Each entry is compiled onto the end of the program that is already loaded,
+//! so it shares the VM's variable table and sees every earlier assignment.
+
+use crate::bytecode::BytecodeProgram;
+use crate::capability::Capabilities;
+use crate::compiler::Compiler;
+use crate::console::Console;
+use crate::diagnostic::ErrorCode;
+use crate::error::NepalError;
+use crate::lexer::Lexer;
+use crate::native::Natives;
+use crate::parser::Parser;
+use crate::token::Token;
+use crate::value::Value;
+use crate::vm::VM;
+
+/// A VM that keeps its variables from one entry to the next.
+pub struct Session {
+    vm: VM,
+    natives: Natives,
+    capabilities: Capabilities,
+}
+
+impl Session {
+    /// A session that uses standard input and output.
+    pub fn new() -> Self {
+        Self::with_console(Console::stdio())
+    }
+
+    pub fn with_console(console: Console) -> Self {
+        let natives = Natives::stdlib();
+        let capabilities = Capabilities::none();
+        Self {
+            vm: Self::empty_vm(console, &natives, &capabilities),
+            natives,
+            capabilities,
+        }
+    }
+
+    /// Lets entries do only what `capabilities` grants, which is nothing by default.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.vm = self.vm.with_capabilities(capabilities.clone());
+        self.capabilities = capabilities;
+        self
+    }
+
+    fn empty_vm(console: Console, natives: &Natives, capabilities: &Capabilities) -> VM {
+        VM::new(BytecodeProgram::new())
+            .with_console(console)
+            .with_natives(natives.clone())
+            .with_capabilities(capabilities.clone())
+    }
+
+    /// Compiles `source` against the loaded program and runs it. Nothing is
+    /// run if it does not compile; if it fails part way, assignments made
+    /// before the error are kept.
+    pub fn eval(&mut self, source: &str) -> Result<(), NepalError> {
+        let tokens = Lexer::new(source).tokenize()?;
+        let statements = Parser::new(tokens).parse()?;
+        let program = Compiler::new()
+            .with_natives(self.natives.clone())
+            .with_program(self.vm.program().clone())
+            .compile(statements)?;
+        self.vm.extend(program);
+        self.vm.run()
+    }
+
+    /// Forgets every variable and all compiled code.
+    pub fn reset(&mut self) {
+        let vm = std::mem::replace(&mut self.vm, VM::new(BytecodeProgram::new()));
+        self.vm = Self::empty_vm(vm.into_console(), &self.natives, &self.capabilities);
+    }
+
+    /// One `name = value` line per assigned variable, in the order they were
+    /// first used. Strings are quoted.
+    pub fn vars(&self) -> Vec<String> {
+        self.vm
+            .globals()
+            .map(|(name, value)| match value {
+                Value::String(s) => format!("{} = {:?}", name, s),
+                other => format!("{} = {}", name, other),
+            })
+            .collect()
+    }
+
+    /// Every instruction compiled so far, one per line.
+    pub fn bytecode(&self) -> Vec<String> {
+        self.vm
+            .program()
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| format!("{:04}  {:?}", index, instruction.opcode))
+            .collect()
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `source` stops inside a block comment, a `yedi ... sakiyo` block
+/// or a statement, so the REPL should read another line before running it.
+pub fn needs_more_input(source: &str) -> bool {
+    let tokens = match Lexer::new(source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            return error
+                .errors()
+                .iter()
+                .any(|e| e.diagnostic().is_some_and(|d| d.code == ErrorCode::UnterminatedBlockComment));
+        }
+    };
+
+    let mut depth = 0usize;
+    for token in &tokens {
+        match token.node {
+            Token::Yedi => depth += 1,
+            // `aile` closes the block before it and opens its own
+            Token::Aile if depth == 0 => depth += 1,
+            Token::Sakiyo => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+
+    match Parser::new(tokens).parse() {
+        Ok(_) => false,
+        Err(error) => error
+            .errors()
+            .iter()
+            .any(|e| e.diagnostic().is_some_and(|d| d.code == ErrorCode::UnexpectedEndOfFile)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::OutputBuffer;
+
+    fn session() -> (Session, OutputBuffer) {
+        let (console, output) = Console::memory("");
+        (Session::with_console(console).with_capabilities(Capabilities::console()), output)
+    }
+
+    #[test]
+    fn entries_share_variables() {
+        let (mut session, output) = session();
+        session.eval("oi mug a = 2").unwrap();
+        session.eval("oi mug naam = \"Sita\"").unwrap();
+        session.eval("mug jod a, a lai b\nbol mug b").unwrap();
+        assert_eq!(output.contents(), "4\n");
+        assert_eq!(session.vars(), ["a = 2", "naam = \"Sita\"", "b = 4"]);
+    }
+
+    #[test]
+    fn failed_entries_keep_the_session_usable() {
+        let (mut session, output) = session();
+        session.eval("oi mug a = 1").unwrap();
+        let instructions = session.bytecode().len();
+        assert!(session.eval("oi mug b = guna").is_err());
+        assert_eq!(session.bytecode().len(), instructions);
+
+        let err = session.eval("oi mug c = 5\nmug bhag c, zero lai d").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::UndefinedVariable);
+        session.eval("bol mug \"a=\" + a + \" c=\" + c").unwrap();
+        assert_eq!(output.contents(), "a=1 c=5\n");
+    }
+
+    #[test]
+    fn reset_forgets_everything() {
+        let (mut session, _) = session();
+        session.eval("oi mug a = 1").unwrap();
+        session.reset();
+        assert!(session.vars().is_empty());
+        assert!(session.bytecode().is_empty());
+        let err = session.eval("bol mug a").unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::UndefinedVariable);
+    }
+
+    #[test]
+    fn detects_unfinished_entries() {
+        assert!(needs_more_input("yedi a babaal \"x\" bhane"));
+        assert!(needs_more_input("yedi a babaal \"x\" bhane\nbol mug a\naile feri a babaal \"y\" bhane"));
+        assert!(needs_more_input("oi mug a ="));
+        assert!(needs_more_input("/* comment"));
+        assert!(!needs_more_input("yedi a babaal \"x\" bhane\nbol mug a\nsakiyo"));
+        assert!(!needs_more_input("oi mug a = 1"));
+        assert!(!needs_more_input("oi mug = 1"));
+        assert!(!needs_more_input(""));
+    }
+}
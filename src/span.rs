@@ -0,0 +1,91 @@
+//! Source locations shared by tokens, AST nodes, bytecode and diagnostics.
+
+use std::fmt;
+use std::ops::Deref;
+
+/// A region of source text.
+///
+/// `start` and `end` are byte offsets into the source; `line` (1-based) and
+/// `column` (0-based, in characters) describe where the span starts.
+/// A span with `line == 0` has no known location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self { start, end, line, column }
+    }
+
+    /// Whether this span points at real source text.
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+
+    /// Smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        if !self.is_known() {
+            return other;
+        }
+        if !other.is_known() {
+            return self;
+        }
+        let first = if self.start <= other.start { self } else { other };
+        Span {
+            start: first.start,
+            end: self.end.max(other.end),
+            line: first.line,
+            column: first.column,
+        }
+    }
+}
+
+/// A value together with the source span it came from.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Self { node, span }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    /// Wraps a value that has no source location, e.g. one built in a test.
+    fn from(node: T) -> Self {
+        Self { node, span: Span::default() }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl From<&str> for Spanned<String> {
+    fn from(name: &str) -> Self {
+        Self::from(name.to_string())
+    }
+}
+
+impl PartialEq<str> for Spanned<String> {
+    fn eq(&self, other: &str) -> bool {
+        self.node == other
+    }
+}
+
+impl fmt::Display for Spanned<String> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.node)
+    }
+}
@@ -0,0 +1,125 @@
+//! Where each variable is given a value and read, and the types it can
+//! hold, for editor features such as hover and go-to-definition.
+//!
+//! Types are those [inferred](crate::infer) for the linter and
+//! `check --types`, so all three agree.
+
+use crate::ast::Statement;
+use crate::infer::Inference;
+use crate::native::Type;
+use crate::span::{Span, Spanned};
+use std::collections::HashMap;
+
+pub use crate::infer::{Access, Occurrence};
+
+/// A variable that is given a value somewhere in the program.
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    /// The name where it is first given a value
+    pub definition: Span,
+    /// The statement that first gives it a value
+    pub statement: Span,
+    /// Every type it is given, in the order first seen
+    pub types: Vec<Type>,
+}
+
+/// The variables of a program and every place a name appears.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    variables: Vec<Variable>,
+    /// Variables by name
+    index: HashMap<String, usize>,
+    /// In source order
+    occurrences: Vec<Occurrence>,
+}
+
+impl Symbols {
+    pub fn new(statements: &[Spanned<Statement>]) -> Self {
+        let mut symbols = Self::default();
+        let occurrences = Inference::new(statements).occurrences().to_vec();
+        for occurrence in &occurrences {
+            if let Some(kind) = occurrence.given {
+                symbols.write(occurrence, kind);
+            }
+        }
+        symbols.occurrences = occurrences;
+        symbols.occurrences.sort_by_key(|occurrence| occurrence.span.start);
+        symbols
+    }
+
+    /// Variables in the order they are first given a value.
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.index.get(name).map(|&i| &self.variables[i])
+    }
+
+    /// The name at byte `offset`, including just after its last character.
+    pub fn at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end)
+    }
+
+    fn write(&mut self, occurrence: &Occurrence, kind: Type) {
+        match self.index.get(&occurrence.name) {
+            Some(&i) if !self.variables[i].types.contains(&kind) => self.variables[i].types.push(kind),
+            Some(_) => {}
+            None => {
+                self.index.insert(occurrence.name.clone(), self.variables.len());
+                self.variables.push(Variable {
+                    name: occurrence.name.clone(),
+                    definition: occurrence.span,
+                    statement: occurrence.statement,
+                    types: vec![kind],
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn symbols(source: &str) -> Symbols {
+        Symbols::new(&Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap())
+    }
+
+    #[test]
+    fn finds_definitions_uses_and_types() {
+        let source = "\
+oi mug bhan naam
+oi mug a = 2
+mug jod a, a lai b
+mug jod naam, a lai c
+yedi naam babaal \"Sita\" bhane
+  oi mug b = \"dui\"
+  mug lambai naam lai n
+sakiyo
+";
+        let symbols = symbols(source);
+        let names: Vec<&str> = symbols.variables().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["naam", "a", "b", "c", "n"]);
+        let types = |name| symbols.variable(name).unwrap().types.clone();
+        assert_eq!(types("b"), [Type::Number, Type::String]);
+        assert_eq!(types("c"), [Type::String]);
+        assert_eq!(types("n"), [Type::Any]);
+
+        let b = symbols.variable("b").unwrap();
+        assert_eq!(&source[b.definition.start..b.definition.end], "b");
+        assert_eq!(&source[b.statement.start..b.statement.end], "mug jod a, a lai b");
+
+        // The `naam` in `mug lambai naam` is read; `lambai` is called
+        let offset = source.find("lambai").unwrap();
+        assert_eq!(symbols.at(offset).unwrap().access, Access::Call);
+        assert_eq!(symbols.at(offset + "lambai ".len()).unwrap().access, Access::Read);
+        let reads = symbols.occurrences.iter().filter(|o| o.name == "naam" && o.access == Access::Read).count();
+        assert_eq!(reads, 3);
+    }
+}
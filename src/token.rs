@@ -15,6 +15,8 @@ pub enum Token {
     Lai,
     Equals,
     Comma,
+    /// Before the type in `oi mug umer: sankhya = 18`
+    Colon,
     IsEquals,
     NotEquals,
     Yedi,     // Replace If with Yedi
@@ -25,3 +27,57 @@ pub enum Token {
     Aile,  // New: for else if
     Feri,  // New: for "feri" keyword
 }
+
+/// A token, or source text that [`Lexer::tokenize`](crate::lexer::Lexer::tokenize)
+/// drops but tools such as the formatter must keep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lexeme {
+    Token(Token),
+    /// A `//`, `#` or `/* */` comment as written
+    Comment(String),
+    /// The end of a line; other whitespace is not kept
+    Newline,
+}
+
+impl std::fmt::Display for Token {
+    /// Shows the token as it appears in diagnostics, e.g. "`bol mug`" or "\"rato\"".
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "`{}`", name),
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::String(s) => write!(f, "\"{}\"", s),
+            other => write!(f, "`{}`", other.keyword()),
+        }
+    }
+}
+
+impl Token {
+    /// Source spelling of keyword and punctuation tokens.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Token::OiMug => "oi mug",
+            Token::BolMug => "bol mug",
+            Token::Mug => "mug",
+            Token::Plus => "+",
+            Token::Bhan => "bhan",
+            Token::Jod => "jod",
+            Token::Ghata => "ghata",
+            Token::Guna => "guna",
+            Token::Bhag => "bhag",
+            Token::Lai => "lai",
+            Token::Equals => "=",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::IsEquals => "==",
+            Token::NotEquals => "!=",
+            Token::Yedi => "yedi",
+            Token::Bhane => "bhane",
+            Token::Sakiyo => "sakiyo",
+            Token::Babaal => "babaal",
+            Token::Laamo => "laamo",
+            Token::Aile => "aile",
+            Token::Feri => "feri",
+            Token::Identifier(_) | Token::Number(_) | Token::String(_) => "",
+        }
+    }
+}
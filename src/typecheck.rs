@@ -0,0 +1,253 @@
+//! Static types for `lyangpiler check --types`: values used as the wrong
+//! type, found before the program runs rather than when the VM gets there.
+//!
+//! Each variable's type is [inferred](crate::infer) in program order from
+//! what it is given, as the linter does. A variable [annotated](Annotation)
+//! `sankhya` or `shabda` always has that type, and giving it another is an
+//! error.
+//!
+//! Only certain mismatches are reported; a value of any type is never one.
+
+use crate::ast::{Annotation, Argument, Condition, Ident, Statement, Value};
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
+use crate::error::NepalError;
+use crate::infer::{Inference, Known};
+use crate::native::{Natives, Type};
+use crate::span::{Span, Spanned};
+
+/// Checks the types in a parsed program, reporting every mismatch found.
+pub fn check(statements: &[Spanned<Statement>], natives: &Natives) -> Result<(), NepalError> {
+    let mut checker = Checker {
+        natives,
+        inference: Inference::new(statements),
+        errors: Vec::new(),
+    };
+    checker.block(statements);
+
+    let mut errors = checker.errors;
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(NepalError::Multiple(errors)),
+    }
+}
+
+struct Checker<'a> {
+    natives: &'a Natives,
+    inference: Inference,
+    errors: Vec<NepalError>,
+}
+
+impl Checker<'_> {
+    fn block(&mut self, statements: &[Spanned<Statement>]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Spanned<Statement>) {
+        match &statement.node {
+            Statement::Declaration(name, _) | Statement::Input(name) | Statement::Addition(name, _) => self.write(name),
+            Statement::Subtraction(target, sources) => self.arithmetic("ghata", target, sources),
+            Statement::Multiplication(target, sources) => self.arithmetic("guna", target, sources),
+            Statement::Division(target, sources) => self.arithmetic("bhag", target, sources),
+            Statement::StringConcat(target, _) => self.write(target),
+            Statement::Print(_) | Statement::PrintString(_) => {}
+            Statement::Call(function, args, target) => {
+                self.call(function, args, statement.span);
+                if let Some(target) = target {
+                    self.write(target);
+                }
+            }
+            Statement::If(condition, body, else_branch) => {
+                let (Condition::Equals(name, literal) | Condition::NotEquals(name, literal)) = condition;
+                if let Some(known) = self.known(name).filter(|known| known.kind == Type::Number) {
+                    let diagnostic = Diagnostic::new(ErrorCode::NumberComparedToString)
+                        .with_arg(&name.node)
+                        .with_arg(format!("\"{}\"", literal))
+                        .with_span(name.span)
+                        .with_label(known.origin, Note::TypeGivenHere);
+                    self.errors.push(diagnostic.into());
+                }
+                self.block(body);
+                if let Some(other) = else_branch {
+                    self.statement(other);
+                }
+            }
+            Statement::Annotated(_, statement) => self.statement(statement),
+        }
+    }
+
+    /// `ghata`, `guna` and `bhag`, which only work on numbers.
+    fn arithmetic(&mut self, keyword: &str, target: &Ident, sources: &[Ident]) {
+        for source in sources {
+            let Some(known) = self.known(source) else {
+                continue;
+            };
+            if let (Type::String | Type::Boolean, Some(word)) = (known.kind, known.kind.word()) {
+                let diagnostic = Diagnostic::new(ErrorCode::OperandType)
+                    .with_arg(keyword)
+                    .with_arg(&source.node)
+                    .with_word(word)
+                    .with_span(source.span)
+                    .with_label(known.origin, Note::TypeGivenHere);
+                self.errors.push(diagnostic.into());
+            }
+        }
+        self.write(target);
+    }
+
+    fn call(&mut self, function: &Ident, args: &[Argument], span: Span) {
+        let Some(params) = self.natives.params(&function.node) else {
+            // Unknown functions are the compiler's to report
+            return;
+        };
+        for (position, (&param, arg)) in params.iter().zip(args).enumerate() {
+            let (kind, arg_span, origin) = match arg {
+                Argument::Variable(name) => match self.known(name) {
+                    Some(known) => (known.kind, name.span, known.origin),
+                    None => continue,
+                },
+                Argument::Literal(Value::Number(_)) => (Type::Number, span, Span::default()),
+                Argument::Literal(Value::String(_)) => (Type::String, span, Span::default()),
+            };
+            let (Some(expected), Some(found)) = (param.word(), kind.word()) else {
+                continue;
+            };
+            if param != kind {
+                let diagnostic = Diagnostic::new(ErrorCode::ArgumentType)
+                    .with_arg(position + 1)
+                    .with_arg(&function.node)
+                    .with_word(expected)
+                    .with_word(found)
+                    .with_span(arg_span)
+                    .with_label(origin, Note::TypeGivenHere);
+                self.errors.push(diagnostic.into());
+            }
+        }
+    }
+
+    /// What the variable read at `name` may hold there.
+    fn known(&self, name: &Ident) -> Option<Known> {
+        self.inference.of(name).and_then(|occurrence| occurrence.known)
+    }
+
+    /// Reports a value given to `name` that its annotation does not allow.
+    fn write(&mut self, name: &Ident) {
+        let given = self.inference.of(name).and_then(|occurrence| occurrence.given);
+        let (Some(annotation), Some(kind)) = (self.inference.annotation(&name.node), given) else {
+            return;
+        };
+        let declared = annotation.kind.node;
+        if let (Some(expected), Some(found), Some(fix)) = (declared.word(), kind.word(), Annotation::name_of(kind)) {
+            if declared != kind {
+                let diagnostic = Diagnostic::new(ErrorCode::AnnotationMismatch)
+                    .with_arg(&name.node)
+                    .with_word(expected)
+                    .with_word(found)
+                    .with_arg(fix)
+                    .with_span(name.span)
+                    .with_label(annotation.kind.span, Note::TypeGivenHere);
+                self.errors.push(diagnostic.into());
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::samples::SAMPLES;
+
+    /// The code of each error, with the source its span points at.
+    fn errors(source: &str) -> Vec<(ErrorCode, String)> {
+        let mut parser = Parser::new(Lexer::new(source).tokenize().unwrap());
+        let statements = parser.parse().unwrap();
+        match check(&statements, &Natives::stdlib()) {
+            Ok(()) => Vec::new(),
+            Err(error) => error
+                .errors()
+                .into_iter()
+                .map(|error| {
+                    let diagnostic = error.diagnostic().unwrap();
+                    let span = diagnostic.span.unwrap();
+                    (diagnostic.code, source[span.start..span.end].to_string())
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn infers_types_from_declarations_arithmetic_and_input() {
+        let source = "\
+oi mug bhan umer
+oi mug barsa = 2
+mug ghata umer, barsa lai baki
+mug jod umer, barsa lai sabai
+mug guna sabai, barsa lai dobar
+yedi barsa babaal \"2\" bhane
+    bol mug \"dui\"
+sakiyo
+mug lambai 5 lai n
+";
+        assert_eq!(
+            errors(source),
+            [
+                (ErrorCode::OperandType, "umer".to_string()),
+                (ErrorCode::OperandType, "sabai".to_string()),
+                (ErrorCode::NumberComparedToString, "barsa".to_string()),
+                (ErrorCode::ArgumentType, "mug lambai 5 lai n".to_string()),
+            ]
+        );
+        // The `guna` error points back at where `sabai` became a string
+        let mut parser = Parser::new(Lexer::new(source).tokenize().unwrap());
+        let error = check(&parser.parse().unwrap(), &Natives::stdlib()).unwrap_err();
+        let label = error.errors()[1].diagnostic().unwrap().labels[0].span;
+        assert_eq!(&source[label.start..label.end], "sabai");
+    }
+
+    #[test]
+    fn types_that_differ_between_paths_are_not_reported() {
+        let source = "\
+oi mug bhan rang
+oi mug x = 1
+yedi rang babaal \"rato\" bhane
+    oi mug x = \"ek\"
+sakiyo
+mug ghata x, x lai y
+mug sankhya rang lai z
+yedi z babaal \"1\" bhane
+sakiyo
+";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn annotations_fix_a_variables_type() {
+        let source = "\
+oi mug umer: sankhya = 18
+oi mug bhan naam: shabda
+oi mug kati: sankhya = \"tin\"
+mug jod umer, naam lai umer
+oi mug total: sankhya = 0
+mug sankhya naam lai total
+yedi total laamo \"0\" bhane
+sakiyo
+";
+        assert_eq!(
+            errors(source),
+            [
+                (ErrorCode::AnnotationMismatch, "kati".to_string()),
+                (ErrorCode::AnnotationMismatch, "umer".to_string()),
+                // A call's result may be anything, but `total` is declared a number
+                (ErrorCode::NumberComparedToString, "total".to_string()),
+            ]
+        );
+        for sample in SAMPLES {
+            assert_eq!(errors(sample), []);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Runtime values and the operations on them.
+//!
+//! The VM and the tree-walking interpreter both evaluate through this module,
+//! so they agree on every result and every error. Errors are returned without
+//! a span; each engine adds the location of the statement being run.
+
+use crate::ast;
+use crate::diagnostic::{Diagnostic, ErrorCode, Word};
+use std::fmt::{self, Write};
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// A value produced while running a program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(i32),
+    String(Str),
+    Boolean(bool),
+}
+
+/// The text of a string value. Clones share one buffer, so copying a value
+/// or pushing a constant never copies the text. The buffer is only changed
+/// in place when nothing else shares it; see [`BinaryOp::apply_owned`].
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Str(Rc<String>);
+
+impl Str {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `self` and `other` are clones of the same buffer.
+    pub fn shares(&self, other: &Str) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Appends `text`, copying the buffer first if it is shared.
+    fn append(&mut self, text: &Value) {
+        write!(Rc::make_mut(&mut self.0), "{}", text).expect("writing to a String cannot fail");
+    }
+}
+
+impl Deref for Str {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Str {
+    fn from(s: &str) -> Self {
+        Str(Rc::new(s.to_string()))
+    }
+}
+
+impl From<String> for Str {
+    fn from(s: String) -> Self {
+        Str(Rc::new(s))
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for Str {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Value {
+    /// Name of the value's type as shown in diagnostics
+    pub fn type_name(&self) -> Word {
+        match self {
+            Value::Number(_) => Word::Number,
+            Value::String(_) => Word::String,
+            Value::Boolean(_) => Word::Boolean,
+        }
+    }
+
+    /// Compares the way `babaal` does: strings ignore case, and values of
+    /// different types cannot be compared.
+    pub fn equals(&self, other: &Value) -> Result<bool, Diagnostic> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(a == b),
+            (Value::String(a), Value::String(b)) => Ok(a.to_lowercase() == b.to_lowercase()),
+            (Value::Boolean(a), Value::Boolean(b)) => Ok(a == b),
+            (a, b) => Err(Diagnostic::new(ErrorCode::InvalidComparison)
+                .with_word(a.type_name())
+                .with_word(b.type_name())),
+        }
+    }
+}
+
+impl From<ast::Value> for Value {
+    fn from(value: ast::Value) -> Self {
+        match value {
+            ast::Value::Number(n) => Value::Number(n),
+            ast::Value::String(s) => Value::String(s.into()),
+        }
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// An operation combining two values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    /// `+` between the segments of a string
+    Concat,
+}
+
+impl BinaryOp {
+    /// How the operation is written in LyangLang, e.g. `ghata`.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "jod",
+            BinaryOp::Subtract => "ghata",
+            BinaryOp::Multiply => "guna",
+            BinaryOp::Divide => "bhag",
+            BinaryOp::Concat => "+",
+        }
+    }
+
+    /// Applies the operation to `a` and `b`.
+    pub fn apply(self, a: &Value, b: &Value) -> Result<Value, Diagnostic> {
+        let result = match (self, a, b) {
+            (BinaryOp::Add, Value::Number(x), Value::Number(y)) => x.checked_add(*y),
+            (BinaryOp::Subtract, Value::Number(x), Value::Number(y)) => x.checked_sub(*y),
+            (BinaryOp::Multiply, Value::Number(x), Value::Number(y)) => x.checked_mul(*y),
+            (BinaryOp::Divide, Value::Number(_), Value::Number(0)) => {
+                return Err(Diagnostic::new(ErrorCode::DivisionByZero));
+            }
+            (BinaryOp::Divide, Value::Number(x), Value::Number(y)) => x.checked_div(*y),
+            (BinaryOp::Add | BinaryOp::Concat, Value::String(_), Value::String(_) | Value::Number(_))
+            | (BinaryOp::Add | BinaryOp::Concat, Value::Number(_), Value::String(_))
+            | (BinaryOp::Concat, Value::String(_), Value::Boolean(_))
+            | (BinaryOp::Concat, Value::Boolean(_), Value::String(_)) => {
+                return Ok(Value::String(format!("{}{}", a, b).into()));
+            }
+            _ => {
+                return Err(Diagnostic::new(ErrorCode::InvalidOperands)
+                    .with_arg(self.keyword())
+                    .with_word(a.type_name())
+                    .with_word(b.type_name()));
+            }
+        };
+        result
+            .map(Value::Number)
+            .ok_or_else(|| Diagnostic::new(ErrorCode::NumberOverflow).with_arg(self.keyword()))
+    }
+
+    /// Like [`apply`](Self::apply), but when the result is `a`'s text with
+    /// `b` appended it is written into `a`'s buffer, which is only copied if
+    /// it is shared. Joining onto a result that nothing else holds is then
+    /// amortized constant time, so building a string piece by piece is linear.
+    pub fn apply_owned(self, a: Value, b: &Value) -> Result<Value, Diagnostic> {
+        match a {
+            Value::String(mut text) if self.appends(b) => {
+                text.append(b);
+                Ok(Value::String(text))
+            }
+            a => self.apply(&a, b),
+        }
+    }
+
+    /// Whether the operation on a string and `b` appends `b`, which never fails.
+    pub fn appends(self, b: &Value) -> bool {
+        match self {
+            BinaryOp::Concat => true,
+            BinaryOp::Add => matches!(b, Value::String(_) | Value::Number(_)),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic_overflow_is_an_error() {
+        let err = BinaryOp::Add.apply(&Value::Number(i32::MAX), &Value::Number(1)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NumberOverflow);
+        let err = BinaryOp::Divide.apply(&Value::Number(i32::MIN), &Value::Number(-1)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NumberOverflow);
+    }
+
+    #[test]
+    fn jod_joins_strings_but_arithmetic_needs_numbers() {
+        let joined = BinaryOp::Add.apply(&Value::String("a".into()), &Value::Number(1)).unwrap();
+        assert_eq!(joined, Value::String("a1".into()));
+        let err = BinaryOp::Multiply.apply(&Value::String("a".into()), &Value::Number(1)).unwrap_err();
+        assert_eq!(err.message(), "cannot use `guna` on a string and a number");
+    }
+
+    #[test]
+    fn appending_copies_only_shared_text() {
+        let shared = Value::from("ab");
+        let joined = BinaryOp::Concat.apply_owned(shared.clone(), &Value::Boolean(true)).unwrap();
+        assert_eq!(joined, Value::from("abtrue"));
+        assert_eq!(shared, Value::from("ab"));
+
+        let Value::String(before) = joined.clone() else { unreachable!() };
+        drop(before);
+        let Value::String(after) = BinaryOp::Add.apply_owned(joined, &Value::Number(7)).unwrap() else { unreachable!() };
+        assert_eq!(after.as_str(), "abtrue7");
+
+        let err = BinaryOp::Add.apply_owned(Value::from("a"), &Value::Boolean(true)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidOperands);
+    }
+
+    #[test]
+    fn comparison_ignores_case_and_rejects_mixed_types() {
+        assert!(Value::String("Rato".into()).equals(&Value::String("rato".into())).unwrap());
+        let err = Value::Number(18).equals(&Value::String("18".into())).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidComparison);
+    }
+}
@@ -0,0 +1,173 @@
+//! Static checks on a [`BytecodeProgram`] before it runs.
+//!
+//! The VM checks each instruction as it executes, so a bad program can fail
+//! part way through after printing output or reading input. Programs loaded
+//! from `.nbc` files may not come from our compiler, so they are verified
+//! first: every jump target and table index must be valid, and every
+//! instruction must see the same stack depth on every path that reaches it.
+
+use crate::bytecode::{BytecodeProgram, Opcode};
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
+
+/// Checks `program` without running it. The error points at the source of
+/// the first offending instruction.
+pub fn verify(program: &BytecodeProgram) -> Result<(), Diagnostic> {
+    for (offset, instruction) in program.instructions.iter().enumerate() {
+        check_operands(program, offset, instruction.opcode)
+            .map_err(|diagnostic| diagnostic.with_span(instruction.span).with_note(Note::InternalError))?;
+    }
+    stack_depths(program).map(|_| ())
+}
+
+fn check_operands(program: &BytecodeProgram, offset: usize, opcode: Opcode) -> Result<(), Diagnostic> {
+    match opcode {
+        Opcode::PushString(index) if index >= program.string_pool.len() => {
+            Err(Diagnostic::new(ErrorCode::InvalidStringIndex).with_arg(index))
+        }
+        Opcode::PushVariable(index) | Opcode::StoreVariable(index) | Opcode::LoadVariable(index)
+            if index >= program.variable_names.len() =>
+        {
+            Err(Diagnostic::new(ErrorCode::InvalidVariableIndex).with_arg(index))
+        }
+        Opcode::CallNative(index, _) if index >= program.native_names.len() => {
+            Err(Diagnostic::new(ErrorCode::InvalidNativeIndex).with_arg(index))
+        }
+        Opcode::Jump(target) | Opcode::JumpIfTrue(target) | Opcode::JumpIfFalse(target)
+            if target >= program.instructions.len() =>
+        {
+            Err(Diagnostic::new(ErrorCode::InvalidJumpTarget)
+                .with_arg(offset)
+                .with_arg(target))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// How many values `opcode` pops and pushes.
+fn stack_effect(opcode: Opcode) -> (usize, usize) {
+    match opcode {
+        Opcode::PushNumber(_) | Opcode::PushString(_) | Opcode::PushVariable(_) | Opcode::LoadVariable(_) => (0, 1),
+        Opcode::Input => (0, 1),
+        Opcode::Pop | Opcode::StoreVariable(_) | Opcode::Print => (1, 0),
+        Opcode::JumpIfTrue(_) | Opcode::JumpIfFalse(_) => (1, 0),
+        Opcode::Add
+        | Opcode::Subtract
+        | Opcode::Multiply
+        | Opcode::Divide
+        | Opcode::Concat
+        | Opcode::Equal
+        | Opcode::NotEqual => (2, 1),
+        Opcode::CallNative(_, arg_count) => (arg_count, 1),
+        Opcode::Jump(_) | Opcode::Return | Opcode::Halt => (0, 0),
+    }
+}
+
+/// Follows every path from the first instruction, recording the stack depth
+/// each instruction starts with; unreachable instructions get `None`. Jump
+/// targets must already have been checked.
+pub fn stack_depths(program: &BytecodeProgram) -> Result<Vec<Option<usize>>, Diagnostic> {
+    let instructions = &program.instructions;
+    let mut depths: Vec<Option<usize>> = vec![None; instructions.len()];
+    let mut pending = Vec::new();
+    if !instructions.is_empty() {
+        depths[0] = Some(0);
+        pending.push(0);
+    }
+
+    while let Some(offset) = pending.pop() {
+        let instruction = &instructions[offset];
+        let depth = depths[offset].unwrap_or_default();
+        let (pops, pushes) = stack_effect(instruction.opcode);
+        if depth < pops {
+            return Err(Diagnostic::new(ErrorCode::StackUnderflow)
+                .with_span(instruction.span)
+                .with_note(Note::InternalError));
+        }
+        let after = depth - pops + pushes;
+
+        // Running off the end stops the program like `Halt`
+        for next in instruction.opcode.successors(offset).into_iter().filter(|&n| n < instructions.len()) {
+            match depths[next] {
+                None => {
+                    depths[next] = Some(after);
+                    pending.push(next);
+                }
+                Some(known) if known != after => {
+                    return Err(Diagnostic::new(ErrorCode::StackMismatch)
+                        .with_arg(next)
+                        .with_arg(known)
+                        .with_arg(after)
+                        .with_span(instructions[next].span)
+                        .with_note(Note::InternalError));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(depths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::samples::SAMPLES;
+    use crate::span::Span;
+
+    fn program(opcodes: &[Opcode]) -> BytecodeProgram {
+        let mut program = BytecodeProgram::new();
+        program.add_string("rato".to_string());
+        program.add_variable("rang".to_string());
+        for &opcode in opcodes {
+            program.add_instruction(opcode, Span::default());
+        }
+        program
+    }
+
+    #[test]
+    fn accepts_compiled_programs() {
+        for source in SAMPLES {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            let compiled = Compiler::new().compile(Parser::new(tokens).parse().unwrap()).unwrap();
+            verify(&compiled).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_bad_indexes_and_jumps() {
+        let err = verify(&program(&[Opcode::PushString(1), Opcode::Print])).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidStringIndex);
+        let err = verify(&program(&[Opcode::PushNumber(1), Opcode::StoreVariable(3)])).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidVariableIndex);
+        let err = verify(&program(&[Opcode::CallNative(0, 0)])).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidNativeIndex);
+        let err = verify(&program(&[Opcode::Jump(2), Opcode::Halt])).unwrap_err();
+        assert_eq!(err.message(), "instruction 0 jumps to 2, which is outside the program");
+    }
+
+    #[test]
+    fn checks_stack_depth_on_every_path() {
+        let err = verify(&program(&[Opcode::PushNumber(1), Opcode::Add])).unwrap_err();
+        assert_eq!(err.code, ErrorCode::StackUnderflow);
+
+        // The jump skips the push, so `Halt` is reached with 0 or 1 values
+        let branches = [
+            Opcode::LoadVariable(0),
+            Opcode::PushString(0),
+            Opcode::Equal,
+            Opcode::JumpIfFalse(5),
+            Opcode::PushNumber(1),
+            Opcode::Halt,
+        ];
+        let err = verify(&program(&branches)).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "the stack depth at instruction 5 is 0 on one path but 1 on another"
+        );
+
+        // Unreachable code is not checked, and loops are fine when balanced
+        verify(&program(&[Opcode::Jump(2), Opcode::Add, Opcode::PushNumber(1), Opcode::Pop, Opcode::Jump(2)])).unwrap();
+    }
+}
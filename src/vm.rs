@@ -1,85 +1,158 @@
 use crate::bytecode::{BytecodeProgram, Opcode};
+use crate::capability::{Capabilities, Capability};
+use crate::console::Console;
+use crate::diagnostic::{Diagnostic, ErrorCode, Note};
 use crate::error::NepalError;
-use std::io::{self, Write};
-
-/// Runtime value representation in the VM
-#[derive(Debug, Clone, PartialEq)]
-pub enum Value {
-    Number(i32),
-    String(String),
-    Boolean(bool),
-}
-
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Boolean(b) => write!(f, "{}", b),
-        }
-    }
-}
+use crate::limits::{Limits, Meter};
+use crate::native::Natives;
+use crate::value::{BinaryOp, Value};
 
 /// Virtual Machine for executing LyangLang bytecode
 pub struct VM {
     program: BytecodeProgram,
     ip: usize,                // Instruction pointer
     stack: Vec<Value>,        // Operand stack
-    variables: Vec<Value>,    // Variable storage
+    variables: Vec<Option<Value>>, // Variable storage, `None` until first assigned
+    strings: Vec<Value>,      // The string pool and variable names as values, so
+    names: Vec<Value>,        // pushing one shares its text instead of copying it
     running: bool,
+    console: Console,
+    natives: Natives,
+    capabilities: Capabilities,
+    meter: Meter,
+    hook: Option<Box<dyn DebugHook>>,
+}
+
+/// Lets a debugger watch a program run. The VM calls [`DebugHook::before`]
+/// before every instruction, so the hook can pause by not returning until
+/// the user wants to go on.
+pub trait DebugHook {
+    /// `vm` is about to run the instruction at [`VM::ip`].
+    fn before(&mut self, vm: &VM) -> Resume;
+}
+
+/// What the VM does after a [`DebugHook`] returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Run the instruction
+    Continue,
+    /// End the program here, as if it had halted
+    Stop,
 }
 
 impl VM {
     pub fn new(program: BytecodeProgram) -> Self {
         let var_count = program.variable_names.len();
-        Self {
-            program,
+        let mut vm = Self {
+            program: BytecodeProgram::new(),
             ip: 0,
             stack: Vec::new(),
-            variables: vec![Value::Number(0); var_count], // Initialize variables with default values
+            variables: vec![None; var_count],
+            strings: Vec::new(),
+            names: Vec::new(),
             running: false,
-        }
+            console: Console::stdio(),
+            natives: Natives::stdlib(),
+            capabilities: Capabilities::default(),
+            meter: Meter::new(Limits::default()),
+            hook: None,
+        };
+        vm.load(program);
+        vm
+    }
+
+    /// Switches to `program`, converting the strings it added to values.
+    fn load(&mut self, program: BytecodeProgram) {
+        let value = |s: &String| Value::from(s.as_str());
+        self.strings.extend(program.string_pool[self.strings.len()..].iter().map(value));
+        self.names.extend(program.variable_names[self.names.len()..].iter().map(value));
+        self.program = program;
+    }
+
+    /// Resolves native calls in `natives` instead of the standard library.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = natives;
+        self
+    }
+
+    /// Runs the program against `console` instead of standard input and output.
+    pub fn with_console(mut self, console: Console) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Lets the program do only what `capabilities` grants.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Stops the program with a `ResourceLimit` error if it goes over `limits`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.meter = Meter::new(limits);
+        self
+    }
+
+    /// Calls `hook` before every instruction.
+    pub fn with_debug_hook(mut self, hook: impl DebugHook + 'static) -> Self {
+        self.hook = Some(Box::new(hook));
+        self
     }
 
     /// Runs the bytecode program
     pub fn run(&mut self) -> Result<(), NepalError> {
+        // The hook is lent out while it looks at the VM
+        let mut hook = self.hook.take();
+        let result = self.execute(hook.as_deref_mut());
+        self.hook = hook;
+        result
+    }
+
+    fn execute(&mut self, mut hook: Option<&mut (dyn DebugHook + 'static)>) -> Result<(), NepalError> {
         self.running = true;
+        self.meter.restart();
         
         while self.running && self.ip < self.program.instructions.len() {
+            if let Some(hook) = hook.as_deref_mut() {
+                if hook.before(self) == Resume::Stop {
+                    self.running = false;
+                    break;
+                }
+            }
             let instruction = &self.program.instructions[self.ip];
+            let span = instruction.span;
+            if let Err(diagnostic) = self.meter.step() {
+                return Err(diagnostic.with_span(span).into());
+            }
             
             match instruction.opcode {
                 // Stack operations
                 Opcode::PushNumber(value) => {
-                    self.stack.push(Value::Number(value));
+                    self.push(Value::Number(value))?;
                     self.ip += 1;
                 },
                 
                 Opcode::PushString(index) => {
-                    if let Some(s) = self.program.string_pool.get(index) {
-                        self.stack.push(Value::String(s.clone()));
+                    if let Some(s) = self.strings.get(index) {
+                        self.push(s.clone())?;
                         self.ip += 1;
                     } else {
-                        return Err(NepalError::RuntimeError(
-                            "String constant index out of bounds"
-                        ));
+                        return Err(self.error(ErrorCode::InvalidStringIndex).with_arg(index).into());
                     }
                 },
                 
                 Opcode::PushVariable(index) => {
-                    if let Some(var_name) = self.program.variable_names.get(index) {
-                        self.stack.push(Value::String(var_name.clone()));
+                    if let Some(var_name) = self.names.get(index) {
+                        self.push(var_name.clone())?;
                         self.ip += 1;
                     } else {
-                        return Err(NepalError::RuntimeError(
-                            "Variable index out of bounds"
-                        ));
+                        return Err(self.error(ErrorCode::InvalidVariableIndex).with_arg(index).into());
                     }
                 },
                 
                 Opcode::Pop => {
                     if self.stack.pop().is_none() {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                        return Err(self.stack_underflow());
                     }
                     self.ip += 1;
                 },
@@ -88,187 +161,68 @@ impl VM {
                 Opcode::StoreVariable(index) => {
                     if let Some(value) = self.stack.pop() {
                         if index < self.variables.len() {
-                            self.variables[index] = value;
+                            if let Err(diagnostic) = self.meter.assign(self.variables[index].as_ref(), &value) {
+                                return Err(diagnostic.with_span(span).into());
+                            }
+                            self.variables[index] = Some(value);
                             self.ip += 1;
                         } else {
-                            return Err(NepalError::RuntimeError(
-                                "Variable index out of bounds"
-                            ));
+                            return Err(self.error(ErrorCode::InvalidVariableIndex).with_arg(index).into());
                         }
                     } else {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                        return Err(self.stack_underflow());
                     }
                 },
                 
                 Opcode::LoadVariable(index) => {
-                    if index < self.variables.len() {
-                        self.stack.push(self.variables[index].clone());
-                        self.ip += 1;
-                    } else {
-                        return Err(NepalError::RuntimeError(
-                            "Variable index out of bounds"
-                        ));
-                    }
-                },
-                
-                // Arithmetic operations
-                Opcode::Add => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            self.stack.push(Value::Number(a_val + b_val));
-                        },
-                        (Value::String(a_str), Value::String(b_str)) => {
-                            self.stack.push(Value::String(a_str + &b_str));
-                        },
-                        (Value::String(a_str), Value::Number(b_val)) => {
-                            self.stack.push(Value::String(a_str + &b_val.to_string()));
-                        },
-                        (Value::Number(a_val), Value::String(b_str)) => {
-                            self.stack.push(Value::String(a_val.to_string() + &b_str));
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot add these types"
-                            ));
+                    match self.variables.get(index) {
+                        Some(Some(value)) => {
+                            self.push(value.clone())?;
+                            self.ip += 1;
                         }
-                    }
-                    
-                    self.ip += 1;
-                },
-                
-                Opcode::Subtract => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            self.stack.push(Value::Number(a_val - b_val));
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot subtract these types"
-                            ));
+                        Some(None) => {
+                            let name = &self.program.variable_names[index];
+                            return Err(self.error(ErrorCode::UndefinedVariable).with_arg(name).into());
                         }
-                    }
-                    
-                    self.ip += 1;
-                },
-                
-                Opcode::Multiply => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            self.stack.push(Value::Number(a_val * b_val));
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot multiply these types"
-                            ));
+                        None => {
+                            return Err(self.error(ErrorCode::InvalidVariableIndex).with_arg(index).into());
                         }
                     }
-                    
-                    self.ip += 1;
                 },
                 
-                Opcode::Divide => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            if b_val == 0 {
-                                return Err(NepalError::RuntimeError("Division by zero"));
-                            }
-                            self.stack.push(Value::Number(a_val / b_val));
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot divide these types"
-                            ));
-                        }
-                    }
-                    
-                    self.ip += 1;
-                },
+                // Arithmetic operations
+                Opcode::Add => self.binary(BinaryOp::Add)?,
+                Opcode::Subtract => self.binary(BinaryOp::Subtract)?,
+                Opcode::Multiply => self.binary(BinaryOp::Multiply)?,
+                Opcode::Divide => self.binary(BinaryOp::Divide)?,
                 
                 // String operations
-                Opcode::Concat => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    let result = match (a, b) {
-                        (Value::String(a_str), Value::String(b_str)) => {
-                            Value::String(a_str + &b_str)
-                        },
-                        (Value::String(a_str), Value::Number(b_val)) => {
-                            Value::String(a_str + &b_val.to_string())
-                        },
-                        (Value::Number(a_val), Value::String(b_str)) => {
-                            Value::String(a_val.to_string() + &b_str)
-                        },
-                        (Value::String(a_str), Value::Boolean(b_val)) => {
-                            Value::String(a_str + &b_val.to_string())
-                        },
-                        (Value::Boolean(a_val), Value::String(b_str)) => {
-                            Value::String(a_val.to_string() + &b_str)
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot concatenate these types"
-                            ));
-                        }
-                    };
-                    
-                    self.stack.push(result);
-                    self.ip += 1;
-                },
+                Opcode::Concat => self.binary(BinaryOp::Concat)?,
                 
                 // I/O operations
                 Opcode::Print => {
                     if let Some(value) = self.stack.pop() {
-                        println!("{}", value);
+                        self.capabilities
+                            .check(&Capability::Stdout)
+                            .map_err(|diagnostic| self.locate(diagnostic))?;
+                        self.console
+                            .print_line(value)
+                            .map_err(|diagnostic| self.locate(diagnostic))?;
                         self.ip += 1;
                     } else {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                        return Err(self.stack_underflow());
                     }
                 },
                 
                 Opcode::Input => {
-                    let mut input = String::new();
-                    print!("> ");
-                    io::stdout().flush().map_err(|_| NepalError::RuntimeError("IO error"))?;
-                    io::stdin()
-                        .read_line(&mut input)
-                        .map_err(|_| NepalError::RuntimeError("Failed to read input"))?;
-                    
-                    // Trim newline character
-                    let input = input.trim().to_string();
-                    self.stack.push(Value::String(input));
+                    self.capabilities
+                        .check(&Capability::Stdin)
+                        .map_err(|diagnostic| self.locate(diagnostic))?;
+                    let input = self
+                        .console
+                        .read_line()
+                        .map_err(|diagnostic| self.locate(diagnostic))?;
+                    self.push(Value::from(input))?;
                     self.ip += 1;
                 },
                 
@@ -278,14 +232,15 @@ impl VM {
                         match condition {
                             Value::Boolean(true) => self.ip = address,
                             Value::Boolean(false) => self.ip += 1,
-                            _ => {
-                                return Err(NepalError::RuntimeError(
-                                    "Type error: Condition must be boolean"
-                                ));
+                            other => {
+                                return Err(self
+                                    .error(ErrorCode::ConditionNotBoolean)
+                                    .with_word(other.type_name())
+                                    .into());
                             }
                         }
                     } else {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                        return Err(self.stack_underflow());
                     }
                 },
                 
@@ -294,14 +249,15 @@ impl VM {
                         match condition {
                             Value::Boolean(false) => self.ip = address,
                             Value::Boolean(true) => self.ip += 1,
-                            _ => {
-                                return Err(NepalError::RuntimeError(
-                                    "Type error: Condition must be boolean"
-                                ));
+                            other => {
+                                return Err(self
+                                    .error(ErrorCode::ConditionNotBoolean)
+                                    .with_word(other.type_name())
+                                    .into());
                             }
                         }
                     } else {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                        return Err(self.stack_underflow());
                     }
                 },
                 
@@ -309,68 +265,26 @@ impl VM {
                     self.ip = address;
                 },
                 
-                // Comparison
-                Opcode::Equal => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
+                // Native functions
+                Opcode::CallNative(index, arg_count) => {
+                    if self.stack.len() < arg_count {
+                        return Err(self.stack_underflow());
                     }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    let result = match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            Value::Boolean(a_val == b_val)
-                        },
-                        (Value::String(a_str), Value::String(b_str)) => {
-                            Value::Boolean(
-                                a_str.to_lowercase() == b_str.to_lowercase(),
-                            )
-                        },
-                        (Value::Boolean(a_val), Value::Boolean(b_val)) => {
-                            Value::Boolean(a_val == b_val)
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot compare these types"
-                            ));
-                        }
+                    let Some(name) = self.program.native_names.get(index) else {
+                        return Err(self.error(ErrorCode::InvalidNativeIndex).with_arg(index).into());
                     };
-                    
-                    self.stack.push(result);
+                    let args = self.stack.split_off(self.stack.len() - arg_count);
+                    let result = self
+                        .natives
+                        .call(name, args, &self.capabilities)
+                        .map_err(|diagnostic| self.locate(diagnostic))?;
+                    self.push(result)?;
                     self.ip += 1;
                 },
                 
-                Opcode::NotEqual => {
-                    if self.stack.len() < 2 {
-                        return Err(NepalError::RuntimeError("Stack underflow"));
-                    }
-                    
-                    let b = self.stack.pop().unwrap();
-                    let a = self.stack.pop().unwrap();
-                    
-                    let result = match (a, b) {
-                        (Value::Number(a_val), Value::Number(b_val)) => {
-                            Value::Boolean(a_val != b_val)
-                        },
-                        (Value::String(a_str), Value::String(b_str)) => {
-                            Value::Boolean(
-                                a_str.to_lowercase() != b_str.to_lowercase(),
-                            )
-                        },
-                        (Value::Boolean(a_val), Value::Boolean(b_val)) => {
-                            Value::Boolean(a_val != b_val)
-                        },
-                        _ => {
-                            return Err(NepalError::RuntimeError(
-                                "Type error: Cannot compare these types"
-                            ));
-                        }
-                    };
-                    
-                    self.stack.push(result);
-                    self.ip += 1;
-                },
+                // Comparison
+                Opcode::Equal => self.compare(true)?,
+                Opcode::NotEqual => self.compare(false)?,
                 
                 // Program flow
                 Opcode::Return => {
@@ -382,11 +296,143 @@ impl VM {
                     self.running = false;
                 },
             }
+
         }
         
         Ok(())
     }
 
+    /// Pushes `value`, checking the stack limit. Only instructions that can
+    /// grow the stack need the check.
+    fn push(&mut self, value: Value) -> Result<(), NepalError> {
+        if let Err(diagnostic) = self.meter.stack(self.stack.len() + 1) {
+            return Err(self.locate(diagnostic));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Pops two operands and pushes the result of `op` on them.
+    fn binary(&mut self, op: BinaryOp) -> Result<(), NepalError> {
+        if self.stack.len() < 2 {
+            return Err(self.stack_underflow());
+        }
+
+        let b = self.stack.pop().unwrap();
+        let a = self.stack.pop().unwrap();
+        // In `s = s + x` the variable's reference to the old text would force
+        // a copy, and the variable is overwritten next anyway
+        if let (Value::String(text), Some(Opcode::StoreVariable(index))) = (&a, self.next_opcode()) {
+            if let Some(slot) = self.variables.get_mut(index) {
+                if matches!(slot, Some(Value::String(old)) if old.shares(text)) && op.appends(&b) {
+                    if let Some(old) = slot.take() {
+                        self.meter.release(&old);
+                    }
+                }
+            }
+        }
+        let result = op.apply_owned(a, &b).map_err(|diagnostic| self.locate(diagnostic))?;
+        self.stack.push(result);
+        self.ip += 1;
+        Ok(())
+    }
+
+    /// Pops two operands and pushes whether their equality is `expected`.
+    fn compare(&mut self, expected: bool) -> Result<(), NepalError> {
+        if self.stack.len() < 2 {
+            return Err(self.stack_underflow());
+        }
+
+        let b = self.stack.pop().unwrap();
+        let a = self.stack.pop().unwrap();
+        let equal = a.equals(&b).map_err(|diagnostic| self.locate(diagnostic))?;
+        self.stack.push(Value::Boolean(equal == expected));
+        self.ip += 1;
+        Ok(())
+    }
+
+    fn next_opcode(&self) -> Option<Opcode> {
+        self.program.instructions.get(self.ip + 1).map(|instruction| instruction.opcode)
+    }
+
+    /// A diagnostic of kind `code` pointing at the current instruction's source.
+    fn error(&self, code: ErrorCode) -> Diagnostic {
+        self.at_current(Diagnostic::new(code))
+    }
+
+    fn at_current(&self, diagnostic: Diagnostic) -> Diagnostic {
+        match self.program.instructions.get(self.ip) {
+            Some(instruction) => diagnostic.with_span(instruction.span),
+            None => diagnostic,
+        }
+    }
+
+    fn locate(&self, diagnostic: Diagnostic) -> NepalError {
+        self.at_current(diagnostic).into()
+    }
+
+    fn stack_underflow(&self) -> NepalError {
+        self.error(ErrorCode::StackUnderflow)
+            .with_note(Note::InternalError)
+            .into()
+    }
+
+    pub fn program(&self) -> &BytecodeProgram {
+        &self.program
+    }
+
+    /// Offset of the next instruction to run.
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// The operand stack, bottom first.
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Switches to `program`, which must be the current program with code
+    /// appended (see `Compiler::with_program`). Variables keep their values
+    /// and the next `run` starts at the new code.
+    pub fn extend(&mut self, program: BytecodeProgram) {
+        self.ip = self.program.instructions.len();
+        self.variables.resize(program.variable_names.len(), None);
+        self.load(program);
+        self.stack.clear();
+    }
+
+    /// The value of the variable `name`, if the program has assigned it.
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        let index = self.program.variable_names.lookup(name)?;
+        self.variables[index].as_ref()
+    }
+
+    /// Assigns `value` to `name` before the program runs. Returns `false` if
+    /// the program never mentions `name`.
+    pub fn set_global(&mut self, name: &str, value: Value) -> bool {
+        match self.program.variable_names.lookup(name) {
+            Some(index) => {
+                self.variables[index] = Some(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every assigned variable with its value.
+    pub fn globals(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.program
+            .variable_names
+            .iter()
+            .zip(&self.variables)
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)))
+    }
+
+    /// Gives back the console so it can be used for the next program.
+    pub fn into_console(self) -> Console {
+        self.console
+    }
+
     /// Gets the top value from the stack without removing it
     #[allow(dead_code)]
     pub fn peek(&self) -> Option<&Value> {
@@ -399,8 +445,9 @@ impl VM {
         self.ip = 0;
         self.stack.clear();
         self.running = false;
-        // Clear variables to default values
-        self.variables = vec![Value::Number(0); self.variables.len()];
+        // Forget every variable's value
+        self.variables = vec![None; self.variables.len()];
+        self.meter.clear_heap();
     }
 }
 
@@ -411,6 +458,12 @@ mod tests {
     use crate::ast::Statement;
     use crate::ast::Value as AstValue;
     use crate::compiler::Compiler;
+    use crate::span::Spanned;
+
+    fn compile(stmts: Vec<Statement>) -> BytecodeProgram {
+        let mut c = Compiler::new();
+        c.compile(stmts.into_iter().map(Spanned::from).collect()).unwrap()
+    }
 
     #[test]
     fn string_concat_vm_matches_literal_space() {
@@ -426,9 +479,7 @@ mod tests {
                 ],
             ),
         ];
-        let mut c = Compiler::new();
-        let program = c.compile(stmts).unwrap();
-        let mut vm = VM::new(program);
+        let mut vm = VM::new(compile(stmts));
         vm.run().unwrap();
         let idx = vm
             .program
@@ -436,7 +487,7 @@ mod tests {
             .iter()
             .position(|n| n == "fullName")
             .unwrap();
-        assert_eq!(vm.variables[idx], Value::String("Ram Bahadur".into()));
+        assert_eq!(vm.variables[idx], Some(Value::String("Ram Bahadur".into())));
     }
 
     #[test]
@@ -446,11 +497,98 @@ mod tests {
             Statement::Declaration("b".into(), AstValue::Number(4)),
             Statement::Multiplication("p".into(), vec!["a".into(), "b".into()]),
         ];
-        let mut c = Compiler::new();
-        let program = c.compile(stmts).unwrap();
-        let mut vm = VM::new(program);
+        let mut vm = VM::new(compile(stmts));
         vm.run().unwrap();
         let i = vm.program.variable_names.iter().position(|n| n == "p").unwrap();
-        assert_eq!(vm.variables[i], Value::Number(12));
+        assert_eq!(vm.variables[i], Some(Value::Number(12)));
+    }
+
+    #[test]
+    fn runtime_errors_point_at_statement() {
+        let source = "oi mug a = 1\noi mug b = 0\nmug bhag a, b lai c\n";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+        let mut vm = VM::new(Compiler::new().compile(stmts).unwrap());
+        let err = vm.run().unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.code, ErrorCode::DivisionByZero);
+        assert_eq!(diagnostic.span.unwrap().line, 3);
+    }
+
+    #[test]
+    fn type_errors_name_operand_types() {
+        let stmts = vec![
+            Statement::Declaration("a".into(), AstValue::String("x".into())),
+            Statement::Declaration("b".into(), AstValue::Number(2)),
+            Statement::Subtraction("c".into(), vec!["a".into(), "b".into()]),
+        ];
+        let err = VM::new(compile(stmts)).run().unwrap_err();
+        assert!(matches!(err, NepalError::TypeError(_)));
+        assert_eq!(
+            err.diagnostic().unwrap().message(),
+            "cannot use `ghata` on a string and a number"
+        );
+    }
+
+    #[test]
+    fn runs_against_scripted_console() {
+        let source = "oi mug bhan rang\nyedi rang babaal \"rato\" bhane\n  bol mug \"ramro \" + rang\nsakiyo\n";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+        let (console, output) = Console::memory("RATO\n");
+        let mut vm = VM::new(Compiler::new().compile(stmts).unwrap())
+            .with_console(console.with_prompt("rang? "))
+            .with_capabilities(Capabilities::console());
+        vm.run().unwrap();
+        assert_eq!(output.contents(), "rang? ramro RATO\n");
+    }
+
+    #[test]
+    fn refuses_what_was_not_granted() {
+        let source = "bol mug \"namaste\"\noi mug bhan naam\n";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+        let (console, output) = Console::memory("Sita\n");
+        let err = VM::new(Compiler::new().compile(stmts).unwrap())
+            .with_console(console)
+            .with_capabilities(Capabilities::none().with_stdout())
+            .run()
+            .unwrap_err();
+        let diagnostic = err.diagnostic().unwrap();
+        assert_eq!(diagnostic.message(), "permission denied: this needs the `stdin` capability");
+        assert_eq!(diagnostic.span.unwrap().line, 2);
+        assert_eq!(output.contents(), "namaste\n");
+    }
+
+    #[test]
+    fn stops_runaway_programs() {
+        let mut program = BytecodeProgram::new();
+        program.add_instruction(Opcode::PushNumber(1), crate::span::Span::default());
+        program.add_instruction(Opcode::Jump(0), crate::span::Span::default());
+
+        let err = VM::new(program.clone()).with_limits(Limits::new().with_max_steps(1_000)).run().unwrap_err();
+        assert!(matches!(err, NepalError::ResourceLimit(_)));
+        let err = VM::new(program.clone()).with_limits(Limits::new().with_max_stack(10)).run().unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().message(), "the program went over its limit of 10 stack slots");
+
+        let mut program = BytecodeProgram::new();
+        program.add_instruction(Opcode::Jump(0), crate::span::Span::default());
+        let timeout = Limits::new().with_timeout(std::time::Duration::from_millis(10));
+        let err = VM::new(program).with_limits(timeout).run().unwrap_err();
+        assert_eq!(err.diagnostic().unwrap().code, ErrorCode::ResourceLimit);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn calls_native_functions() {
+        let source = "oi mug naam = \"sita\"\nmug thulo_akshar naam lai thulo_naam\nmug lambai thulo_naam lai n\nbol mug thulo_naam\nbol mug n\n";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+        let (console, output) = Console::memory("");
+        VM::new(Compiler::new().compile(stmts).unwrap())
+            .with_console(console)
+            .with_capabilities(Capabilities::console())
+            .run()
+            .unwrap();
+        assert_eq!(output.contents(), "SITA\n4\n");
+    }
+}
@@ -0,0 +1,12 @@
+//! The sample programs in the repository, shared by the unit tests and
+//! the integration tests. Each test crate uses only some of these.
+#![allow(dead_code)]
+
+/// The sample programs, which every stage should handle.
+pub const SAMPLES: [&str; 2] = [include_str!("../../example.nbh"), include_str!("../../myapp/main.nbh")];
+
+/// Where [`SAMPLES`] are, relative to the crate root.
+pub const SAMPLE_PATHS: [&str; 2] = ["example.nbh", "myapp/main.nbh"];
+
+/// Console input that takes [`SAMPLES`] down each of their branches.
+pub const SAMPLE_INPUTS: [&str; 4] = ["Ram\n18\nrato\n", "Sita\n20\nNILO\nhariyo\n", "Hari\nthik cha\n", ""];
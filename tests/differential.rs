@@ -0,0 +1,258 @@
+//! Differential tests: every program must print the same output and report
+//! the same errors whether it runs on the tree-walking interpreter or is
+//! compiled and run on the stack or register VM, with or without
+//! optimization, and before and after `lyangpiler fmt`.
+
+mod common;
+
+use common::{SAMPLE_INPUTS, SAMPLE_PATHS};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Lines `run --vm` prints around the program's own output.
+const VM_BANNER: &str = "Running with Lyangpiler VM\n";
+const VM_DONE: &str = "Program execution completed.\n";
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    success: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Interpreter,
+    Vm,
+    OptimizedVm,
+    RegisterVm,
+    OptimizedRegisterVm,
+}
+
+fn run(file: &Path, mode: Mode, input: &str) -> Outcome {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lyangpiler"));
+    command.arg("run").arg(file).arg("--allow-stdin").arg("--allow-stdout");
+    match mode {
+        Mode::Interpreter => {}
+        Mode::Vm => {
+            command.arg("--vm");
+        }
+        Mode::OptimizedVm => {
+            command.arg("-O");
+        }
+        Mode::RegisterVm => {
+            command.arg("--registers");
+        }
+        Mode::OptimizedRegisterVm => {
+            command.arg("-O").arg("--registers");
+        }
+    }
+    let vm = mode != Mode::Interpreter;
+    let mut child = command
+        .env_remove("LYANGPILER_LANG")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start lyangpiler");
+    // The program may stop before reading all of its input.
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+
+    let mut stdout = String::from_utf8(output.stdout).unwrap();
+    if vm {
+        stdout = stdout.strip_prefix(VM_BANNER).expect("missing VM banner").to_string();
+        if output.status.success() {
+            stdout = stdout.strip_suffix(VM_DONE).expect("missing VM trailer").to_string();
+        }
+    }
+    Outcome {
+        stdout,
+        stderr: String::from_utf8(output.stderr).unwrap(),
+        success: output.status.success(),
+    }
+}
+
+/// Runs `file` in every mode and returns the shared outcome.
+fn assert_engines_agree(file: &Path, input: &str) -> Outcome {
+    let interpreted = run(file, Mode::Interpreter, input);
+    for mode in [Mode::Vm, Mode::OptimizedVm, Mode::RegisterVm, Mode::OptimizedRegisterVm] {
+        assert_eq!(
+            interpreted,
+            run(file, mode, input),
+            "{:?} disagrees with the interpreter on {} with input {:?}:\n{}",
+            mode,
+            file.display(),
+            input,
+            std::fs::read_to_string(file).unwrap_or_default()
+        );
+    }
+    interpreted
+}
+
+#[test]
+fn samples_behave_the_same_in_every_mode() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    for sample in SAMPLE_PATHS {
+        for input in SAMPLE_INPUTS {
+            let outcome = assert_engines_agree(&root.join(sample), input);
+            assert!(outcome.success, "{} failed:\n{}", sample, outcome.stderr);
+        }
+    }
+}
+
+#[test]
+fn bytecode_files_run_like_their_source() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("lyangpiler-nbc-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for sample in SAMPLE_PATHS {
+        let output = dir.join(Path::new(sample).with_extension("nbc").file_name().unwrap());
+        let status = Command::new(env!("CARGO_BIN_EXE_lyangpiler"))
+            .arg("build")
+            .arg(root.join(sample))
+            .arg("-o")
+            .arg(&output)
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "could not build {}", sample);
+        for input in SAMPLE_INPUTS {
+            let expected = run(&root.join(sample), Mode::Vm, input);
+            assert_eq!(run(&output, Mode::Vm, input), expected, "{}", sample);
+            assert_eq!(run(&output, Mode::RegisterVm, input), expected, "{}", sample);
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Builds random but syntactically valid programs.
+struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    /// `d` is never declared up front, so reading it may fail
+    const VARIABLES: [&'static str; 5] = ["a", "b", "c", "d", "naam"];
+    const NUMBERS: [&'static str; 6] = ["0", "1", "2", "7", "100", "2147483647"];
+    const STRINGS: [&'static str; 5] = ["rato", "Rato", "nilo", "", " "];
+    const OPERATIONS: [&'static str; 4] = ["jod", "ghata", "guna", "bhag"];
+    const FUNCTIONS: [&'static str; 4] = ["lambai", "thulo_akshar", "sano_akshar", "sankhya"];
+
+    fn next(&mut self) -> usize {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 16) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.next() % items.len()]
+    }
+
+    fn program(&mut self) -> String {
+        let mut source = String::new();
+        if !self.next().is_multiple_of(4) {
+            source.push_str("oi mug a = 7\noi mug b = 2\noi mug c = 0\noi mug naam = \"Rato\"\n");
+        }
+        for _ in 0..1 + self.next() % 10 {
+            self.statement(&mut source, 0);
+        }
+        if self.next().is_multiple_of(10) {
+            // Rejected by the compiler, so nothing may run
+            source.push_str("oi mug c = guna\n");
+        }
+        source
+    }
+
+    fn statement(&mut self, out: &mut String, depth: usize) {
+        let var = self.pick(&Self::VARIABLES);
+        let other = self.pick(&Self::VARIABLES);
+        let third = self.pick(&Self::VARIABLES);
+        let op = self.pick(&Self::OPERATIONS);
+        let number = self.pick(&Self::NUMBERS);
+        let string = self.pick(&Self::STRINGS);
+        let line = match self.next() % if depth == 0 { 15 } else { 13 } {
+            0 => format!("oi mug {} = {}", var, number),
+            1 => format!("oi mug {} = \"{}\"", var, string),
+            2 => format!("oi mug {} = {} + \"{}\" + {}", var, other, string, third),
+            3 => format!("oi mug {} = {} {}, {}", var, op, other, third),
+            4 => format!("mug {} {}, {} lai {}", op, other, third, var),
+            5 => format!("bol mug {}", var),
+            6 => format!("bol mug \"{}: \" + {}", string, var),
+            7 => format!("oi mug bhan {}", var),
+            8 => format!("oi mug {} = {} {}", var, op, other),
+            9 => format!("bol mug \"{}\"", string),
+            10 => format!("mug {} {} lai {}", self.pick(&Self::FUNCTIONS), other, var),
+            11 => format!("mug shesh {}, {} lai {}", other, number, var),
+            // Literal runs the optimizer folds
+            12 => format!("bol mug \"{}\" + \"-\" + {} + \"{}\" + \"!\"", string, other, string),
+            _ => {
+                let keyword = if self.next().is_multiple_of(3) { "aile feri" } else { "yedi" };
+                let comparison = if self.next().is_multiple_of(2) { "babaal" } else { "laamo" };
+                out.push_str(&format!("{} {} {} \"{}\" bhane\n", keyword, var, comparison, string));
+                for _ in 0..1 + self.next() % 3 {
+                    self.statement(out, depth + 1);
+                }
+                "sakiyo".to_string()
+            }
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+}
+
+#[test]
+fn generated_programs_behave_the_same_in_every_mode() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("lyangpiler-differential-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (mut succeeded, mut failed) = (0, 0);
+
+    for seed in [0x9e37_79b9_7f4a_7c15, 0xd1b5_4a32_d192_ed03, 0x8cb9_2ba7_2f3d_8dd7, 0x6a09_e667_f3bc_c909] {
+        let mut generator = Generator { state: seed };
+        for i in 0..150 {
+            let file = dir.join(format!("program{}.nbh", i));
+            let input = ["rato\n5\nNilo\n", "7\n", ""][i % 3];
+            std::fs::write(&file, generator.program()).unwrap();
+            let outcome = assert_engines_agree(&file, input);
+            if outcome.success {
+                succeeded += 1;
+            } else {
+                failed += 1;
+            }
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    // Make sure both the success and the error paths were compared.
+    assert!(succeeded > 40 && failed > 40, "{} succeeded, {} failed", succeeded, failed);
+}
+
+#[test]
+fn formatting_does_not_change_what_programs_do() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("lyangpiler-fmt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut generator = Generator { state: 0x2545_f491_4f6c_dd1d };
+    let lyangpiler = || {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_lyangpiler"));
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+        command
+    };
+
+    for i in 0..50 {
+        let (original, formatted) = (dir.join(format!("program{}.nbh", i)), dir.join(format!("formatted{}.nbh", i)));
+        let source = generator.program();
+        std::fs::write(&original, &source).unwrap();
+        std::fs::write(&formatted, &source).unwrap();
+        assert!(lyangpiler().arg("fmt").arg(&formatted).status().unwrap().success());
+        assert!(lyangpiler().arg("fmt").arg("--check").arg(&formatted).status().unwrap().success(), "not idempotent:\n{}", source);
+
+        // Error excerpts show the new indentation, so only compare what ran
+        let (before, after) = (run(&original, Mode::Interpreter, "rato\n"), run(&formatted, Mode::Interpreter, "rato\n"));
+        assert_eq!((before.stdout, before.success), (after.stdout, after.success), "{}", source);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}